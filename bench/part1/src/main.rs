@@ -1,3 +1,5 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::SystemTime;
 
 use kv::{Client, KVConfig, StorageType};
@@ -6,6 +8,30 @@ use rand::{prelude::ThreadRng, seq::SliceRandom, thread_rng, Rng};
 const SAMPLES: u128 = 128;
 const SIZES: u32 = 11;
 
+/// Global allocator counting the allocations made, so the benchmarks can report them.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
 /// Run the Put call benchmarks.
 /// # Arguments
 /// * `db` - A mutable ref to the `Client` DB.
@@ -30,7 +56,7 @@ fn put_bench(db: &mut Client) {
 /// Run the Get call benchmarks.
 /// # Arguments
 /// * `db` - A mutable ref to the `Client` DB.
-fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
+fn get_bench(db: &mut Client, valid_keys: &[i64]) {
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
         .choose_multiple(&mut r, SAMPLES as usize)
@@ -54,7 +80,7 @@ fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
 /// * `db` - A mutable ref to the `Client` DB.
 /// * `valid_keys` - A ref to a list of valid keys to scan from and to.
 /// * `range` - The range for the scan call.
-fn scan_bench(db: &mut Client, valid_keys: &Vec<i64>, range: i64) {
+fn scan_bench(db: &mut Client, valid_keys: &[i64], range: i64) {
     let half_range: i64 = range / 2;
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
@@ -75,6 +101,188 @@ fn scan_bench(db: &mut Client, valid_keys: &Vec<i64>, range: i64) {
     );
 }
 
+/// Run a wide Scan call benchmark over a DB of consecutive keys, reporting the allocations made by the scan.
+/// # Arguments
+/// * `mb` - The number of megabytes for the DB.
+/// * `scan_capacity` - The scan capacity of the DB.
+fn wide_scan_bench(mb: usize, scan_capacity: usize) {
+    let mut db: Client = Client::open(
+        format!("part_1_bench_wide_{}", scan_capacity).to_string(),
+        KVConfig::default()
+            .memtable_size(256 * 256)
+            .scan_capacity(scan_capacity)
+            .storage_type(StorageType::AppendOnlyLog)
+            .cleanup(true),
+    );
+    let num_keys: i64 = (mb * 256 * 256) as i64;
+    for key in 0..num_keys {
+        db.put(key, key);
+    }
+
+    let allocations: usize = ALLOCATIONS.load(Ordering::Relaxed);
+    let start: SystemTime = SystemTime::now();
+    let found: usize = db.scan(0, num_keys - 1).len();
+    let finish: u128 = start.elapsed().unwrap().as_nanos();
+    println!(
+        "SCAN of {} keys with scan capacity {} took {} nanoseconds and {} allocations",
+        found,
+        scan_capacity,
+        finish,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations
+    );
+}
+
+/// Run repeated Get calls over the pages of a few SSTs, reporting the SST files opened by the reads.
+/// # Arguments
+/// * `max_open_files` - The max open files of the DB.
+fn page_read_bench(max_open_files: usize) {
+    let mut db: Client = Client::open(
+        format!("part_1_bench_pages_{}", max_open_files).to_string(),
+        KVConfig::default()
+            .memtable_size(256 * 256)
+            .max_open_files(max_open_files)
+            .storage_type(StorageType::AppendOnlyLog)
+            .cleanup(true),
+    );
+    let num_keys: i64 = 4 * 256 * 256;
+    for key in 0..num_keys {
+        db.put(key, key);
+    }
+    // leave the memtable empty so every get reads a page
+    db.put(num_keys, num_keys);
+
+    let mut r: ThreadRng = thread_rng();
+    let opens: u64 = kv::file_opens();
+    let start: SystemTime = SystemTime::now();
+    for _ in 0..SAMPLES * 64 {
+        db.get(r.gen_range(0..num_keys));
+    }
+    let finish: u128 = start.elapsed().unwrap().as_nanos();
+    println!(
+        "GETs with max open files {} took {} nanoseconds ({} per get) and {} file opens",
+        max_open_files,
+        finish,
+        finish / (SAMPLES * 64),
+        kv::file_opens() - opens
+    );
+}
+
+/// Run repeated Get calls of keys still in the memtable, live and deleted, over a flushed SST holding them too.
+/// Neither kind reads the SST, so the time is the overhead of the get path over one memtable lookup.
+fn memtable_get_bench() {
+    let mut db: Client = Client::open(
+        "part_1_bench_memtable_gets".to_string(),
+        KVConfig::default()
+            .memtable_size(256 * 256)
+            .storage_type(StorageType::AppendOnlyLog)
+            .cleanup(true),
+    );
+    let num_keys: i64 = 256 * 256;
+    for key in 0..num_keys {
+        db.put(key, key);
+    }
+    // the first half of the keys overwritten and the second half deleted, all in the memtable
+    for key in 0..num_keys / 2 {
+        db.put(key, -key);
+    }
+    let deleted: Vec<i64> = (num_keys / 2..num_keys).collect();
+    db.delete_batch(&deleted);
+
+    let mut r: ThreadRng = thread_rng();
+    for (name, keys) in [
+        ("live", 0..num_keys / 2),
+        ("deleted", num_keys / 2..num_keys),
+    ] {
+        let numbers: Vec<i64> = (0..SAMPLES * 1024)
+            .map(|_| r.gen_range(keys.clone()))
+            .collect();
+        kv::reset_read_stats();
+        let start: SystemTime = SystemTime::now();
+        for a in numbers {
+            db.get(a);
+        }
+        let finish: u128 = start.elapsed().unwrap().as_nanos();
+        println!(
+            "{} GETs of {} memtable keys took {} nanoseconds ({} per get) and {} page reads",
+            SAMPLES * 1024,
+            name,
+            finish,
+            finish / (SAMPLES * 1024),
+            kv::read_stats().pages
+        );
+    }
+}
+
+/// Run repeated Get calls over SSTs of one page, searched directly, or of two pages, searched page by page.
+/// # Arguments
+/// * `memtable_size` - The memtable size of the DB, 256 entries fill exactly one page.
+fn single_page_get_bench(memtable_size: u32) {
+    let mut db: Client = Client::open(
+        format!("part_1_bench_single_page_{}", memtable_size).to_string(),
+        KVConfig::default()
+            .memtable_size(memtable_size)
+            .storage_type(StorageType::AppendOnlyLog)
+            .cleanup(true),
+    );
+    let num_keys: i64 = 64 * memtable_size as i64;
+    for key in 0..num_keys {
+        db.put(key, key);
+    }
+    // leave the memtable empty so every get reads a page
+    db.put(num_keys, num_keys);
+
+    let mut r: ThreadRng = thread_rng();
+    kv::reset_read_stats();
+    let start: SystemTime = SystemTime::now();
+    for _ in 0..SAMPLES * 64 {
+        db.get(r.gen_range(0..num_keys));
+    }
+    let finish: u128 = start.elapsed().unwrap().as_nanos();
+    println!(
+        "GETs over SSTs of {} entries took {} nanoseconds ({} per get) and {} page reads",
+        memtable_size,
+        finish,
+        finish / (SAMPLES * 64),
+        kv::read_stats().pages
+    );
+}
+
+/// Run repeated Update calls against Put calls of one key of a full memtable. Update replaces the value of the
+/// memtable node in place while Put goes through the insert and rebalance path.
+fn memtable_update_bench() {
+    let mut db: Client = Client::open(
+        "part_1_bench_memtable_updates".to_string(),
+        KVConfig::default()
+            .memtable_size(256 * 256)
+            .storage_type(StorageType::AppendOnlyLog)
+            .cleanup(true),
+    );
+    let num_keys: i64 = 256 * 256 - 1;
+    for key in 0..num_keys {
+        db.put(key, key);
+    }
+
+    let key: i64 = num_keys / 2;
+    for name in ["UPDATE", "PUT"] {
+        let start: SystemTime = SystemTime::now();
+        for value in 0..SAMPLES as i64 * 1024 {
+            if name == "UPDATE" {
+                db.update(key, value);
+            } else {
+                db.put(key, value);
+            }
+        }
+        let finish: u128 = start.elapsed().unwrap().as_nanos();
+        println!(
+            "{} {}s of a memtable key took {} nanoseconds ({} per call)",
+            SAMPLES * 1024,
+            name,
+            finish,
+            finish / (SAMPLES * 1024)
+        );
+    }
+}
+
 /// Insert data into the `Client` DB to get it ready for the benchmarks. Return a list of valid keys
 /// for some benchmarks that require the list.
 /// # Arguments
@@ -107,4 +315,20 @@ fn main() {
         scan_bench(&mut db, &valid_keys, 100);
         put_bench(&mut db);
     }
+
+    // no pre-allocation against the default scan capacity
+    wide_scan_bench(4, 0);
+    wide_scan_bench(4, 1 << 20);
+
+    // an open per page read against the default max open files
+    page_read_bench(0);
+    page_read_bench(64);
+
+    memtable_get_bench();
+
+    // SSTs of exactly one page against ones spilling a single entry onto a second page
+    single_page_get_bench(256);
+    single_page_get_bench(257);
+
+    memtable_update_bench();
 }
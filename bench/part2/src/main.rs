@@ -1,10 +1,11 @@
 use std::time::SystemTime;
 
-use kv::{Client, KVConfig, StorageType};
+use kv::{read_stats, reset_read_stats, Client, KVConfig, ReadStats, StorageType};
 use rand::{prelude::ThreadRng, seq::SliceRandom, thread_rng, Rng};
 
 const SAMPLES: u128 = 1024;
 const SIZES: u32 = 11;
+const READ_AMP_SIZES: u32 = 6;
 
 fn put_bench(db: &mut Client) {
     let mut r: ThreadRng = thread_rng();
@@ -24,7 +25,7 @@ fn put_bench(db: &mut Client) {
     );
 }
 
-fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
+fn get_bench(db: &mut Client, valid_keys: &[i64]) {
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
         .choose_multiple(&mut r, SAMPLES as usize)
@@ -43,7 +44,7 @@ fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
     );
 }
 
-fn scan_bench(db: &mut Client, valid_keys: &Vec<i64>, range: i64) {
+fn scan_bench(db: &mut Client, valid_keys: &[i64], range: i64) {
     let half_range: i64 = range / 2;
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
@@ -75,7 +76,145 @@ fn insert_data(db: &mut Client, mb: usize) -> Vec<i64> {
     ret
 }
 
+fn storage_name(storage_type: StorageType) -> &'static str {
+    match storage_type {
+        StorageType::AppendOnlyLog => "AppendOnlyLog",
+        StorageType::BTree => "BTree",
+        StorageType::LSMTree => "LSMTree",
+        StorageType::Null => "Null",
+        StorageType::Memory => "Memory",
+    }
+}
+
+// Runs `op` on each key and returns the average pages and SSTs read from disk per call, and the calls per second.
+fn measure_reads(
+    db: &mut Client,
+    keys: &[i64],
+    mut op: impl FnMut(&mut Client, i64),
+) -> (f64, f64, u128) {
+    let mut total: ReadStats = ReadStats::default();
+    let start: SystemTime = SystemTime::now();
+    for &key in keys {
+        reset_read_stats();
+        op(db, key);
+        let stats: ReadStats = read_stats();
+        total.pages += stats.pages;
+        total.ssts += stats.ssts;
+    }
+    let finish: u128 = start.elapsed().unwrap().as_nanos().max(1);
+    let calls: f64 = keys.len() as f64;
+    (
+        total.pages as f64 / calls,
+        total.ssts as f64 / calls,
+        keys.len() as u128 * 1_000_000_000 / finish,
+    )
+}
+
+fn read_amplification_bench(range: i64) {
+    let half_range: i64 = range / 2;
+    for i in 0..READ_AMP_SIZES {
+        println!("DB Size of {}MB:", 2_i64.pow(i));
+        println!(
+            "{:<14} {:>10} {:>10} {:>12} {:>11} {:>11} {:>13}",
+            "Storage",
+            "GET pages",
+            "GET SSTs",
+            "GETs / sec",
+            "SCAN pages",
+            "SCAN SSTs",
+            "SCANs / sec"
+        );
+        for storage_type in [
+            StorageType::AppendOnlyLog,
+            StorageType::BTree,
+            StorageType::LSMTree,
+        ] {
+            let mut db: Client = Client::open(
+                format!("part_2_read_amp_bench_{}", i),
+                KVConfig::default()
+                    .memtable_size(256 * 256)
+                    .storage_type(storage_type)
+                    .cleanup(true),
+            );
+            let valid_keys: Vec<i64> = insert_data(&mut db, 2_usize.pow(i));
+            let numbers: Vec<i64> = valid_keys
+                .choose_multiple(&mut thread_rng(), SAMPLES as usize)
+                .cloned()
+                .collect();
+
+            let (get_pages, get_ssts, gets) = measure_reads(&mut db, &numbers, |db, key| {
+                db.get(key);
+            });
+            let (scan_pages, scan_ssts, scans) = measure_reads(&mut db, &numbers, |db, key| {
+                db.scan(
+                    key.saturating_sub(half_range),
+                    key.saturating_add(half_range),
+                );
+            });
+            println!(
+                "{:<14} {:>10.2} {:>10.2} {:>12} {:>11.2} {:>11.2} {:>13}",
+                storage_name(storage_type),
+                get_pages,
+                get_ssts,
+                gets,
+                scan_pages,
+                scan_ssts,
+                scans
+            );
+        }
+    }
+}
+
+// Times random GETs of sequential keys from 0 on a BTree, without and then with the dense key hint.
+fn dense_keys_bench() {
+    for i in 0..SIZES {
+        println!("DB Size of {}MB:", 2_i64.pow(i));
+        let num_keys: i64 = 2_i64.pow(i) * 256 * 256;
+        for dense_keys in [None, Some(0)] {
+            let mut db: Client = Client::open(
+                format!("part_2_dense_bench_{}", i),
+                KVConfig::default()
+                    .memtable_size(256 * 256)
+                    .storage_type(StorageType::BTree)
+                    .dense_keys(dense_keys)
+                    .cleanup(true),
+            );
+            for key in 0..num_keys {
+                db.put(key, key);
+            }
+            let mut r: ThreadRng = thread_rng();
+            let numbers: Vec<i64> = (0..SAMPLES).map(|_| r.gen_range(0..num_keys)).collect();
+            let start: SystemTime = SystemTime::now();
+            for a in numbers {
+                db.get(a);
+            }
+            let finish: u128 = start.elapsed().unwrap().as_nanos().max(1);
+            println!(
+                "{} Dense GETs {} the hint took {} nanoseconds. Throughput of {} GETs / second",
+                SAMPLES,
+                if dense_keys.is_some() {
+                    "with"
+                } else {
+                    "without"
+                },
+                finish,
+                SAMPLES * 1_000_000_000 / finish
+            );
+        }
+    }
+}
+
 fn main() {
+    // `part2 read-amp` reports the pages and SSTs read per operation of each storage type instead
+    if std::env::args().nth(1).as_deref() == Some("read-amp") {
+        read_amplification_bench(100);
+        return;
+    }
+    // `part2 dense` compares the GETs of dense keys with and without the dense key hint instead
+    if std::env::args().nth(1).as_deref() == Some("dense") {
+        dense_keys_bench();
+        return;
+    }
     for i in 0..SIZES {
         let mut db: Client = Client::open(
             format!("part_2_bench_{}", i).to_string(),
@@ -24,7 +24,7 @@ fn put_bench(db: &mut Client) {
     );
 }
 
-fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
+fn get_bench(db: &mut Client, valid_keys: &[i64]) {
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
         .choose_multiple(&mut r, SAMPLES as usize)
@@ -43,7 +43,7 @@ fn get_bench(db: &mut Client, valid_keys: &Vec<i64>) {
     );
 }
 
-fn scan_bench(db: &mut Client, valid_keys: &Vec<i64>, range: i64) {
+fn scan_bench(db: &mut Client, valid_keys: &[i64], range: i64) {
     let half_range: i64 = range / 2;
     let mut r: ThreadRng = thread_rng();
     let numbers: Vec<i64> = valid_keys
@@ -0,0 +1,82 @@
+use twox_hash::xxh3::hash64_with_seed;
+
+/// The number of rows of counters of the `FrequencySketch`, each hashed with its own seed.
+const SKETCH_ROWS: usize = 4;
+/// The largest value of a counter, they are 4 bit counters kept in a byte each.
+const MAX_COUNT: u8 = 15;
+/// The number of recorded accesses, per page of the buffer capacity, after which every counter is halved.
+const SAMPLES_PER_PAGE: u64 = 10;
+
+/// Struct to represent a TinyLFU style count min sketch of how often pages were accessed recently. The `BufferPool`
+/// consults it to only admit a page over the one it would evict when the page was accessed more often.
+pub struct FrequencySketch {
+    /// The counters, `SKETCH_ROWS` rows of `width` counters each.
+    counters: Vec<u8>,
+    /// The number of counters per row, a power of two.
+    width: usize,
+    /// The number of accesses recorded since the counters were last halved.
+    samples: u64,
+    /// The number of accesses after which the counters are halved, so old popularity fades.
+    sample_size: u64,
+}
+
+// Implementation of `FrequencySketch`.
+impl FrequencySketch {
+    /// Creating a new `FrequencySketch` sized for a buffer of `capacity` pages, with all counters at zero.
+    /// # Arguments
+    /// * `capacity` - The number of pages of the buffer the sketch is for.
+    pub fn new(capacity: usize) -> Self {
+        let width: usize = (capacity.max(1) * 4).next_power_of_two();
+        FrequencySketch {
+            counters: vec![0; SKETCH_ROWS * width],
+            width,
+            samples: 0,
+            sample_size: capacity.max(1) as u64 * SAMPLES_PER_PAGE,
+        }
+    }
+
+    /// Helper function to return the index of the counter of `data` in each row.
+    /// # Arguments
+    /// * `self` - A ref to the `FrequencySketch`.
+    /// * `data` - The bytes identifying the page.
+    fn indexes(&self, data: &[u8]) -> [usize; SKETCH_ROWS] {
+        let mut indexes: [usize; SKETCH_ROWS] = [0; SKETCH_ROWS];
+        for (row, index) in indexes.iter_mut().enumerate() {
+            let hashed: u64 = hash64_with_seed(data, row as u64);
+            *index = row * self.width + (hashed as usize & (self.width - 1));
+        }
+        indexes
+    }
+
+    /// Function to record one access to the page identified by `data`. Halves every counter once `sample_size`
+    /// accesses were recorded.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `FrequencySketch`.
+    /// * `data` - The bytes identifying the page.
+    pub fn record(&mut self, data: &[u8]) {
+        for index in self.indexes(data) {
+            if self.counters[index] < MAX_COUNT {
+                self.counters[index] += 1;
+            }
+        }
+        self.samples += 1;
+        if self.samples >= self.sample_size {
+            for counter in self.counters.iter_mut() {
+                *counter /= 2;
+            }
+            self.samples /= 2;
+        }
+    }
+
+    /// Function to return the estimated number of recent accesses to the page identified by `data`.
+    /// # Arguments
+    /// * `self` - A ref to the `FrequencySketch`.
+    /// * `data` - The bytes identifying the page.
+    pub fn estimate(&self, data: &[u8]) -> u8 {
+        self.indexes(data)
+            .iter()
+            .map(|index| self.counters[*index])
+            .min()
+            .unwrap_or(0)
+    }
+}
@@ -101,6 +101,29 @@ impl LRUMain {
         self.add_node(node);
     }
 
+    /// The function called by `BufferPool::invalidate_sst` to take a node out of the `LRUMain` before it is evicted.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `LRUMain` object to update.
+    /// * `node` - A ref to the `LRUNode` to remove.
+    pub fn remove_node(&mut self, node: Rc<RefCell<LRUNode>>) {
+        let mut node_ref: RefMut<'_, LRUNode> = node.borrow_mut();
+        let prev_wrapped: Option<Rc<RefCell<LRUNode>>> = node_ref.prev.take();
+        let next_wrapped: Option<Rc<RefCell<LRUNode>>> = node_ref.next.take();
+
+        if let Some(prev) = &prev_wrapped {
+            let mut prev_ref: RefMut<'_, LRUNode> = prev.borrow_mut();
+            prev_ref.next = next_wrapped.clone();
+        } else {
+            self.back_q = next_wrapped.clone();
+        }
+        if let Some(next) = &next_wrapped {
+            let mut next_ref: RefMut<'_, LRUNode> = next.borrow_mut();
+            next_ref.prev = prev_wrapped.clone();
+        } else {
+            self.front_q = prev_wrapped.clone();
+        }
+    }
+
     /// The function called to perform inserting a node in the `LRUMain`.
     /// # Arguments
     /// * `self` - A mutable ref to the `LRUMain` object to update it with the new node.
@@ -132,8 +155,7 @@ impl LRUMain {
             data = old_front_ref.data.upgrade();
         }
         self.front_q = old_front.borrow_mut().prev.take();
-        if self.front_q.is_some() {
-            let new_front: &Rc<RefCell<LRUNode>> = self.front_q.as_ref().unwrap();
+        if let Some(new_front) = &self.front_q {
             let mut new_front_ref: RefMut<'_, LRUNode> = new_front.borrow_mut();
             new_front_ref.next = None;
         } else {
@@ -144,6 +166,16 @@ impl LRUMain {
         data
     }
 
+    /// The function called by `BufferPool::insert` to look at the `BufferNode` that `next_to_evict` would evict,
+    /// without evicting it.
+    /// # Arguments
+    /// * `self` - A ref to the `LRUMain` object.
+    pub fn peek_next_to_evict(&self) -> Option<Rc<RefCell<BufferNode>>> {
+        self.front_q
+            .as_ref()
+            .and_then(|front| front.borrow().data.upgrade())
+    }
+
     /// A helper function that returns if the `LRUMain` structure is empty. Return `true` if empty. `false` otherwise.
     fn is_empty(&self) -> bool {
         if self.front_q.is_none() != self.back_q.is_none() {
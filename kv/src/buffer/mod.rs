@@ -1,6 +1,9 @@
+mod admission;
 mod lru;
 
+use crate::buffer::admission::FrequencySketch;
 use crate::buffer::lru::{LRUMain, LRUNode};
+use crate::files;
 use crate::serde::deserialize_page;
 use std::{
     cell::{Ref, RefCell, RefMut},
@@ -36,10 +39,13 @@ pub struct BufferPool {
     size: usize,
     /// The current size of the `buffer`.
     curr_size: usize,
-    /// The buffer's hash representation.
+    /// The buffer's hash representation. Has `size` buckets unless shrunk by `shrink_to_fit`.
     buffer: Vec<Option<Rc<RefCell<BufferNode>>>>,
     /// The buffer's LRU representation (used to know order of eviction).
     lru: LRUMain,
+    /// The recent access frequencies of the pages, `None` to admit every page. When set, a page read into a full
+    /// buffer is only kept if it was accessed more often than the page it would evict.
+    admission: Option<FrequencySketch>,
 }
 
 /// Helper function to hash a `BufferKey` into a usize to know where it belongs in the `BufferPool`'s `buffer`.
@@ -47,7 +53,7 @@ pub struct BufferPool {
 /// * `key` - The `BufferKey` to hash.
 /// * `arr_size` - The max size of the buffer hash array to not overflow.
 fn custom_hash(key: &BufferKey, arr_size: usize) -> usize {
-    let combined: String = format!("{} {}", key.sst_name, key.page_offset);
+    let combined: String = key.combined();
     let data: &[u8] = combined.as_bytes();
     let hashed: u64 = hash64(data);
     (hashed % (arr_size as u64)) as usize
@@ -86,6 +92,11 @@ impl BufferKey {
             page_offset,
         }
     }
+
+    /// Returns the `sst_name` and `page_offset` combined into the string the key is hashed by.
+    fn combined(&self) -> String {
+        format!("{} {}", self.sst_name, self.page_offset)
+    }
 }
 
 // Special implementation of `BufferKey`. To check equality.
@@ -138,9 +149,42 @@ impl BufferPool {
             curr_size: 0,
             buffer: buf,
             lru: LRUMain::new(),
+            admission: None,
         }
     }
 
+    /// Setting whether pages read into a full buffer go through a TinyLFU style admission filter, so a page is only
+    /// kept over the one it would evict if it was accessed more often recently. One off pages, such as those of a
+    /// large scan, then do not evict the hot ones.
+    /// # Arguments
+    /// * `admission` - `true` to filter the admitted pages, `false` to admit every page.
+    pub fn with_admission(mut self, admission: bool) -> Self {
+        self.admission = admission.then(|| FrequencySketch::new(self.size));
+        self
+    }
+
+    /// Returns the largest number of pages the buffer holds.
+    pub fn capacity(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the `(sst_name, page_offset)` of every buffered page, in bucket then chain order, to see what is cached
+    /// when debugging eviction.
+    /// # Arguments
+    /// * `self` - A ref to the `BufferPool` object.
+    pub fn cached_keys(&self) -> Vec<(String, usize)> {
+        let mut keys: Vec<(String, usize)> = Vec::with_capacity(self.curr_size);
+        for bucket in self.buffer.iter() {
+            let mut curr_node: Option<Rc<RefCell<BufferNode>>> = bucket.clone();
+            while let Some(node) = curr_node {
+                let node_ref: Ref<'_, BufferNode> = node.borrow();
+                keys.push((node_ref.key.sst_name.clone(), node_ref.key.page_offset));
+                curr_node = node_ref.next.clone();
+            }
+        }
+        keys
+    }
+
     /// The primary function for outside functions that use a buffer to call. It will check the buffer for the requested data
     /// and if it is not found it will get it from storage and add it to itself before returning the data.
     /// # Arguments
@@ -149,6 +193,9 @@ impl BufferPool {
     /// * `page_offset` - The offset to find the requested page in the SST.
     pub fn find_page(&mut self, sst_name: &str, page_offset: usize) -> Vec<(i64, i64)> {
         let key: BufferKey = BufferKey::new(sst_name.to_string(), page_offset);
+        if let Some(sketch) = &mut self.admission {
+            sketch.record(key.combined().as_bytes());
+        }
 
         if let Some(page) = self.find_buffer_page(&key) {
             return page;
@@ -189,11 +236,18 @@ impl BufferPool {
     /// * `key` - The `BufferKey` to use in the insert for hashing.
     /// * `page` - The content of the new page to add to buffer.
     fn insert(&mut self, key: BufferKey, page: Vec<(i64, i64)>) {
+        if self.curr_size == self.size && !self.admits(&key) {
+            return;
+        }
         if self.curr_size == self.size && !self.run_eviction() {
             panic!("Eviction failed when attempting overflow insert!");
         }
+        // a shrunk buffer grows back as pages are added, up to one bucket per page
+        if self.curr_size >= self.buffer.len() {
+            self.rehash((self.buffer.len() * 2).clamp(1, self.size));
+        }
 
-        let hash: usize = custom_hash(&key, self.size);
+        let hash: usize = custom_hash(&key, self.buffer.len());
 
         let lru_node: Rc<RefCell<LRUNode>> = Rc::new(RefCell::new(LRUNode::new(Weak::new())));
         let new_node: Rc<RefCell<BufferNode>> =
@@ -219,16 +273,100 @@ impl BufferPool {
         self.curr_size += 1;
     }
 
+    /// The helper function called by `insert` when the buffer is full to check the admission filter. Returns `true`
+    /// when the page of `key` was accessed more often than the page that would be evicted for it, or when there is
+    /// no admission filter. `false` otherwise.
+    /// # Arguments
+    /// * `self` - A ref to the `BufferPool` object.
+    /// * `key` - The `BufferKey` of the page to admit.
+    fn admits(&self, key: &BufferKey) -> bool {
+        let Some(sketch) = &self.admission else {
+            return true;
+        };
+        match self.lru.peek_next_to_evict() {
+            Some(victim) => {
+                sketch.estimate(key.combined().as_bytes())
+                    > sketch.estimate(victim.borrow().key.combined().as_bytes())
+            }
+            None => true,
+        }
+    }
+
+    /// Function to drop every buffered page of `sst_name` and close its open file. Must be called when the SST is
+    /// removed or replaced, so its pages are not kept in the buffer.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `BufferPool` object for manipulation of the `buffer` and `lru`.
+    /// * `sst_name` - The name of the SST to drop the pages of.
+    pub fn invalidate_sst(&mut self, sst_name: &str) {
+        for hash in 0..self.buffer.len() {
+            let mut curr_node: Option<Rc<RefCell<BufferNode>>> = self.buffer[hash].clone();
+            while let Some(node) = curr_node {
+                curr_node = node.borrow().next.clone();
+                if node.borrow().key.sst_name != sst_name {
+                    continue;
+                }
+
+                let lru_node: Rc<RefCell<LRUNode>> = node.borrow().lru_node.upgrade().unwrap();
+                self.lru.remove_node(lru_node);
+                self.unlink_buffer_node(&mut node.borrow_mut());
+                self.curr_size -= 1;
+            }
+        }
+        files::forget_file(sst_name);
+    }
+
+    /// Function to release the memory kept from busier times, by rehashing the buffered pages into as many buckets as
+    /// there are pages. The pages stay buffered.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `BufferPool` object for manipulation of the `buffer`.
+    pub fn shrink_to_fit(&mut self) {
+        self.rehash(self.curr_size.max(1).min(self.size));
+    }
+
+    /// The helper function called by `insert` and `shrink_to_fit` to move every buffered page into a new `buffer` of
+    /// `buckets` buckets. The `lru` is left as is.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `BufferPool` object for manipulation of the `buffer`.
+    /// * `buckets` - The number of buckets of the new `buffer`.
+    fn rehash(&mut self, buckets: usize) {
+        let mut nodes: Vec<Rc<RefCell<BufferNode>>> = Vec::with_capacity(self.curr_size);
+        for bucket in self.buffer.iter_mut() {
+            let mut curr_node: Option<Rc<RefCell<BufferNode>>> = bucket.take();
+            while let Some(node) = curr_node {
+                {
+                    let mut node_ref: RefMut<'_, BufferNode> = node.borrow_mut();
+                    curr_node = node_ref.next.take();
+                    node_ref.prev = None;
+                }
+                nodes.push(node);
+            }
+        }
+
+        let mut buf: Vec<Option<Rc<RefCell<BufferNode>>>> = Vec::with_capacity(buckets);
+        for _ in 0..buckets {
+            buf.push(None);
+        }
+        for node in nodes {
+            let hash: usize = custom_hash(&node.borrow().key, buckets);
+            if let Some(old_root) = buf[hash].take() {
+                old_root.borrow_mut().prev = Some(node.clone());
+                node.borrow_mut().next = Some(old_root);
+            }
+            buf[hash] = Some(node);
+        }
+        self.buffer = buf;
+    }
+
     /// The helper function called by `find_buffer_page` to do the searching for the page in the buffer.
     /// # Arguments
     /// * `self` - A ref to the `BufferPool` object.
     /// * `key` - The `BufferKey` to find.
     fn search_buffer(&self, key: &BufferKey) -> Option<Rc<RefCell<BufferNode>>> {
-        if self.size == 0 {
+        if self.buffer.is_empty() {
             return None;
         }
 
-        let hash: usize = custom_hash(key, self.size);
+        let hash: usize = custom_hash(key, self.buffer.len());
 
         match &self.buffer[hash] {
             Some(node) => {
@@ -252,24 +390,13 @@ impl BufferPool {
                 {
                     let mut evict_node_ref: RefMut<'_, BufferNode> = evict_node.borrow_mut();
                     assert!(evict_node_ref.lru_node.upgrade().is_none());
-
-                    let prev_wrapped: Option<Rc<RefCell<BufferNode>>> = evict_node_ref.prev.take();
-                    let next_wrapped: Option<Rc<RefCell<BufferNode>>> = evict_node_ref.next.take();
-
-                    if let Some(prev) = &prev_wrapped {
-                        let mut prev_ref: RefMut<'_, BufferNode> = prev.borrow_mut();
-                        prev_ref.next = next_wrapped.clone();
-                    } else {
-                        let hash: usize = custom_hash(&evict_node_ref.key, self.size);
-                        self.buffer[hash] = next_wrapped.clone();
-                    }
-                    if let Some(next) = &next_wrapped {
-                        let mut next_ref: RefMut<'_, BufferNode> = next.borrow_mut();
-                        next_ref.prev = prev_wrapped.clone();
-                    }
-
-                    drop(prev_wrapped);
-                    drop(next_wrapped);
+                    self.unlink_buffer_node(&mut evict_node_ref);
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        sst = %evict_node_ref.key.sst_name,
+                        page_offset = evict_node_ref.key.page_offset,
+                        "evict"
+                    );
                 }
 
                 drop(evict_node);
@@ -279,6 +406,28 @@ impl BufferPool {
             None => false,
         }
     }
+
+    /// The helper function called by `run_eviction` and `invalidate_sst` to take a node out of its chain in the
+    /// `buffer`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `BufferPool` object for manipulation of the `buffer`.
+    /// * `node_ref` - A mutable ref to the `BufferNode` to take out.
+    fn unlink_buffer_node(&mut self, node_ref: &mut BufferNode) {
+        let prev_wrapped: Option<Rc<RefCell<BufferNode>>> = node_ref.prev.take();
+        let next_wrapped: Option<Rc<RefCell<BufferNode>>> = node_ref.next.take();
+
+        if let Some(prev) = &prev_wrapped {
+            let mut prev_ref: RefMut<'_, BufferNode> = prev.borrow_mut();
+            prev_ref.next = next_wrapped.clone();
+        } else {
+            let hash: usize = custom_hash(&node_ref.key, self.buffer.len());
+            self.buffer[hash] = next_wrapped.clone();
+        }
+        if let Some(next) = &next_wrapped {
+            let mut next_ref: RefMut<'_, BufferNode> = next.borrow_mut();
+            next_ref.prev = prev_wrapped.clone();
+        }
+    }
 }
 
 #[cfg(test)]
@@ -291,6 +440,9 @@ mod tests {
         };
 
         use crate::buffer::{BufferKey, BufferNode, BufferPool};
+        use crate::files::{file_opens, read_stats, reset_read_stats};
+        use crate::serde::{serialize_kv_to_file, PAGE_SIZE};
+        use std::fs::remove_dir_all;
 
         #[test]
         fn test_buffer_inserts_simple() {
@@ -301,7 +453,7 @@ mod tests {
 
             for i in 1..=num_inserts as usize {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -312,31 +464,27 @@ mod tests {
 
             assert_eq!(buffer.curr_size, 5);
 
-            for i in 0..=(buf_size - 1) as usize {
-                match buffer.buffer[i].take() {
-                    Some(node) => {
-                        let borrowed: Ref<'_, BufferNode> = node.borrow();
-                        let page: Vec<(i64, i64)> = borrowed.get_page_data();
+            for i in 0..buf_size {
+                if let Some(node) = buffer.buffer[i].take() {
+                    let borrowed: Ref<'_, BufferNode> = node.borrow();
+                    let page: Vec<(i64, i64)> = borrowed.get_page_data();
+                    if let Some(idx) = expected.iter().position(|x| *x == page) {
+                        expected.remove(idx);
+                    } else {
+                        panic!();
+                    }
+                    let mut curr_node: Option<Rc<RefCell<BufferNode>>> = borrowed.next.to_owned();
+                    while let Some(node) = curr_node {
+                        let unwrapped_node: Ref<'_, BufferNode> = node.borrow();
+                        let page: Vec<(i64, i64)> = unwrapped_node.get_page_data();
                         if let Some(idx) = expected.iter().position(|x| *x == page) {
                             expected.remove(idx);
                         } else {
-                            assert!(false);
-                        }
-                        let mut curr_node: Option<Rc<RefCell<BufferNode>>> =
-                            borrowed.next.to_owned();
-                        while let Some(node) = curr_node {
-                            let unwrapped_node: Ref<'_, BufferNode> = node.borrow();
-                            let page: Vec<(i64, i64)> = unwrapped_node.get_page_data();
-                            if let Some(idx) = expected.iter().position(|x| *x == page) {
-                                expected.remove(idx);
-                            } else {
-                                assert!(false);
-                            }
-
-                            curr_node = unwrapped_node.next.to_owned();
+                            panic!();
                         }
+
+                        curr_node = unwrapped_node.next.to_owned();
                     }
-                    _ => {}
                 }
             }
             assert!(expected.is_empty());
@@ -352,7 +500,7 @@ mod tests {
 
             for i in 1..=num_inserts {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -366,35 +514,31 @@ mod tests {
 
             assert_eq!(buffer.curr_size, buf_size);
 
-            for i in 0..=(buf_size - 1) as usize {
-                match buffer.buffer[i].take() {
-                    Some(node) => {
-                        let borrowed: Ref<'_, BufferNode> = node.borrow();
-                        let page: Vec<(i64, i64)> = borrowed.get_page_data();
+            for i in 0..buf_size {
+                if let Some(node) = buffer.buffer[i].take() {
+                    let borrowed: Ref<'_, BufferNode> = node.borrow();
+                    let page: Vec<(i64, i64)> = borrowed.get_page_data();
+                    if let Some(idx) = expected.iter().position(|x| *x == page) {
+                        expected.remove(idx);
+                    } else {
+                        panic!();
+                    }
+                    let mut curr_node: Option<Rc<RefCell<BufferNode>>> = borrowed.next.to_owned();
+                    while let Some(node) = curr_node {
+                        let unwrapped_node: Ref<'_, BufferNode> = node.borrow();
+                        let page: Vec<(i64, i64)> = unwrapped_node.get_page_data();
                         if let Some(idx) = expected.iter().position(|x| *x == page) {
                             expected.remove(idx);
                         } else {
-                            assert!(false);
-                        }
-                        let mut curr_node: Option<Rc<RefCell<BufferNode>>> =
-                            borrowed.next.to_owned();
-                        while let Some(node) = curr_node {
-                            let unwrapped_node: Ref<'_, BufferNode> = node.borrow();
-                            let page: Vec<(i64, i64)> = unwrapped_node.get_page_data();
-                            if let Some(idx) = expected.iter().position(|x| *x == page) {
-                                expected.remove(idx);
-                            } else {
-                                assert!(false);
-                            }
-
-                            curr_node = unwrapped_node.next.to_owned();
+                            panic!();
                         }
+
+                        curr_node = unwrapped_node.next.to_owned();
                     }
-                    _ => {}
                 }
             }
 
-            assert_eq!(expected.len(), (num_inserts - buf_size) as usize);
+            assert_eq!(expected.len(), num_inserts - buf_size);
 
             for _ in 1..=num_inserts - buf_size {
                 assert_eq!(expected.pop(), overflow.pop());
@@ -409,9 +553,9 @@ mod tests {
             let mut expected: Vec<Vec<(i64, i64)>> = Vec::new();
             let mut buffer: BufferPool = BufferPool::new(buf_size);
 
-            for i in 1..=num_inserts as usize {
+            for i in 1..=num_inserts {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -428,7 +572,7 @@ mod tests {
                 if let Some(page) = buffer.find_buffer_page(key) {
                     assert_eq!(page, expected[i]);
                 } else {
-                    assert!(false);
+                    panic!();
                 }
             }
 
@@ -446,9 +590,9 @@ mod tests {
             let mut expected: Vec<Vec<(i64, i64)>> = Vec::new();
             let mut buffer: BufferPool = BufferPool::new(buf_size);
 
-            for i in 1..=num_inserts as usize {
+            for i in 1..=num_inserts {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -463,14 +607,11 @@ mod tests {
             assert_eq!(buffer.curr_size, buf_size);
 
             for i in 0..=num_inserts - 1 {
-                let key: &BufferKey = &keys[i as usize];
+                let key: &BufferKey = &keys[i];
                 let ret = buffer.find_buffer_page(key);
                 if i > num_inserts - buf_size - 1 {
                     assert!(ret.is_some());
-                    assert_eq!(
-                        ret.unwrap(),
-                        expected[(i - (num_inserts - buf_size)) as usize]
-                    );
+                    assert_eq!(ret.unwrap(), expected[i - (num_inserts - buf_size)]);
                 } else {
                     assert_eq!(ret, None);
                 }
@@ -487,9 +628,9 @@ mod tests {
             let mut pages: Vec<Vec<(i64, i64)>> = Vec::new();
             let mut buffer: BufferPool = BufferPool::new(buf_size);
 
-            for i in 1..=buf_size as usize {
+            for i in 1..=buf_size {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -502,7 +643,7 @@ mod tests {
 
             assert_eq!(buffer.curr_size, buf_size);
 
-            for i in 0..=(num_search - 1) as usize {
+            for i in 0..num_search {
                 let key: &BufferKey = &keys[i];
                 let ret: Option<Vec<(i64, i64)>> = buffer.find_buffer_page(key);
                 assert!(ret.is_some());
@@ -515,7 +656,7 @@ mod tests {
 
             for i in buf_size + 1..=num_inserts as usize {
                 let mut page: Vec<(i64, i64)> = Vec::new();
-                for _ in 0..=10 as usize {
+                for _ in 0..=10_usize {
                     let num1 = i;
                     let num2 = i;
                     page.push((num1 as i64, num2 as i64));
@@ -528,7 +669,7 @@ mod tests {
             }
 
             assert_eq!(buffer.curr_size, buf_size);
-            assert_eq!(keys_expected.len(), buf_size as usize);
+            assert_eq!(keys_expected.len(), buf_size);
 
             for i in 0..=(num_inserts - 1) as usize {
                 let key: &BufferKey = &keys[i];
@@ -541,5 +682,178 @@ mod tests {
                 }
             }
         }
+
+        #[test]
+        fn test_buffer_invalidate_sst() {
+            let buf_size = 5;
+            let mut buffer: BufferPool = BufferPool::new(buf_size);
+
+            for i in 1..=buf_size {
+                let page: Vec<(i64, i64)> = vec![(i as i64, i as i64); 10];
+                buffer.insert(BufferKey::new(format!("sst{}", i % 2), i * 2), page);
+            }
+            assert_eq!(buffer.curr_size, buf_size);
+
+            buffer.invalidate_sst("sst1");
+            assert_eq!(buffer.curr_size, 2);
+            for i in 1..=buf_size {
+                let ret: Option<Vec<(i64, i64)>> =
+                    buffer.find_buffer_page(&BufferKey::new(format!("sst{}", i % 2), i * 2));
+                assert_eq!(ret.is_some(), i % 2 == 0);
+            }
+
+            // the LRU still evicts the oldest remaining pages first
+            for i in 6..=9 {
+                let page: Vec<(i64, i64)> = vec![(i as i64, i as i64); 10];
+                buffer.insert(BufferKey::new(format!("sst{}", i), i * 2), page);
+            }
+            assert_eq!(buffer.curr_size, buf_size);
+            assert!(buffer
+                .find_buffer_page(&BufferKey::new("sst0".to_string(), 4))
+                .is_none());
+            assert!(buffer
+                .find_buffer_page(&BufferKey::new("sst0".to_string(), 8))
+                .is_some());
+        }
+
+        #[test]
+        fn test_buffer_misses_open_file_once() {
+            let file_path: &str = "bufferTestDB1/output_0.bin";
+            let kv_vec: Vec<(i64, i64)> = (0..256 * 4).map(|i| (i, i)).collect();
+            serialize_kv_to_file(file_path, &kv_vec);
+
+            // a single page buffer, so every find is a miss
+            let mut buffer: BufferPool = BufferPool::new(1);
+            let opens: u64 = file_opens();
+            for i in 0..8 {
+                let page: Vec<(i64, i64)> = buffer.find_page(file_path, (i % 4) * PAGE_SIZE);
+                assert_eq!(page[0], kv_vec[(i % 4) * 256]);
+            }
+            assert_eq!(file_opens() - opens, 1);
+
+            buffer.invalidate_sst(file_path);
+            assert_eq!(buffer.curr_size, 0);
+            buffer.find_page(file_path, 0);
+            assert_eq!(file_opens() - opens, 2);
+
+            buffer.invalidate_sst(file_path);
+            remove_dir_all("bufferTestDB1").expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_buffer_shrink_to_fit() {
+            let file_paths: [&str; 2] =
+                ["bufferTestDB2/output_0.bin", "bufferTestDB2/output_1.bin"];
+            let kv_vec: Vec<(i64, i64)> = (0..256 * 32).map(|i| (i, i)).collect();
+            for file_path in file_paths {
+                serialize_kv_to_file(file_path, &kv_vec);
+            }
+
+            let mut buffer: BufferPool = BufferPool::new(64);
+            for file_path in file_paths {
+                for i in 0..32 {
+                    buffer.find_page(file_path, i * PAGE_SIZE);
+                }
+            }
+            assert_eq!(buffer.curr_size, 64);
+
+            // half of the pages are gone but the buffer keeps all of its buckets until shrunk
+            buffer.invalidate_sst(file_paths[0]);
+            assert_eq!(buffer.buffer.capacity(), 64);
+            buffer.shrink_to_fit();
+            assert_eq!(buffer.curr_size, 32);
+            assert_eq!(buffer.buffer.len(), 32);
+            assert!(buffer.buffer.capacity() < 64);
+
+            // the remaining pages are still buffered
+            reset_read_stats();
+            for i in 0..32 {
+                let page: Vec<(i64, i64)> = buffer.find_page(file_paths[1], i * PAGE_SIZE);
+                assert_eq!(page[0], kv_vec[i * 256]);
+            }
+            assert_eq!(read_stats().pages, 0);
+
+            // and the buffer grows back as pages are added
+            for i in 0..32 {
+                buffer.find_page(file_paths[0], i * PAGE_SIZE);
+            }
+            assert_eq!(buffer.curr_size, 64);
+            assert_eq!(buffer.buffer.len(), 64);
+            reset_read_stats();
+            buffer.find_page(file_paths[0], 0);
+            buffer.find_page(file_paths[1], 31 * PAGE_SIZE);
+            assert_eq!(read_stats().pages, 0);
+
+            buffer.invalidate_sst(file_paths[0]);
+            buffer.invalidate_sst(file_paths[1]);
+            remove_dir_all("bufferTestDB2").expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_buffer_cached_keys() {
+            let mut buffer: BufferPool = BufferPool::new(3);
+            assert!(buffer.cached_keys().is_empty());
+
+            for i in 1..=5_usize {
+                buffer.insert(
+                    BufferKey::new(format!("sst{}", i), i * 2),
+                    vec![(i as i64, 0)],
+                );
+            }
+
+            // the two oldest pages were evicted
+            let mut keys: Vec<(String, usize)> = buffer.cached_keys();
+            keys.sort();
+            assert_eq!(
+                keys,
+                vec![
+                    ("sst3".to_string(), 6),
+                    ("sst4".to_string(), 8),
+                    ("sst5".to_string(), 10)
+                ]
+            );
+
+            buffer.invalidate_sst("sst4");
+            let mut keys: Vec<(String, usize)> = buffer.cached_keys();
+            keys.sort();
+            assert_eq!(
+                keys,
+                vec![("sst3".to_string(), 6), ("sst5".to_string(), 10)]
+            );
+        }
+
+        #[test]
+        fn test_buffer_admission_keeps_hot_pages() {
+            let file_path: &str = "bufferTestDB3/output_0.bin";
+            let kv_vec: Vec<(i64, i64)> = (0..256 * 72).map(|i| (i, i)).collect();
+            serialize_kv_to_file(file_path, &kv_vec);
+
+            // returns the number of the 4 hot pages still buffered after a scan of the 64 other pages
+            let hot_pages_left = |admission: bool| -> usize {
+                let mut buffer: BufferPool = BufferPool::new(8).with_admission(admission);
+                for _ in 0..3 {
+                    for i in 0..4 {
+                        buffer.find_page(file_path, i * PAGE_SIZE);
+                    }
+                }
+                for i in 8..72 {
+                    let page: Vec<(i64, i64)> = buffer.find_page(file_path, i * PAGE_SIZE);
+                    assert_eq!(page[0], kv_vec[i * 256]);
+                }
+                assert_eq!(buffer.curr_size, 8);
+                let left: usize = buffer
+                    .cached_keys()
+                    .iter()
+                    .filter(|(_, offset)| *offset < 4 * PAGE_SIZE)
+                    .count();
+                buffer.invalidate_sst(file_path);
+                left
+            };
+
+            assert_eq!(hot_pages_left(false), 0);
+            assert_eq!(hot_pages_left(true), 4);
+
+            remove_dir_all("bufferTestDB3").expect("Remove dir all has failed!");
+        }
     }
 }
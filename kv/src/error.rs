@@ -0,0 +1,33 @@
+use std::{fmt, io};
+
+/// Enum of the errors returned by the `Client` DB.
+#[derive(Debug, PartialEq)]
+pub enum KvError {
+    /// The `KVConfig` holds a value that the DB cannot run with, and why.
+    InvalidConfig(String),
+    /// An argument passed to the `Client` cannot be used, and why.
+    InvalidArgument(String),
+    /// The storage type of the DB does not support the operation.
+    Unsupported(String),
+    /// The page at `offset` of SST `file` could not be read in full, or holds keys out of order.
+    CorruptPage { file: String, offset: usize },
+    /// The SST `file` could not be opened or read, with the `kind` of IO error, for example one removed during a read.
+    Io { file: String, kind: io::ErrorKind },
+}
+
+// Implementation of the `Display` trait for the `KvError`.
+impl fmt::Display for KvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KvError::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+            KvError::InvalidArgument(reason) => write!(f, "invalid argument: {}", reason),
+            KvError::Unsupported(operation) => write!(f, "unsupported operation: {}", operation),
+            KvError::CorruptPage { file, offset } => {
+                write!(f, "corrupt page at offset {} of {}", offset, file)
+            }
+            KvError::Io { file, kind } => write!(f, "IO error on {}: {}", file, kind),
+        }
+    }
+}
+
+impl std::error::Error for KvError {}
@@ -0,0 +1,305 @@
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+
+use crate::serde::{O_DIRECT, PAGE_SIZE};
+
+/// The default number of SST files kept open for reading.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// Struct of the SST reads done since the last `reset_read_stats`, to measure the read amplification of operations.
+/// Only reads from disk are counted, pages found in a buffer pool are not.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ReadStats {
+    /// The number of pages read.
+    pub pages: u64,
+    /// The number of distinct SST files read from.
+    pub ssts: u64,
+}
+
+/// Struct of the LRU of read only SST file handles. A `Client` is bound to the thread it was opened on, so one
+/// `OpenFiles` is kept per thread and shared by the `Client`s of that thread, each keeping its own limit on the
+/// files of its directory.
+struct OpenFiles {
+    /// The open files by path, with the time they were last used.
+    files: HashMap<String, (File, u64)>,
+    /// The largest number of files kept open by directory, 0 to open a file on every read. The directories without
+    /// one keep `DEFAULT_MAX_OPEN_FILES`.
+    limits: HashMap<String, usize>,
+    /// The logical time of the last read.
+    clock: u64,
+    /// The number of files opened so far.
+    opens: u64,
+    /// The number of pages read since the last `reset_read_stats`.
+    page_reads: u64,
+    /// The files read from since the last `reset_read_stats`.
+    files_read: HashSet<String>,
+}
+
+thread_local! {
+    static OPEN_FILES: RefCell<OpenFiles> = RefCell::new(OpenFiles::new());
+}
+
+/// Helper function to give the same key to `./db/file` and `db/file`.
+/// # Arguments
+/// * `file_path` - The path to normalize.
+fn normalize(file_path: &str) -> &str {
+    file_path.strip_prefix("./").unwrap_or(file_path)
+}
+
+/// Helper function to get the directory of a normalized `file_path`, the key of its limit.
+/// # Arguments
+/// * `file_path` - The normalized path to the file.
+fn dir_of(file_path: &str) -> &str {
+    file_path.rsplit_once('/').map_or("", |(dir, _)| dir)
+}
+
+// Implementation of the `OpenFiles`.
+impl OpenFiles {
+    /// Creating a new, empty `OpenFiles`.
+    fn new() -> Self {
+        Self {
+            files: HashMap::new(),
+            limits: HashMap::new(),
+            clock: 0,
+            opens: 0,
+            page_reads: 0,
+            files_read: HashSet::new(),
+        }
+    }
+
+    /// Function to get the largest number of files of the directory `dir` kept open.
+    /// # Arguments
+    /// * `self` - A ref to the `OpenFiles`.
+    /// * `dir` - The normalized path to the directory.
+    fn limit(&self, dir: &str) -> usize {
+        self.limits
+            .get(dir)
+            .copied()
+            .unwrap_or(DEFAULT_MAX_OPEN_FILES)
+    }
+
+    /// Function to close the least recently used files of the directory `dir` until at most `limit` are open.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `OpenFiles`.
+    /// * `dir` - The normalized path to the directory.
+    /// * `limit` - The number of files of `dir` that can stay open.
+    fn evict_to(&mut self, dir: &str, limit: usize) {
+        let mut open: Vec<(u64, String)> = self
+            .files
+            .iter()
+            .filter(|(path, _)| dir_of(path) == dir)
+            .map(|(path, (_, last_used))| (*last_used, path.clone()))
+            .collect();
+        if open.len() <= limit {
+            return;
+        }
+        open.sort_unstable();
+        for (_, path) in &open[..open.len() - limit] {
+            self.files.remove(path);
+        }
+    }
+
+    /// Function to fill `buf` with the bytes at `offset` of `file_path`, reusing an open handle if there is one.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `OpenFiles`.
+    /// * `file_path` - The path to the file.
+    /// * `buf` - The buffer to fill, aligned for `O_DIRECT`.
+    /// * `offset` - The offset in the file to read from, aligned for `O_DIRECT`.
+    fn read_exact_at(&mut self, file_path: &str, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        self.clock += 1;
+        let key: &str = normalize(file_path);
+        self.page_reads += (buf.len() as u64).div_ceil(PAGE_SIZE as u64);
+        if !self.files_read.contains(key) {
+            self.files_read.insert(key.to_string());
+        }
+
+        if let Some((file, last_used)) = self.files.get_mut(key) {
+            *last_used = self.clock;
+            return file.read_exact_at(buf, offset);
+        }
+
+        let file: File = OpenOptions::new()
+            .read(true)
+            .custom_flags(O_DIRECT) // libc::O_DIRECT
+            .open(file_path)?;
+        self.opens += 1;
+        let result: io::Result<()> = file.read_exact_at(buf, offset);
+
+        let limit: usize = self.limit(dir_of(key));
+        if limit > 0 {
+            self.evict_to(dir_of(key), limit - 1);
+            self.files.insert(key.to_string(), (file, self.clock));
+        }
+        result
+    }
+}
+
+/// Given `file_path`, fill `buf` with the bytes at `offset` using the open files of the current thread.
+/// # Arguments
+/// * `file_path` - The path to the file.
+/// * `buf` - The buffer to fill, aligned for `O_DIRECT`.
+/// * `offset` - The offset in the file to read from, aligned for `O_DIRECT`.
+pub fn read_exact_at(file_path: &str, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    OPEN_FILES.with(|files| files.borrow_mut().read_exact_at(file_path, buf, offset))
+}
+
+/// Setting the largest number of files of the directory `dir_path` the current thread keeps open for reading, closing
+/// the least recently used ones if needed. Kept until `forget_dir` is called on the directory.
+/// # Arguments
+/// * `dir_path` - The path to the directory.
+/// * `capacity` - The largest number of files kept open, 0 to open a file on every read.
+pub fn set_max_open_files(dir_path: &str, capacity: usize) {
+    let dir: &str = normalize(dir_path).trim_end_matches('/');
+    OPEN_FILES.with(|files| {
+        let mut files = files.borrow_mut();
+        files.limits.insert(dir.to_string(), capacity);
+        files.evict_to(dir, capacity);
+    });
+}
+
+/// Close the handle of `file_path` if it is open. Must be called when the file is removed or replaced.
+/// # Arguments
+/// * `file_path` - The path to the file.
+pub fn forget_file(file_path: &str) {
+    OPEN_FILES.with(|files| {
+        files.borrow_mut().files.remove(normalize(file_path));
+    });
+}
+
+/// Close the handles of all the files in the directory `dir_path` and drop its limit. Must be called when the
+/// directory is removed.
+/// # Arguments
+/// * `dir_path` - The path to the directory.
+pub fn forget_dir(dir_path: &str) {
+    let dir: &str = normalize(dir_path).trim_end_matches('/');
+    let prefix: String = format!("{}/", dir);
+    OPEN_FILES.with(|files| {
+        let mut files = files.borrow_mut();
+        files.files.retain(|path, _| !path.starts_with(&prefix));
+        files.limits.remove(dir);
+    });
+}
+
+/// Get the number of SST files opened for reading so far by the `Client`s of the current thread.
+pub fn file_opens() -> u64 {
+    OPEN_FILES.with(|files| files.borrow().opens)
+}
+
+/// Get the SST reads done by the `Client`s of the current thread since the last `reset_read_stats`.
+pub fn read_stats() -> ReadStats {
+    OPEN_FILES.with(|files| {
+        let files = files.borrow();
+        ReadStats {
+            pages: files.page_reads,
+            ssts: files.files_read.len() as u64,
+        }
+    })
+}
+
+/// Reset the SST reads counted by `read_stats` for the current thread, usually before each measured operation.
+pub fn reset_read_stats() {
+    OPEN_FILES.with(|files| {
+        let mut files = files.borrow_mut();
+        files.page_reads = 0;
+        files.files_read.clear();
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    mod open_files {
+        use crate::files::{
+            file_opens, forget_dir, forget_file, read_exact_at, read_stats, reset_read_stats,
+            set_max_open_files, ReadStats, DEFAULT_MAX_OPEN_FILES, OPEN_FILES,
+        };
+        use crate::serde::{deserialize_page, serialize_kv_to_file, PAGE_SIZE};
+        use std::fs::remove_dir_all;
+
+        fn create_files(folder_path: &str, count: usize) -> Vec<String> {
+            (0..count)
+                .map(|i| {
+                    let file_path: String = format!("{}/output_{}.bin", folder_path, i);
+                    let kv_vec: Vec<(i64, i64)> = (0..512).map(|j| (j, j + i as i64)).collect();
+                    serialize_kv_to_file(&file_path, &kv_vec);
+                    file_path
+                })
+                .collect()
+        }
+
+        #[test]
+        fn test_repeated_reads_reuse_handle() {
+            let files: Vec<String> = create_files("openFilesTestDB1", 1);
+
+            let opens: u64 = file_opens();
+            for _ in 0..10 {
+                assert_eq!(deserialize_page(&files[0], PAGE_SIZE)[0], (256, 256));
+            }
+            assert_eq!(file_opens() - opens, 1);
+
+            // a forgotten file is opened again on the next read
+            forget_file(&format!("./{}", files[0]));
+            deserialize_page(&files[0], 0);
+            assert_eq!(file_opens() - opens, 2);
+
+            forget_dir("openFilesTestDB1");
+            remove_dir_all("openFilesTestDB1").expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_least_recently_used_file_is_closed() {
+            let files: Vec<String> = create_files("openFilesTestDB2", 3);
+            set_max_open_files("openFilesTestDB2", 2);
+
+            let mut page = vec![0u8; 2 * PAGE_SIZE];
+            let opens: u64 = file_opens();
+            deserialize_page(&files[0], 0);
+            deserialize_page(&files[1], 0);
+            deserialize_page(&files[0], 0);
+            // closes files[1], files[0] was used more recently
+            deserialize_page(&files[2], 0);
+            deserialize_page(&files[0], 0);
+            assert_eq!(file_opens() - opens, 3);
+            deserialize_page(&files[1], 0);
+            assert_eq!(file_opens() - opens, 4);
+
+            // with no open files allowed every read opens the file
+            set_max_open_files("./openFilesTestDB2/", 0);
+            deserialize_page(&files[1], 0);
+            deserialize_page(&files[1], 0);
+            assert_eq!(file_opens() - opens, 6);
+
+            // reads past the end of the file are errors
+            assert!(
+                read_exact_at(&files[0], &mut page[..PAGE_SIZE], 4 * PAGE_SIZE as u64).is_err()
+            );
+
+            // the limit goes with the directory
+            forget_dir("openFilesTestDB2");
+            let limit: usize = OPEN_FILES.with(|open| open.borrow().limit("openFilesTestDB2"));
+            assert_eq!(DEFAULT_MAX_OPEN_FILES, limit);
+            remove_dir_all("openFilesTestDB2").expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_read_stats() {
+            let files: Vec<String> = create_files("openFilesTestDB3", 2);
+
+            reset_read_stats();
+            assert_eq!(ReadStats::default(), read_stats());
+            deserialize_page(&files[0], 0);
+            deserialize_page(&files[0], PAGE_SIZE);
+            deserialize_page(&format!("./{}", files[1]), 0);
+            assert_eq!(ReadStats { pages: 3, ssts: 2 }, read_stats());
+
+            reset_read_stats();
+            deserialize_page(&files[1], PAGE_SIZE);
+            assert_eq!(ReadStats { pages: 1, ssts: 1 }, read_stats());
+
+            forget_dir("openFilesTestDB3");
+            remove_dir_all("openFilesTestDB3").expect("Remove dir all has failed!");
+        }
+    }
+}
@@ -8,7 +8,9 @@ use twox_hash::XxHash64;
 
 const PAGE_SIZE: usize = 4096;
 const O_DIRECT: libc::c_int = 0x4000;
-const SEEDS: [u64; 10] = [
+/// The number of hashes of a key, each setting one bit of a filter.
+pub const NUM_SEEDS: usize = 10;
+const SEEDS: [u64; NUM_SEEDS] = [
     11798049322123270191,
     15539830439605854879,
     6578765718544580074,
@@ -21,17 +23,51 @@ const SEEDS: [u64; 10] = [
     7265912439666505101,
 ];
 
+/// Struct of the bloom filter outcomes of the gets of a DB, one per run whose filter was checked.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FilterStats {
+    /// The number of times a filter said the key is not in its run, so the run was not searched.
+    pub rejections: u64,
+    /// The number of times a filter said the key may be in its run, so the run was searched.
+    pub passes: u64,
+    /// The number of passes where the search did not find the key in the run.
+    pub false_positives: u64,
+}
+
+/// Given a per DB `seed`, return the seeds of the key hashes of its filters, the built in ones for `None`. Other
+/// seeds give other bits for the same keys, so the false positives of one DB cannot be worked out from another.
+/// # Arguments
+/// * `seed` - The seed of the DB, `None` for the built in seeds.
+pub fn bloom_seeds(seed: Option<u64>) -> [u64; NUM_SEEDS] {
+    let Some(seed) = seed else {
+        return SEEDS;
+    };
+    let mut seeds: [u64; NUM_SEEDS] = [0; NUM_SEEDS];
+    for (i, derived) in seeds.iter_mut().enumerate() {
+        let mut hasher = XxHash64::with_seed(seed);
+        i.hash(&mut hasher);
+        *derived = hasher.finish();
+    }
+    seeds
+}
+
 pub struct Bitmap {
     bits: Vec<u8>,
     size: u64,
+    seeds: [u64; NUM_SEEDS],
 }
 
 impl Bitmap {
     pub fn new(size: u64) -> Self {
-        let vec_size = (size + 7) / 8; // in bytes, ceil
+        Self::with_seeds(size, SEEDS)
+    }
+
+    pub fn with_seeds(size: u64, seeds: [u64; NUM_SEEDS]) -> Self {
+        let vec_size = size.div_ceil(8); // in bytes, ceil
         Bitmap {
             bits: vec![0; vec_size as usize],
             size,
+            seeds,
         }
     }
 
@@ -61,7 +97,7 @@ pub trait BloomFilter {
 
 impl BloomFilter for Bitmap {
     fn insert_key(&mut self, key: i64) {
-        for seed in SEEDS {
+        for seed in self.seeds {
             let mut hasher = XxHash64::with_seed(seed);
             key.hash(&mut hasher);
             let bit_idx = hasher.finish() % self.size;
@@ -72,7 +108,7 @@ impl BloomFilter for Bitmap {
     }
 
     fn check_key(&self, key: i64) -> bool {
-        for seed in SEEDS {
+        for seed in self.seeds {
             let mut hasher = XxHash64::with_seed(seed);
             key.hash(&mut hasher);
             let bit_idx = hasher.finish() % self.size;
@@ -103,8 +139,8 @@ fn serialize_filter(filename: &str, leaf_lst: &Vec<(i64, i64)>, bits_per_entry:
     // 16 bytes of metadata: bitmap_size in bits (u64) + start page idx of btree(u64)
     let mut bytes: Vec<u8> = Vec::new();
     bytes.extend_from_slice(&(bitmap.size).to_be_bytes()); // 8 bytes
-    let in_byte_size = (bitmap.size + 7) / 8; // in bytes, ceil
-    let btree_idx = ((16 + in_byte_size) + PAGE_SIZE as u64 - 1) / PAGE_SIZE as u64; // ceil
+    let in_byte_size = bitmap.size.div_ceil(8); // in bytes, ceil
+    let btree_idx = (16 + in_byte_size).div_ceil(PAGE_SIZE as u64); // ceil
     bytes.extend_from_slice(&(btree_idx).to_be_bytes()); // 8 bytes
 
     // bitmap
@@ -112,7 +148,7 @@ fn serialize_filter(filename: &str, leaf_lst: &Vec<(i64, i64)>, bits_per_entry:
 
     // pad rest of page with 0s
     let mut padding_size = 0;
-    if bytes.len() % PAGE_SIZE != 0 {
+    if !bytes.len().is_multiple_of(PAGE_SIZE) {
         padding_size = PAGE_SIZE - (bytes.len() % PAGE_SIZE);
     }
     let padding = vec![0; padding_size];
@@ -145,16 +181,18 @@ fn deserialize_filter(filename: &str) -> (Bitmap, usize) {
     file.read_exact(&mut bytes)
         .expect("Filter Deserializer: file exact read failed!");
     let btree_idx = u64::from_be_bytes(bytes.clone().try_into().unwrap());
-    let in_byte_size: usize = ((bitmap_size + 7) / 8) as usize; // in bytes, ceil
+    let in_byte_size: usize = bitmap_size.div_ceil(8) as usize; // in bytes, ceil
 
     let mut bitmap_bytes = vec![0u8; in_byte_size];
     file.read_exact(&mut bitmap_bytes)
         .expect("Filter Deserializer: file exact read failed!");
 
     // 2. construct Bitmap
+    // serialized filters are built with the built in seeds, see `construct_filter`
     let bitmap = Bitmap {
         size: bitmap_size,
         bits: bitmap_bytes,
+        seeds: SEEDS,
     };
 
     (bitmap, btree_idx as usize)
@@ -163,7 +201,8 @@ fn deserialize_filter(filename: &str) -> (Bitmap, usize) {
 #[cfg(test)]
 mod tests {
     use crate::filter::{
-        construct_filter, /*deserialize_filter, serialize_filter,*/ Bitmap, BloomFilter,
+        bloom_seeds, construct_filter, /*deserialize_filter, serialize_filter,*/ Bitmap,
+        BloomFilter,
     };
     // use std::fs::{create_dir_all, remove_dir_all};
 
@@ -187,9 +226,26 @@ mod tests {
         for i in 0..=511 {
             lst.push((i, i));
         }
-        let filter: Bitmap = construct_filter(&lst, &(10 as u8));
-        assert!(filter.check_key(299 as i64));
-        assert!(!filter.check_key(513 as i64));
+        let filter: Bitmap = construct_filter(&lst, &10_u8);
+        assert!(filter.check_key(299_i64));
+        assert!(!filter.check_key(513_i64));
+    }
+
+    #[test]
+    fn test_filter_seeds() {
+        assert_eq!(bloom_seeds(None), Bitmap::new(8).seeds);
+        let mut filters: Vec<Bitmap> = [Some(1), Some(2)]
+            .into_iter()
+            .map(|seed| Bitmap::with_seeds(512 * 10, bloom_seeds(seed)))
+            .collect();
+        for filter in &mut filters {
+            for key in 0..512 {
+                filter.insert_key(key);
+            }
+            assert!((0..512).all(|key| filter.check_key(key)));
+        }
+        assert_ne!(filters[0].bits, filters[1].bits);
+        assert_ne!(bloom_seeds(Some(1)), bloom_seeds(None));
     }
 
     // #[test]
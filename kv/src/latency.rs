@@ -0,0 +1,198 @@
+use std::time::Duration;
+
+/// The number of bits of each latency kept below its highest set bit, so every bucket is at most 1/8 wide.
+const SUB_BUCKET_BITS: u32 = 3;
+
+/// The number of sub-buckets each power of two is split into.
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+
+/// The number of buckets needed to hold any `u64` number of nanoseconds.
+const BUCKETS: usize = ((64 - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS) as usize;
+
+/// Helper function to find the bucket of a latency of `nanos` nanoseconds. Latencies below `SUB_BUCKETS` get a bucket
+/// of their own, the others go to one of the `SUB_BUCKETS` equal parts of their power of two.
+/// # Arguments
+/// * `nanos` - The latency in nanoseconds.
+fn bucket_index(nanos: u64) -> usize {
+    if nanos < SUB_BUCKETS {
+        return nanos as usize;
+    }
+    let exponent: u32 = 63 - nanos.leading_zeros();
+    let sub_bucket: u64 = (nanos >> (exponent - SUB_BUCKET_BITS)) & (SUB_BUCKETS - 1);
+    ((exponent - SUB_BUCKET_BITS + 1) as u64 * SUB_BUCKETS + sub_bucket) as usize
+}
+
+/// Helper function to find the largest latency in nanoseconds that goes to the bucket at `index`.
+/// # Arguments
+/// * `index` - The index of the bucket.
+fn bucket_upper_bound(index: usize) -> u64 {
+    let index: u64 = index as u64;
+    if index < SUB_BUCKETS {
+        return index;
+    }
+    let exponent: u64 = index / SUB_BUCKETS + SUB_BUCKET_BITS as u64 - 1;
+    let width: u64 = 1 << (exponent - SUB_BUCKET_BITS as u64);
+    (SUB_BUCKETS + index % SUB_BUCKETS) * width + (width - 1)
+}
+
+/// Struct of a histogram of latencies with fixed buckets, a few of them per power of two like an HDR histogram, so
+/// recording is a single increment and percentiles are off by at most 1/8.
+pub struct LatencyHistogram {
+    /// The number of latencies recorded in each bucket.
+    buckets: Vec<u64>,
+    /// The number of latencies recorded.
+    count: u64,
+}
+
+// Implementation of the `LatencyHistogram`.
+impl LatencyHistogram {
+    /// Creating a new empty `LatencyHistogram`.
+    pub fn new() -> Self {
+        LatencyHistogram {
+            buckets: vec![0; BUCKETS],
+            count: 0,
+        }
+    }
+
+    /// Function to record one `elapsed` latency.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `LatencyHistogram`.
+    /// * `elapsed` - The latency to record.
+    pub fn record(&mut self, elapsed: Duration) {
+        let nanos: u64 = elapsed.as_nanos().min(u64::MAX as u128) as u64;
+        self.buckets[bucket_index(nanos)] += 1;
+        self.count += 1;
+    }
+
+    /// Function to return the latency that `percentile` percent of the recorded ones are at or below, rounded up to
+    /// the end of its bucket. `Duration::ZERO` if nothing was recorded.
+    /// # Arguments
+    /// * `self` - A ref to the `LatencyHistogram`.
+    /// * `percentile` - The percentile, from 0 to 100.
+    pub fn percentile(&self, percentile: f64) -> Duration {
+        if self.count == 0 {
+            return Duration::ZERO;
+        }
+        let rank: u64 =
+            ((percentile / 100.0 * self.count as f64).ceil() as u64).clamp(1, self.count);
+
+        let mut seen: u64 = 0;
+        for (index, &bucket_count) in self.buckets.iter().enumerate() {
+            seen += bucket_count;
+            if seen >= rank {
+                return Duration::from_nanos(bucket_upper_bound(index));
+            }
+        }
+        unreachable!("LatencyHistogram: rank {} above the count!", rank)
+    }
+
+    /// Function to return the `OpLatency` summary of the recorded latencies.
+    /// # Arguments
+    /// * `self` - A ref to the `LatencyHistogram`.
+    pub fn summary(&self) -> OpLatency {
+        OpLatency {
+            count: self.count,
+            p50: self.percentile(50.0),
+            p99: self.percentile(99.0),
+        }
+    }
+}
+
+// Special default `LatencyHistogram` implementation.
+impl Default for LatencyHistogram {
+    /// The default `LatencyHistogram` implementation.
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Struct of the latencies of one `Client` operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OpLatency {
+    /// The number of operations timed.
+    pub count: u64,
+    /// The median latency.
+    pub p50: Duration,
+    /// The latency 99% of the operations are at or below.
+    pub p99: Duration,
+}
+
+/// Struct of the latencies of the gets, puts and scans of a `Client` since it was opened.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LatencyReport {
+    /// The latencies of `get` and `try_get`.
+    pub get: OpLatency,
+    /// The latencies of `put`, flushes included.
+    pub put: OpLatency,
+    /// The latencies of `scan` and `try_scan`.
+    pub scan: OpLatency,
+}
+
+/// Struct of the histograms a `Client` records its operations in.
+#[derive(Default)]
+pub struct Latencies {
+    /// The histogram of the gets.
+    pub get: LatencyHistogram,
+    /// The histogram of the puts.
+    pub put: LatencyHistogram,
+    /// The histogram of the scans.
+    pub scan: LatencyHistogram,
+}
+
+// Implementation of the `Latencies`.
+impl Latencies {
+    /// Function to return the `LatencyReport` of the recorded operations.
+    /// # Arguments
+    /// * `self` - A ref to the `Latencies`.
+    pub fn report(&self) -> LatencyReport {
+        LatencyReport {
+            get: self.get.summary(),
+            put: self.put.summary(),
+            scan: self.scan.summary(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod latency {
+        use crate::latency::{bucket_index, bucket_upper_bound, LatencyHistogram, BUCKETS};
+        use std::time::Duration;
+
+        #[test]
+        fn test_bucket_bounds() {
+            assert_eq!(0, bucket_index(0));
+            assert_eq!(7, bucket_index(7));
+            assert_eq!(BUCKETS - 1, bucket_index(u64::MAX));
+            assert_eq!(u64::MAX, bucket_upper_bound(BUCKETS - 1));
+
+            // every bucket starts right after the previous one ends and is at most 1/8 of its values wide
+            for index in 1..BUCKETS {
+                let lower: u64 = bucket_upper_bound(index - 1) + 1;
+                let upper: u64 = bucket_upper_bound(index);
+                assert_eq!(index, bucket_index(lower));
+                assert_eq!(index, bucket_index(upper));
+                assert!(upper - lower <= lower / 8);
+            }
+        }
+
+        #[test]
+        fn test_percentiles_of_known_distribution() {
+            let mut histogram: LatencyHistogram = LatencyHistogram::new();
+            assert_eq!(Duration::ZERO, histogram.percentile(50.0));
+
+            // 1 to 1000 microseconds, once each
+            for micros in 1..=1000 {
+                histogram.record(Duration::from_micros(micros));
+            }
+            let summary = histogram.summary();
+            assert_eq!(1000, summary.count);
+            assert!(summary.p50 >= Duration::from_micros(500));
+            assert!(summary.p50 <= Duration::from_micros(500 + 500 / 8));
+            assert!(summary.p99 >= Duration::from_micros(990));
+            assert!(summary.p99 <= Duration::from_micros(990 + 990 / 8));
+            assert!(histogram.percentile(100.0) >= Duration::from_micros(1000));
+            assert!(histogram.percentile(0.0) <= Duration::from_micros(2));
+        }
+    }
+}
@@ -1,14 +1,57 @@
 mod buffer;
+mod error;
+mod files;
 mod filter;
+#[cfg(feature = "latency")]
+mod latency;
 mod memtable;
+mod record;
 mod serde;
+mod snapshot;
 mod storage;
 
+pub use crate::error::KvError;
+pub use crate::files::{file_opens, read_stats, reset_read_stats, ReadStats};
+use crate::filter::bloom_seeds;
+pub use crate::filter::FilterStats;
+#[cfg(feature = "latency")]
+use crate::latency::Latencies;
+#[cfg(feature = "latency")]
+pub use crate::latency::{LatencyReport, OpLatency};
 use crate::memtable::Memtable;
-use crate::storage::{AppendOnlyLog, BTree, DiskStorage, LSMTree};
-use std::collections::HashMap;
+use crate::record::RECORD_SIZE;
+pub use crate::serde::list_databases;
+pub use crate::serde::SstFooter;
+use crate::serde::{
+    drop_corrupt_ssts, get_sst_names, is_temp_file, next_sst_index, read_sst_footer, verify_ssts,
+    SstNaming,
+};
+use crate::snapshot::LevelRun;
+use crate::storage::CompactionCallback;
+use crate::storage::{AppendOnlyLog, BTree, LSMTree, MemoryStorage, NullStorage};
+pub use crate::storage::{CompactionEvent, DiskStorage, ScanOutput, ValueSource};
+use itertools::{EitherOrBoth, Itertools};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::{read_dir, remove_dir_all};
+use std::io::{self, Read, Write};
+use std::ops::{Bound, ControlFlow};
 use std::path::Path;
+use std::rc::Rc;
+#[cfg(feature = "tracing")]
+use std::time::Duration;
+#[cfg(any(feature = "latency", feature = "tracing"))]
+use std::time::Instant;
+use twox_hash::xxh3::hash64;
+
+/// The smallest buffer pool size of the `BTree` and `LSMTree` storages, in pages: the 4 internal pages and the leaf
+/// page a get reads in the tallest B tree an SST can hold, 2^32 leaf pages under internal pages of 256 keys. A
+/// smaller pool evicts the top of a descent before it is done and reads it again on every get.
+pub const MIN_BUFFERPOOL_SIZE: usize = 5;
+
+/// The duration above which a get is traced as slow, at the debug level instead of the trace level.
+#[cfg(feature = "tracing")]
+const SLOW_GET: Duration = Duration::from_millis(1);
 
 /// Struct for the `Client`.
 pub struct Client {
@@ -20,22 +63,80 @@ pub struct Client {
     memtable_size: u32,
     /// The number of ssts in the DB.
     sst_count: u32,
+    /// The number of KV pairs written to the SSTs since the open, on top of those found on disk at the open, see
+    /// `entries_on_disk`. Merges only drop KV pairs, so the SSTs hold at most as many.
+    stored_entries: u64,
     /// The storage type of the DB (DiskStorage type).
     storage: Box<dyn DiskStorage>,
     /// If the DB should be cleaned up on close.
     cleanup: bool,
+    /// The largest number of KV pairs a scan pre-allocates room for.
+    scan_capacity: usize,
+    /// The largest number of keys a scan may return, `None` for no limit.
+    max_scan_keys: Option<usize>,
+    /// The config the built-in storage was made from, to make it again. `None` for a custom storage.
+    config: Option<KVConfig>,
+    /// If the DB was closed by `close`, so dropping it has nothing left to do.
+    closed: bool,
+    /// The errors of the corrupt SSTs taken out of the DB when it was opened.
+    lost_ssts: Vec<KvError>,
+    /// The latencies of the operations so far.
+    #[cfg(feature = "latency")]
+    latencies: Latencies,
 }
 
 /// Struct for the `KVConfig`.
+#[derive(Clone)]
 pub struct KVConfig {
     /// The memtable size to be used for the DB.
     memtable_size: u32,
     /// The buffer pool size to be used for the DB.
     bufferpool_size: usize,
+    /// Whether the buffer pool only admits pages accessed more often than the ones they would evict.
+    bufferpool_admission: bool,
     /// If the DB should be cleaned up on close.
     cleanup: bool,
     /// The storage type to be used for the DB.
     storage_type: StorageType,
+    /// The largest number of KV pairs a scan pre-allocates room for.
+    scan_capacity: usize,
+    /// The largest number of SST files of the DB kept open for reading.
+    max_open_files: usize,
+    /// The number of `BTree` SSTs above which they are merged into one.
+    btree_merge_threshold: u32,
+    /// The number of bloom filter bits per key of the `LSMTree` runs.
+    bloom_bits_per_key: u64,
+    /// The seed the hashes of the `LSMTree` bloom filters are derived from, `None` for the built in ones.
+    bloom_seed: Option<u64>,
+    /// The fraction of each `BTree` leaf page filled with KV pairs.
+    leaf_fill_factor: f32,
+    /// Whether the `AppendOnlyLog` SSTs are written as delta encoded pages.
+    delta_encoding: bool,
+    /// The largest number of bytes per second a `LSMTree` merge writes, 0 for no limit.
+    compaction_bytes_per_sec: u64,
+    /// The number of threads encoding the pages of the `LSMTree` runs.
+    #[cfg(feature = "rayon")]
+    encode_threads: usize,
+    /// Whether all the SSTs of an existing DB are checked when it is opened.
+    verify_on_open: bool,
+    /// What opening does with the corrupt SSTs found by `verify_on_open`.
+    corruption_policy: CorruptionPolicy,
+    /// Whether the `AppendOnlyLog` and `BTree` SSTs end with an `SstFooter`.
+    sst_footers: bool,
+    /// The largest number of keys a scan may return, `None` for no limit.
+    max_scan_keys: Option<usize>,
+    /// The smallest key of the `BTree` keys if they are dense sequential integers, `None` if not known to be.
+    dense_keys: Option<i64>,
+    /// The largest number of `AppendOnlyLog` or `BTree` SSTs kept, `None` for no limit.
+    max_ssts: Option<usize>,
+    /// The fraction of tombstones among the `LSMTree` entries past which all its runs are merged, `None` for never.
+    tombstone_compaction_ratio: Option<f32>,
+    /// The callback called after every `LSMTree` merge, `None` to not report them.
+    on_compaction: Option<CompactionCallback>,
+    /// The prefix of the SST file names.
+    sst_prefix: String,
+    /// The extension of the SST file names, without the dot.
+    sst_extension: String,
 }
 
 // Implementation for the `KVConfig`.
@@ -47,13 +148,23 @@ impl KVConfig {
         self.memtable_size = size;
         self
     }
-    /// Setting the buffer pool size.
+    /// Setting the buffer pool size, in pages. The `BTree` and `LSMTree` storages need at least
+    /// `MIN_BUFFERPOOL_SIZE` pages.
     /// # Arguments
     /// * `size` - The buffer pool size wanted.
     pub fn bufferpool_size(mut self, size: usize) -> Self {
         self.bufferpool_size = size;
         self
     }
+    /// Setting whether the buffer pool of a `BTree` or `LSMTree` DB filters the pages it admits once full. A page is
+    /// then only kept if it was accessed more often recently than the least recently used page it would evict, so
+    /// the pages of a large scan do not evict the hot ones.
+    /// # Arguments
+    /// * `admission` - `true` to filter the admitted pages, `false` for plain LRU.
+    pub fn bufferpool_admission(mut self, admission: bool) -> Self {
+        self.bufferpool_admission = admission;
+        self
+    }
     /// Setting the storage type.
     /// # Arguments
     /// * `storage` - The storage type wanted.
@@ -68,38 +179,356 @@ impl KVConfig {
         self.cleanup = cleanup;
         self
     }
+    /// Setting the largest number of KV pairs a scan pre-allocates room for. Scans of narrower ranges or smaller
+    /// DBs pre-allocate less, and scans returning more pairs grow their output as needed.
+    /// # Arguments
+    /// * `capacity` - The scan capacity wanted.
+    pub fn scan_capacity(mut self, capacity: usize) -> Self {
+        self.scan_capacity = capacity;
+        self
+    }
+    /// Setting the largest number of SST files of the DB kept open for reading, the least recently read is closed
+    /// first. Each `Client` keeps its own limit on the files of its directory, also when sharing a thread.
+    /// # Arguments
+    /// * `max` - The number of open files wanted, 0 to open an SST on every page read.
+    pub fn max_open_files(mut self, max: usize) -> Self {
+        self.max_open_files = max;
+        self
+    }
+    /// Setting the number of SSTs of a `BTree` DB above which a flush merges them all into one larger SST, to keep
+    /// the number of SSTs a get searches low.
+    /// # Arguments
+    /// * `threshold` - The merge threshold wanted, 0 to never merge.
+    pub fn btree_merge_threshold(mut self, threshold: u32) -> Self {
+        self.btree_merge_threshold = threshold;
+        self
+    }
+    /// Setting the number of bloom filter bits per key of the runs of an `LSMTree` DB. More bits make fewer gets
+    /// search a run that does not hold the key, see `Client::filter_stats`.
+    /// # Arguments
+    /// * `bits` - The bits per key wanted.
+    pub fn bloom_bits_per_key(mut self, bits: u64) -> Self {
+        self.bloom_bits_per_key = bits;
+        self
+    }
+    /// Setting the seed the key hashes of the bloom filters of an `LSMTree` DB are derived from. Keys picked to be
+    /// false positives of the built in hashes, or of another DB, are not false positives of this one. The filters
+    /// are only kept in memory, so the seed can change between opens.
+    /// # Arguments
+    /// * `seed` - The seed wanted, such as a random one per DB, or `None` for the built in hashes.
+    pub fn bloom_seed(mut self, seed: Option<u64>) -> Self {
+        self.bloom_seed = seed;
+        self
+    }
+    /// Setting the fraction of each leaf page of a `BTree` SST filled with KV pairs. Fuller pages make smaller SSTs,
+    /// emptier ones leave room for future changes to a leaf.
+    /// # Arguments
+    /// * `fill_factor` - The leaf fill factor wanted, more than 0 and at most 1.
+    pub fn leaf_fill_factor(mut self, fill_factor: f32) -> Self {
+        self.leaf_fill_factor = fill_factor;
+        self
+    }
+    /// Setting whether the SSTs of an `AppendOnlyLog` are written as delta + varint encoded pages instead of fixed
+    /// 16 byte records. Dense keys then take a few bytes each. The `BTree` and `LSMTree` layouts rely on a fixed
+    /// number of KV pairs per page, so they always use fixed records.
+    /// # Arguments
+    /// * `delta_encoding` - Whether to delta encode the SSTs.
+    pub fn delta_encoding(mut self, delta_encoding: bool) -> Self {
+        self.delta_encoding = delta_encoding;
+        self
+    }
+    /// Setting the largest number of bytes per second written when `LSMTree` runs are merged. Merges sleep to stay
+    /// under it, so they leave disk bandwidth for reads at the cost of taking longer. 0 means no limit.
+    /// # Arguments
+    /// * `bytes_per_sec` - The merge write rate wanted.
+    pub fn compaction_bytes_per_sec(mut self, bytes_per_sec: u64) -> Self {
+        self.compaction_bytes_per_sec = bytes_per_sec;
+        self
+    }
+    /// Setting the number of threads encoding the leaf pages of the runs an `LSMTree` DB writes, in parallel with
+    /// each other. The pages keep their order, so the runs are the same as encoded on a single thread. Mostly worth it
+    /// for the large runs of `Client::ingest_sorted_run`. 1 encodes them on the calling thread. Only built with the
+    /// `rayon` feature.
+    /// # Arguments
+    /// * `threads` - The number of threads wanted, at least 1.
+    #[cfg(feature = "rayon")]
+    pub fn encode_threads(mut self, threads: usize) -> Self {
+        self.encode_threads = threads;
+        self
+    }
+    /// Setting whether opening an existing `AppendOnlyLog` or `BTree` DB reads all of its SSTs to check that every
+    /// page is readable and holds its keys in order, failing the open if one does not. Otherwise corrupt pages are
+    /// only found when a read gets to them. Opening takes as long as reading the whole DB once.
+    /// # Arguments
+    /// * `verify_on_open` - Whether to check the SSTs on open.
+    pub fn verify_on_open(mut self, verify_on_open: bool) -> Self {
+        self.verify_on_open = verify_on_open;
+        self
+    }
+    /// Setting what opening does with the corrupt SSTs found by `verify_on_open`: fail the open, or take them out of
+    /// the DB and open with the other SSTs, losing the data of the corrupt ones. The SSTs taken out are listed by
+    /// `Client::lost_ssts`. Corrupt pages found later by reads are still returned as errors.
+    /// # Arguments
+    /// * `policy` - The corruption policy wanted.
+    pub fn on_corruption(mut self, policy: CorruptionPolicy) -> Self {
+        self.corruption_policy = policy;
+        self
+    }
+    /// Setting whether the `AppendOnlyLog` and `BTree` SSTs end with a checksummed `SstFooter` holding their entry
+    /// count, min and max keys, page count and format version, read by `Client::sst_footers` without scanning the
+    /// SSTs. `verify_on_open` then also checks each footer against its SST, and takes an SST without one as
+    /// truncated, so it should only be set for DBs written with footers from the start.
+    /// # Arguments
+    /// * `sst_footers` - Whether to write the SST footers.
+    pub fn sst_footers(mut self, sst_footers: bool) -> Self {
+        self.sst_footers = sst_footers;
+        self
+    }
+    /// Setting the largest number of keys a scan may return, to catch swapped or too wide bounds before they read
+    /// the whole DB. The number of keys is estimated from the width of the range and the number of KV pairs stored,
+    /// before anything is read, and `try_scan` returns an error when it is over the limit (`scan` panics).
+    /// # Arguments
+    /// * `max` - The largest number of keys wanted, at least 1, or `None` for no limit.
+    pub fn max_scan_keys(mut self, max: Option<usize>) -> Self {
+        self.max_scan_keys = max;
+        self
+    }
+    /// Setting the hint that the keys of a `BTree` DB are dense sequential integers from `min_key`, such as the keys
+    /// of the benchmarks. Gets and scans then compute the leaf page and entry of a key from its distance to the
+    /// first key of each SST instead of descending the internal pages, and descend as usual when the computed entry
+    /// holds another key, so a wrong hint only costs the extra page read. The other storage types ignore it.
+    /// # Arguments
+    /// * `min_key` - The smallest key of the DB, or `None` if the keys are not known to be dense.
+    pub fn dense_keys(mut self, min_key: Option<i64>) -> Self {
+        self.dense_keys = min_key;
+        self
+    }
+    /// Setting the largest number of SSTs of an `AppendOnlyLog` or `BTree` DB, to bound the SSTs a read searches.
+    /// A flush past it merges the oldest SSTs into one, keeping the newest ones as they are. The `LSMTree` already
+    /// merges its runs, and the other storage types ignore it.
+    /// # Arguments
+    /// * `max` - The largest number of SSTs wanted, at least 1, or `None` for no limit.
+    pub fn max_ssts(mut self, max: Option<usize>) -> Self {
+        self.max_ssts = max;
+        self
+    }
+    /// Setting the fraction of tombstones among the entries of the `LSMTree` runs past which a flush merges all the
+    /// runs into one, dropping the tombstones and the values they delete. Without it tombstones are only dropped
+    /// when they reach the top level, so a delete heavy workload keeps the space of its deleted keys. The other
+    /// storage types ignore it.
+    /// # Arguments
+    /// * `ratio` - The fraction wanted, more than 0 and at most 1, or `None` to never merge on tombstones.
+    pub fn tombstone_compaction_ratio(mut self, ratio: Option<f32>) -> Self {
+        self.tombstone_compaction_ratio = ratio;
+        self
+    }
+    /// Setting a callback called after every merge of the runs of an `LSMTree` DB with what the merge did, see
+    /// `CompactionEvent`, to log or react to compactions. The clones of the `KVConfig` share the callback. The other
+    /// storage types ignore it.
+    /// # Arguments
+    /// * `callback` - The callback wanted.
+    pub fn on_compaction(mut self, callback: Box<dyn FnMut(CompactionEvent)>) -> Self {
+        self.on_compaction = Some(Rc::new(RefCell::new(callback)));
+        self
+    }
+
+    /// Setting the prefix of the SST file names, which are `{prefix}_{index}.{extension}`.
+    /// # Arguments
+    /// * `prefix` - The SST file name prefix wanted.
+    pub fn sst_prefix(mut self, prefix: String) -> Self {
+        self.sst_prefix = prefix;
+        self
+    }
+    /// Setting the extension of the SST file names, which are `{prefix}_{index}.{extension}`.
+    /// # Arguments
+    /// * `extension` - The SST file extension wanted, without the dot.
+    pub fn sst_extension(mut self, extension: String) -> Self {
+        self.sst_extension = extension;
+        self
+    }
+
+    /// Getting the naming convention of the SST files from the config.
+    /// # Arguments
+    /// * `self` - A ref to the `KVConfig`.
+    fn sst_naming(&self) -> SstNaming {
+        SstNaming::new(self.sst_prefix.clone(), self.sst_extension.clone())
+    }
+
+    /// Getting the largest number of SSTs from the config as an SST count, `u32::MAX` at most.
+    /// # Arguments
+    /// * `self` - A ref to the `KVConfig`.
+    fn max_ssts_u32(&self) -> Option<u32> {
+        self.max_ssts
+            .map(|max| u32::try_from(max).unwrap_or(u32::MAX))
+    }
+
+    /// Checking that the config values can be used by the DB. The memtable size must be at least 1, the
+    /// buffer pool size must be at least `MIN_BUFFERPOOL_SIZE` for the storage types that use a buffer pool, and the
+    /// SST file names must be valid (see `SstNaming::validate`).
+    /// # Arguments
+    /// * `self` - A ref to the `KVConfig` to validate.
+    pub fn validate(&self) -> Result<(), KvError> {
+        if self.memtable_size == 0 {
+            return Err(KvError::InvalidConfig(
+                "memtable_size must be at least 1".to_string(),
+            ));
+        }
+        let uses_pool: bool =
+            matches!(self.storage_type, StorageType::BTree | StorageType::LSMTree);
+        if uses_pool && self.bufferpool_size < MIN_BUFFERPOOL_SIZE {
+            return Err(KvError::InvalidConfig(format!(
+                "bufferpool_size must be at least {} for BTree and LSMTree storage",
+                MIN_BUFFERPOOL_SIZE
+            )));
+        }
+        if !(self.leaf_fill_factor > 0.0 && self.leaf_fill_factor <= 1.0) {
+            return Err(KvError::InvalidConfig(
+                "leaf_fill_factor must be more than 0 and at most 1".to_string(),
+            ));
+        }
+        if self.max_scan_keys == Some(0) {
+            return Err(KvError::InvalidConfig(
+                "max_scan_keys must be at least 1".to_string(),
+            ));
+        }
+        if self.max_ssts == Some(0) {
+            return Err(KvError::InvalidConfig(
+                "max_ssts must be at least 1".to_string(),
+            ));
+        }
+        if let Some(ratio) = self.tombstone_compaction_ratio {
+            if !(ratio > 0.0 && ratio <= 1.0) {
+                return Err(KvError::InvalidConfig(
+                    "tombstone_compaction_ratio must be more than 0 and at most 1".to_string(),
+                ));
+            }
+        }
+        #[cfg(feature = "rayon")]
+        if self.encode_threads == 0 {
+            return Err(KvError::InvalidConfig(
+                "encode_threads must be at least 1".to_string(),
+            ));
+        }
+        if matches!(self.storage_type, StorageType::LSMTree) && self.bloom_bits_per_key == 0 {
+            return Err(KvError::InvalidConfig(
+                "bloom_bits_per_key must be at least 1 for LSMTree storage".to_string(),
+            ));
+        }
+        self.sst_naming()
+            .validate()
+            .map_err(KvError::InvalidConfig)?;
+        Ok(())
+    }
 }
 
 // Special default implementation of the `KVConfig`.
 impl Default for KVConfig {
-    /// Default implementation of the `KVConfig`.
-    /// Sets the memtable size and buffer pool size to 256 KV pairs, clean up to `false`, and storage type to append only log.
+    /// Default implementation of the `KVConfig`, see the builder methods for what each setting does:
+    /// * memtable size and buffer pool size of 256 KV pairs, plain LRU buffer pools
+    /// * no clean up, append only log storage
+    /// * scan capacity of 65536 KV pairs, no scan limit
+    /// * 64 open SST files
+    /// * no BTree merges, full BTree leaf pages, no dense key hint
+    /// * bloom filters of 10 bits per key with the built in hashes
+    /// * fixed size records, no SST footers
+    /// * unthrottled merges, runs encoded on 1 thread
+    /// * no check of the SSTs on open, failing on corrupt ones when checked
+    /// * no SST limit, no tombstone triggered merges, no compaction callback
+    /// * SST file names `output_{index}.bin`
     fn default() -> Self {
         Self {
             memtable_size: 256,
             bufferpool_size: 256,
+            bufferpool_admission: false,
             cleanup: false,
             storage_type: StorageType::AppendOnlyLog,
+            scan_capacity: 1 << 16,
+            max_open_files: files::DEFAULT_MAX_OPEN_FILES,
+            btree_merge_threshold: 0,
+            bloom_bits_per_key: 10,
+            bloom_seed: None,
+            leaf_fill_factor: 1.0,
+            delta_encoding: false,
+            compaction_bytes_per_sec: 0,
+            #[cfg(feature = "rayon")]
+            encode_threads: 1,
+            verify_on_open: false,
+            corruption_policy: CorruptionPolicy::Fail,
+            sst_footers: false,
+            max_scan_keys: None,
+            dense_keys: None,
+            max_ssts: None,
+            tombstone_compaction_ratio: None,
+            on_compaction: None,
+            sst_prefix: "output".to_string(),
+            sst_extension: "bin".to_string(),
         }
     }
 }
 
+/// Struct of what `Client::close` left behind.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CloseReport {
+    /// The number of SSTs in the DB once the memtable was flushed.
+    pub sst_count: u32,
+    /// The number of bytes the DB took on disk once the memtable was flushed, before any clean up.
+    pub disk_bytes: u64,
+    /// If the DB directory was removed because the config asks for clean up.
+    pub cleaned_up: bool,
+}
+
+/// The KV pairs `Client::get_with_neighbors` finds: the ones before the key, the one of the key, the ones after it.
+pub type Neighbors = (Vec<(i64, i64)>, Option<(i64, i64)>, Vec<(i64, i64)>);
+
+/// Struct of what `Client::put_tracked` did.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PutOutcome {
+    /// If the put filled the memtable, so it was flushed into an SST.
+    pub flushed: bool,
+}
+
+/// Enum of what opening a DB does with the corrupt SSTs found by `KVConfig::verify_on_open`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CorruptionPolicy {
+    /// Fail the open with the error of the first corrupt SST.
+    Fail,
+    /// Remove the corrupt SSTs, their data is lost.
+    Skip,
+    /// Move the corrupt SSTs into the `quarantine` directory of the DB, out of its SSTs, to inspect or repair them.
+    Quarantine,
+}
+
 /// Struct for the `StorageType`.
+#[derive(Clone, Copy)]
 pub enum StorageType {
     AppendOnlyLog,
     BTree,
     LSMTree,
+    Null,
+    Memory,
 }
 
 // Implementation for the `Client`.
 impl Client {
-    /// Creating a new `Client` with `name` and `config`.
+    /// Creating a new `Client` with `name` and `config`. Panics if the `config` is invalid, see `try_open`.
     /// # Arguments
     /// * `name` - The name of the new `Client`.
     /// * `config` - A `KVConfig` object to set the values of the new `Client`.
     pub fn open(name: String, config: KVConfig) -> Self {
+        Self::try_open(name, config).expect("Client: open failed!")
+    }
+
+    /// Creating a new `Client` with `name` and `config`, returning an error if the `config` is invalid.
+    /// # Arguments
+    /// * `name` - The name of the new `Client`.
+    /// * `config` - A `KVConfig` object to set the values of the new `Client`.
+    pub fn try_open(name: String, config: KVConfig) -> Result<Self, KvError> {
+        config.validate()?;
+
         let mut count: u32 = 0;
-        let db_exists: bool = Path::new(&name).exists();
+        // a memory DB never reads the SSTs of a directory with the same name
+        let db_exists: bool =
+            Path::new(&name).exists() && !matches!(config.storage_type, StorageType::Memory);
         if db_exists {
             count = match read_dir(&name) {
                 Ok(entries) => {
@@ -110,6 +539,7 @@ impl Client {
                                 .metadata()
                                 .map(|metadata| metadata.is_file())
                                 .unwrap_or(false)
+                                && !is_temp_file(entry)
                         })
                         .count();
                     file_count as u32
@@ -120,34 +550,198 @@ impl Client {
             };
         }
 
-        Self {
+        let verified: bool = matches!(
+            config.storage_type,
+            StorageType::AppendOnlyLog | StorageType::BTree
+        );
+        let mut lost_ssts: Vec<KvError> = Vec::new();
+        if db_exists && config.verify_on_open && verified {
+            match config.corruption_policy {
+                CorruptionPolicy::Fail => {
+                    verify_ssts(&name, &config.sst_naming(), config.sst_footers)?
+                }
+                policy => {
+                    let quarantine: bool = policy == CorruptionPolicy::Quarantine;
+                    lost_ssts = drop_corrupt_ssts(
+                        &name,
+                        &config.sst_naming(),
+                        config.sst_footers,
+                        quarantine,
+                    )?;
+                }
+            }
+            #[cfg(feature = "tracing")]
+            for error in &lost_ssts {
+                tracing::warn!(db = %name, policy = ?config.corruption_policy, %error, "corrupt SST taken out");
+            }
+            // the next SST goes after the newest, not at the count of SSTs left
+            if !lost_ssts.is_empty() {
+                count = next_sst_index(&name, &config.sst_naming());
+            }
+        }
+
+        files::set_max_open_files(&name, config.max_open_files);
+
+        let storage: Box<dyn DiskStorage> = Self::new_storage(name.clone(), &config);
+        // the append only log knows its next SST index from the file names
+        if db_exists && matches!(config.storage_type, StorageType::AppendOnlyLog) {
+            count = storage.initial_sst_count();
+        }
+
+        let mut client: Client = Self {
             name: name.clone(),
             memtable: Memtable::new(),
             memtable_size: config.memtable_size,
             sst_count: count,
-            storage: match config.storage_type {
-                StorageType::AppendOnlyLog => Box::new(AppendOnlyLog::new(name)),
-                StorageType::BTree => Box::new(BTree::new(name, config.bufferpool_size)),
-                StorageType::LSMTree => Box::new(LSMTree::new(
+            stored_entries: 0,
+            storage,
+            cleanup: config.cleanup,
+            scan_capacity: config.scan_capacity,
+            max_scan_keys: config.max_scan_keys,
+            config: Some(config),
+            closed: false,
+            lost_ssts,
+            #[cfg(feature = "latency")]
+            latencies: Latencies::default(),
+        };
+        if db_exists {
+            client.stored_entries = client.entries_on_disk();
+        }
+        Ok(client)
+    }
+
+    /// Helper function to count the KV pairs in the SSTs of the `Client` DB when it is opened: the sum of the entry
+    /// counts of the `SstFooter`s when every SST has one, else the number of records its files take the room of,
+    /// which is a lower bound for delta encoded SSTs. A directory that cannot be measured is taken as holding every
+    /// key.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object to count.
+    fn entries_on_disk(&self) -> u64 {
+        let footers: Option<Vec<SstFooter>> = self
+            .sst_footers()
+            .ok()
+            .and_then(|footers| footers.into_iter().collect());
+        match footers {
+            Some(footers) if !footers.is_empty() => {
+                footers.iter().map(|footer| footer.entries).sum()
+            }
+            _ => self
+                .disk_usage()
+                .map_or(u64::MAX, |bytes| bytes / RECORD_SIZE as u64),
+        }
+    }
+
+    /// Helper function to make the built-in storage of the `config` storage type for the DB `name`.
+    /// # Arguments
+    /// * `name` - The name of the DB.
+    /// * `config` - A `KVConfig` object to set the values of the storage.
+    fn new_storage(name: String, config: &KVConfig) -> Box<dyn DiskStorage> {
+        match config.storage_type {
+            StorageType::AppendOnlyLog => Box::new(
+                AppendOnlyLog::new(
+                    name,
+                    config.sst_naming(),
+                    config.delta_encoding,
+                    config.max_ssts_u32(),
+                )
+                .with_footers(config.sst_footers),
+            ),
+            StorageType::BTree => Box::new(
+                BTree::new(
+                    name,
+                    config.bufferpool_size,
+                    config.sst_naming(),
+                    config.btree_merge_threshold,
+                    config.leaf_fill_factor,
+                    config.dense_keys,
+                    config.max_ssts_u32(),
+                )
+                .with_pool_admission(config.bufferpool_admission)
+                .with_footers(config.sst_footers),
+            ),
+            StorageType::LSMTree => {
+                let lsm: LSMTree = LSMTree::new(
                     name,
                     config.bufferpool_size,
                     config.memtable_size,
-                )),
-            },
-            cleanup: config.cleanup,
+                    config.sst_naming(),
+                    config.bloom_bits_per_key,
+                    config.compaction_bytes_per_sec,
+                    config.tombstone_compaction_ratio,
+                )
+                .with_bloom_seeds(bloom_seeds(config.bloom_seed))
+                .with_pool_admission(config.bufferpool_admission)
+                .with_compaction_callback(config.on_compaction.clone());
+                #[cfg(feature = "rayon")]
+                let lsm: LSMTree = lsm.with_encode_threads(config.encode_threads);
+                Box::new(lsm)
+            }
+            StorageType::Null => Box::new(NullStorage),
+            StorageType::Memory => Box::new(MemoryStorage::new()),
         }
     }
 
+    /// Creating a new `Client` with `name` and `config` on top of a custom `storage` instead of the built-in one of
+    /// the `config` storage type, see `DiskStorage` for what the storage must do. Returns an error if the `config`
+    /// is invalid.
+    /// # Arguments
+    /// * `name` - The name of the new `Client`, removed on close if the `config` asks for clean up.
+    /// * `config` - A `KVConfig` object to set the values of the new `Client`.
+    /// * `storage` - The storage the memtable is flushed into and read from.
+    pub fn open_with_storage(
+        name: String,
+        config: KVConfig,
+        storage: Box<dyn DiskStorage>,
+    ) -> Result<Self, KvError> {
+        config.validate()?;
+        files::set_max_open_files(&name, config.max_open_files);
+
+        Ok(Self {
+            name,
+            memtable: Memtable::new(),
+            memtable_size: config.memtable_size,
+            sst_count: storage.initial_sst_count(),
+            stored_entries: 0,
+            storage,
+            cleanup: config.cleanup,
+            scan_capacity: config.scan_capacity,
+            max_scan_keys: config.max_scan_keys,
+            config: None,
+            closed: false,
+            lost_ssts: Vec::new(),
+            #[cfg(feature = "latency")]
+            latencies: Latencies::default(),
+        })
+    }
+
     /// Insert `key` and `value` into the `Client` DB.
     /// # Arguments
     /// * `self` - A mutable ref to the `Client` object to insert a new KV pair.
     /// * `key` - The new key to add.
     /// * `value` - The new value to add.
     pub fn put(&mut self, key: i64, value: i64) {
+        self.put_tracked(key, value);
+    }
+
+    /// Insert `key` and `value` into the `Client` DB like `put`, and tell if it flushed the memtable, for example to
+    /// checkpoint state kept outside of the DB right after its SST is written.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to insert a new KV pair.
+    /// * `key` - The new key to add.
+    /// * `value` - The new value to add.
+    pub fn put_tracked(&mut self, key: i64, value: i64) -> PutOutcome {
+        #[cfg(feature = "latency")]
+        let started: Instant = Instant::now();
+
         self.memtable.put(key, value);
-        if self.memtable.size() >= self.memtable_size {
+        let flushed: bool = self.memtable.size() >= self.memtable_size;
+        if flushed {
             self.flush();
         }
+
+        #[cfg(feature = "latency")]
+        self.latencies.put.record(started.elapsed());
+        PutOutcome { flushed }
     }
 
     /// Get the value corresponding to a `key` from the `Client` DB.
@@ -155,335 +749,4894 @@ impl Client {
     /// * `self` - A mutable ref to the `Client` object to get a value.
     /// * `key` - The key who's value is searched.
     pub fn get(&mut self, key: i64) -> Option<i64> {
-        let result = self.memtable.get(key).or_else(|| self.storage.get(key));
+        self.try_get(key).expect("Client: get failed!")
+    }
+
+    /// Get the value corresponding to a `key` from the `Client` DB, returning an error instead of panicking if an
+    /// SST cannot be read (for example `KvError::CorruptPage` for a truncated append only log SST).
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to get a value.
+    /// * `key` - The key who's value is searched.
+    pub fn try_get(&mut self, key: i64) -> Result<Option<i64>, KvError> {
+        #[cfg(any(feature = "latency", feature = "tracing"))]
+        let started: Instant = Instant::now();
+
+        let result = match self.memtable.get(key) {
+            Some(value) => Some(value),
+            // nothing was ever flushed, no need to look for SSTs
+            None if self.sst_count == 0 => None,
+            None => self.storage.try_get(key)?,
+        };
+
+        #[cfg(feature = "latency")]
+        self.latencies.get.record(started.elapsed());
+        #[cfg(feature = "tracing")]
+        {
+            let elapsed: Duration = started.elapsed();
+            if elapsed > SLOW_GET {
+                tracing::debug!(db = %self.name, key, found = result.is_some(), ?elapsed, "slow get");
+            } else {
+                tracing::trace!(db = %self.name, key, found = result.is_some(), ?elapsed, "get");
+            }
+        }
+
+        if result == Some(i64::MIN) {
+            return Ok(None);
+        }
+        Ok(result)
+    }
+
+    /// Insert a byte string `key` and `value` into the `Client` DB, stored under the xxh3 hash of `key` as an `i64`.
+    /// Two different byte keys can hash to the same `i64`, and then share one value: the last put wins. The hashed
+    /// keys also live alongside the plain `i64` keys, so a `put` of a key equal to a hash overwrites it too.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to insert a new KV pair.
+    /// * `key` - The new byte key to add.
+    /// * `value` - The new value to add.
+    pub fn put_hashed(&mut self, key: &[u8], value: i64) {
+        self.put(hash64(key) as i64, value);
+    }
+
+    /// Get the value corresponding to a byte string `key` put with `put_hashed`. A colliding byte key gives the value
+    /// of the other key, see `put_hashed`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to get a value.
+    /// * `key` - The byte key who's value is searched.
+    pub fn get_hashed(&mut self, key: &[u8]) -> Option<i64> {
+        self.get(hash64(key) as i64)
+    }
+
+    /// Get the value corresponding to a `key` from the `Client` DB along with where it was found, to debug which of
+    /// the memtable and SSTs shadows the others.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to get a value.
+    /// * `key` - The key who's value is searched.
+    pub fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
+        let result = match self.memtable.get(key) {
+            Some(value) => Some((value, ValueSource::Memtable)),
+            None if self.sst_count == 0 => None,
+            None => self.storage.get_with_source(key),
+        };
+        result.filter(|(value, _)| *value != i64::MIN)
+    }
 
-        if result.map_or(false, |a| a == i64::MIN) {
-            return None;
+    /// Get the whole leaf page of the newest SST holding `key`, else the leaf page it would be in of the newest SST
+    /// whose keys span it, else of the newest SST, so a caller can serve many nearby lookups from one page. The pairs
+    /// are as stored in that SST, in ascending key order: tombstones (`i64::MIN` values) are included, and the
+    /// memtable and newer SSTs may hold newer values of the other keys. Only the `BTree` and `LSMTree` storages have
+    /// leaf pages, the others return nothing, as does a DB without SSTs.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to read a page.
+    /// * `key` - The key who's leaf page is searched.
+    pub fn get_page_for_key(&mut self, key: i64) -> Vec<(i64, i64)> {
+        if self.sst_count == 0 {
+            return Vec::new();
         }
-        result
+        self.storage.leaf_page_for_key(key)
     }
 
     /// Scan the `Client` DB on a range of keys from `start` to `end` INCLUSIVE.
+    /// Whatever the storage type, the result is in ascending key order, holds the newest value of each key and
+    /// leaves out the deleted keys. When there is at most one SST, the memtable and SST outputs are merged directly
+    /// instead of going through a `HashMap`. With no SST the storage is not searched at all.
+    /// Panics if the scan may return more keys than the config allows or an SST cannot be read, see `try_scan`.
     /// # Arguments
     /// * `self` - A mutable ref to the `Client` object to scan for values.
     /// * `start` - The start key range of the scan.
     /// * `end` - The end key range of the scan.
     pub fn scan(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        self.try_scan(start, end).expect("Client: scan failed!")
+    }
+
+    /// Scan the `Client` DB on a range of keys from `start` to `end` INCLUSIVE like `scan`, returning a
+    /// `KvError::InvalidArgument` error instead of reading anything when the scan may return more keys than
+    /// `KVConfig::max_scan_keys`, and the error of the storage instead of panicking when an SST cannot be read, such
+    /// as `KvError::Io` for an append only log SST removed during the scan.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    pub fn try_scan(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
         if start > end {
-            return Vec::new();
+            return Ok(Vec::new());
         }
+        self.check_scan_width(start, end)?;
 
-        let mut kv_hash: HashMap<i64, i64> = HashMap::new();
+        #[cfg(any(feature = "latency", feature = "tracing"))]
+        let started: Instant = Instant::now();
 
-        self.memtable.scan(start, end, &mut kv_hash);
-        self.storage.scan(start, end, &mut kv_hash);
+        let output: Vec<(i64, i64)> = self.scan_unchecked(start, end)?;
 
-        kv_hash.into_iter().filter(|a| a.1 != i64::MIN).collect()
+        #[cfg(feature = "latency")]
+        self.latencies.scan.record(started.elapsed());
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            db = %self.name,
+            start,
+            end,
+            keys = output.len(),
+            elapsed = ?started.elapsed(),
+            "scan"
+        );
+        Ok(output)
     }
 
-    /// Close the `Client` DB. Flush if necessary.
-    ///  # Arguments
-    /// * `self` - A mutable ref to the `Client` object to flush it.
-    pub fn close(&mut self) {
-        if self.memtable.size() > 0 {
-            self.flush();
-        }
+    /// Helper function to scan the `Client` DB on a range of keys from `start` to `end` INCLUSIVE like `try_scan`,
+    /// without checking the width of the range against `KVConfig::max_scan_keys`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_unchecked(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
+        let mut output: Vec<(i64, i64)> = if self.sst_count <= 1 {
+            self.scan_merge(start, end)?
+        } else {
+            self.scan_hash(start, end)?
+        };
+        Self::finish_scan(&mut output);
+        Ok(output)
     }
 
-    /// Flush the memtable into an SST.
+    /// Get up to `n` KV pairs before `key`, the KV pair of `key` if it is in the `Client` DB, and up to `n` KV pairs
+    /// after it, each side in ascending key order and without the deleted keys. A single scan of the keys around
+    /// `key` finds all three, widened by doubling on the sides still short of `n` keys until they reach the ends of
+    /// the keys, so sparse keys take a few more scans than dense ones. The scans are not bounded by
+    /// `KVConfig::max_scan_keys`. Panics if an SST cannot be read.
     /// # Arguments
-    /// * `self` - A mutable ref to the `Client` object to flush it.
-    fn flush(&mut self) {
-        let output_lst: Vec<(i64, i64)> = self.memtable.scan_all();
-
-        self.storage.flush(self.sst_count, output_lst);
-
-        self.sst_count += 1;
-        self.memtable = Memtable::new();
-    }
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `key` - The key to get the neighbors of.
+    /// * `n` - The largest number of neighbors wanted on each side.
+    pub fn get_with_neighbors(&mut self, key: i64, n: usize) -> Neighbors {
+        // widths past the ends of the keys are cut at the ends
+        let mut width: (i128, i128) = (n as i128, n as i128);
+        loop {
+            let start: i64 = (key as i128 - width.0).max(i64::MIN as i128) as i64;
+            let end: i64 = (key as i128 + width.1).min(i64::MAX as i128) as i64;
+            let output: Vec<(i64, i64)> = self
+                .scan_unchecked(start, end)
+                .expect("Client: get_with_neighbors failed!");
+            let split: usize = output.partition_point(|a| a.0 < key);
+            let (before, rest) = output.split_at(split);
+            let (found, after) = match rest.first() {
+                Some(&pair) if pair.0 == key => (Some(pair), &rest[1..]),
+                _ => (None, rest),
+            };
 
-    pub fn delete(&mut self, key: i64) {
-        self.memtable.put(key, i64::MIN)
+            let before_done: bool = before.len() >= n || start == i64::MIN;
+            let after_done: bool = after.len() >= n || end == i64::MAX;
+            if before_done && after_done {
+                let before: Vec<(i64, i64)> = before[before.len().saturating_sub(n)..].to_vec();
+                let after: Vec<(i64, i64)> = after[..after.len().min(n)].to_vec();
+                return (before, found, after);
+            }
+            if !before_done {
+                width.0 *= 2;
+            }
+            if !after_done {
+                width.1 *= 2;
+            }
+        }
     }
 
-    pub fn update(&mut self, key: i64, value: i64) {
-        self.memtable.put(key, value)
+    /// Call `f` on every KV pair of the `Client` DB from `start` to `end` INCLUSIVE, in ascending key order, with the
+    /// newest value of each key and without the deleted keys, stopping as soon as `f` returns `ControlFlow::Break`.
+    /// The range is scanned a window of keys at a time, the first `KVConfig::scan_capacity` keys wide: a window
+    /// holding fewer than half that many KV pairs doubles the next one, one holding more halves it. A window widened
+    /// over sparse keys can still hold far more pairs where the keys turn dense, up to all of those left in the range,
+    /// so the pairs held at once depend on how the keys are spread and not on the scan capacity alone. The scan is not
+    /// bounded by `KVConfig::max_scan_keys`, and the windows past a `Break` are never read. Panics if an SST cannot be
+    /// read, see `try_scan_for_each`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    /// * `f` - The function called on each key and value.
+    pub fn scan_for_each(
+        &mut self,
+        start: i64,
+        end: i64,
+        f: impl FnMut(i64, i64) -> ControlFlow<()>,
+    ) {
+        self.try_scan_for_each(start, end, f)
+            .expect("Client: scan_for_each failed!");
     }
-}
 
-// Special implementation of the drop function for the `Client`.
-impl Drop for Client {
-    /// Drop the `Client` DB. Close it and clean up if necessary.
-    /// # Argument
-    /// * `self` - A mutable ref to the `Client` object to drop.
-    fn drop(&mut self) {
-        self.close();
+    /// Call `f` on every KV pair of the `Client` DB from `start` to `end` INCLUSIVE like `scan_for_each`. Returns the
+    /// `KvError` of the first SST that cannot be read instead of panicking, `f` having been called on the KV pairs of
+    /// the windows before it.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    /// * `f` - The function called on each key and value.
+    pub fn try_scan_for_each(
+        &mut self,
+        start: i64,
+        end: i64,
+        mut f: impl FnMut(i64, i64) -> ControlFlow<()>,
+    ) -> Result<(), KvError> {
+        let capacity: i128 = self.scan_capacity.max(1) as i128;
+        let mut width: i128 = capacity;
+        let mut window_start: i128 = start as i128;
+        while window_start <= end as i128 {
+            let window_end: i128 = (window_start + width - 1).min(end as i128);
+            let output: Vec<(i64, i64)> =
+                self.scan_unchecked(window_start as i64, window_end as i64)?;
+            for (key, value) in output.iter() {
+                if f(*key, *value).is_break() {
+                    return Ok(());
+                }
+            }
 
-        if self.cleanup {
-            let _ = remove_dir_all(&self.name);
+            window_start = window_end + 1;
+            if (output.len() as i128) < capacity / 2 {
+                // no wider than all of the keys
+                width = (width * 2).min(1 << 64);
+            } else if output.len() as i128 > capacity {
+                width = (width / 2).max(1);
+            }
         }
+        Ok(())
     }
-}
-
-#[cfg(test)]
-mod tests {
-    mod binary_tree {
-        mod get_value {
-            use crate::{Client, KVConfig};
-            use std::fs::create_dir_all;
 
-            #[test]
-            fn test_get_from_ssts() {
-                let mut kv: Client = Client::open(
-                    "getTestDB1".to_string(),
-                    KVConfig::default()
-                        .storage_type(crate::StorageType::AppendOnlyLog)
-                        .cleanup(true),
-                );
+    /// Scan the `Client` DB on every key whose high `prefix_bits` bits are the low `prefix_bits` bits of `prefix`, for
+    /// keys packing an id into their high bits. That is the range from `prefix << (64 - prefix_bits)` to the same
+    /// with all the low bits set, computed without overflowing at the top of the keys. A `prefix_bits` of 0 scans
+    /// every key, one of 64 or more only the key `prefix`. Panics like `scan`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `prefix` - The high bits of the keys to scan, either sign of packed id works.
+    /// * `prefix_bits` - The number of high bits the prefix takes.
+    pub fn scan_prefix(&mut self, prefix: i64, prefix_bits: u32) -> Vec<(i64, i64)> {
+        // without the sign bit in the prefix the keys wrap around from positive to negative
+        if prefix_bits == 0 {
+            return self.scan(i64::MIN, i64::MAX);
+        }
+        let low_bits: u32 = 64 - prefix_bits.min(64);
+        let start: u64 = (prefix as u64) << low_bits;
+        let end: u64 = start | u64::MAX.checked_shr(prefix_bits).unwrap_or(0);
+        self.scan(start as i64, end as i64)
+    }
 
-                let folder_path: &str = "./getTestDB1/";
-                create_dir_all(folder_path).expect("Create dir all has failed!");
+    /// Check that a scan of the `Client` DB from `start` to `end` INCLUSIVE agrees with gets, as the two read the SSTs
+    /// differently. Every scanned key must `get` the scanned value, and the key right after each scanned one, as well
+    /// as `start`, must `get` to `None` when the scan did not return it. Returns `true` if all of them agree. Meant for
+    /// tests, it does up to two gets per scanned key.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to audit.
+    /// * `start` - The start key range of the audit.
+    /// * `end` - The end key range of the audit.
+    pub fn audit_range(&mut self, start: i64, end: i64) -> bool {
+        let mut scanned: Vec<(i64, i64)> = self.scan(start, end);
+        scanned.sort_unstable();
 
-                for i in 0..200 {
-                    kv.put(i as i64, (i * 2) as i64);
+        let mut missing: Vec<i64> = Vec::new();
+        if start <= end && scanned.first().is_none_or(|&(key, _)| key != start) {
+            missing.push(start);
+        }
+        for (i, &(key, _)) in scanned.iter().enumerate() {
+            let next: Option<i64> = key.checked_add(1).filter(|&next| next <= end);
+            if let Some(next) = next {
+                if scanned
+                    .get(i + 1)
+                    .is_none_or(|&(next_key, _)| next_key != next)
+                {
+                    missing.push(next);
                 }
-
-                let key1: i64 = 12;
-                let key2: i64 = 110;
-
-                assert_eq!(Some(24), kv.get(key1));
-                assert_eq!(Some(220), kv.get(key2));
             }
         }
 
-        mod flush {
-            use crate::Client;
-            use crate::KVConfig;
-            use std::fs::create_dir_all;
+        scanned
+            .into_iter()
+            .all(|(key, value)| self.get(key) == Some(value))
+            && missing.into_iter().all(|key| self.get(key).is_none())
+    }
 
-            #[test]
-            fn test_memtable_flush() {
+    /// Get the live KV pairs of the `Client` DB from `start` to `end` INCLUSIVE as a `HashMap`, the way a scan gathers
+    /// them, without going through a `Vec`. Deleted keys are left out. Panics if the scan may return more keys than the
+    /// config allows, see `try_scan`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    pub fn get_range_as_map(&mut self, start: i64, end: i64) -> HashMap<i64, i64> {
+        if start > end {
+            return HashMap::new();
+        }
+        self.check_scan_width(start, end)
+            .and_then(|_| self.scan_map(start, end))
+            .expect("Client: get_range_as_map failed!")
+    }
+
+    /// Helper function to return a `KvError::InvalidArgument` error if a scan from `start` to `end` INCLUSIVE may
+    /// return more keys than `KVConfig::max_scan_keys`.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn check_scan_width(&self, start: i64, end: i64) -> Result<(), KvError> {
+        if let Some(max) = self.max_scan_keys {
+            let estimate: u128 = self.scan_key_estimate(start, end);
+            if estimate > max as u128 {
+                return Err(KvError::InvalidArgument(format!(
+                    "scan from {} to {} may return {} keys, more than max_scan_keys {}",
+                    start, end, estimate, max
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Helper function to estimate the largest number of keys a scan from `start` to `end` INCLUSIVE returns: the
+    /// width of the range, or if smaller the number of KV pairs stored. That number is taken as the larger of a full
+    /// memtable per flush and the running count of the KV pairs written to the SSTs, as merges can pack several
+    /// flushes into one SST. Nothing is read from disk.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_key_estimate(&self, start: i64, end: i64) -> u128 {
+        let width: u128 = (end as i128 - start as i128 + 1) as u128;
+        let flushed: u128 = self.memtable_size as u128 * (self.sst_count as u128 + 1);
+        let stored: u128 = self.memtable.size() as u128 + self.stored_entries as u128;
+        width.min(flushed.max(stored))
+    }
+
+    /// Scan the `Client` DB on a range of keys given by a `start` and an `end` bound, each included, excluded or
+    /// unbounded. Keys are integers, so the bounds are turned into the equivalent INCLUSIVE range of `scan` and a
+    /// range with no key in it returns nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start bound of the scan.
+    /// * `end` - The end bound of the scan.
+    pub fn scan_range(&mut self, start: Bound<i64>, end: Bound<i64>) -> Vec<(i64, i64)> {
+        let start: i64 = match start {
+            Bound::Included(key) => key,
+            Bound::Excluded(key) => match key.checked_add(1) {
+                Some(key) => key,
+                None => return Vec::new(),
+            },
+            Bound::Unbounded => i64::MIN,
+        };
+        let end: i64 = match end {
+            Bound::Included(key) => key,
+            Bound::Excluded(key) => match key.checked_sub(1) {
+                Some(key) => key,
+                None => return Vec::new(),
+            },
+            Bound::Unbounded => i64::MAX,
+        };
+        self.scan(start, end)
+    }
+
+    /// Helper function to put the `output` of a scan in the order `scan` returns whatever the storage type, ascending
+    /// keys. Both scan paths already keep the newest value of each key and leave out the deleted keys, so the keys
+    /// are unique. Already sorted outputs, such as the merged ones, are only checked.
+    /// # Arguments
+    /// * `output` - The KV pairs found by the scan.
+    fn finish_scan(output: &mut [(i64, i64)]) {
+        if !output.is_sorted_by_key(|a| a.0) {
+            output.sort_unstable_by_key(|a| a.0);
+        }
+    }
+
+    /// Scan the `Client` DB by merging the sorted memtable and storage outputs, newest wins on equal keys.
+    /// Only valid when the storage holds at most one SST since its output is not de-duplicated.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_merge(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
+        let capacity: usize = self.scan_capacity_hint(start, end);
+        let memtable_lst: Vec<(i64, i64)> = self.memtable.scan_vec(start, end);
+        let storage_lst: Vec<(i64, i64)> = if self.sst_count == 0 {
+            Vec::new()
+        } else {
+            self.storage.try_scan_sorted(start, end)?
+        };
+
+        let mut output: Vec<(i64, i64)> = Vec::with_capacity(capacity);
+        output.extend(
+            memtable_lst
+                .into_iter()
+                .merge_join_by(storage_lst, |a, b| a.0.cmp(&b.0))
+                .map(|pair| match pair {
+                    EitherOrBoth::Both(newer, _) | EitherOrBoth::Left(newer) => newer,
+                    EitherOrBoth::Right(older) => older,
+                })
+                .filter(|a| a.1 != i64::MIN),
+        );
+        Ok(output)
+    }
+
+    /// Scan the `Client` DB by gathering the memtable and storage outputs in a `HashMap` to eliminate duplicates.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_hash(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
+        let kv_hash: HashMap<i64, i64> = self.scan_map(start, end)?;
+
+        let mut output: Vec<(i64, i64)> = Vec::with_capacity(kv_hash.len());
+        output.extend(kv_hash);
+        Ok(output)
+    }
+
+    /// Scan the `Client` DB by gathering the memtable and storage outputs in a `HashMap`, then dropping the deleted
+    /// keys.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_map(&mut self, start: i64, end: i64) -> Result<HashMap<i64, i64>, KvError> {
+        let mut kv_hash: HashMap<i64, i64> =
+            HashMap::with_capacity(self.scan_capacity_hint(start, end));
+
+        self.memtable.scan(start, end, &mut kv_hash);
+        self.storage.try_scan(start, end, &mut kv_hash)?;
+
+        kv_hash.retain(|_, value| *value != i64::MIN);
+        Ok(kv_hash)
+    }
+
+    /// Count the live keys of the `Client` DB exactly: every key, in the memtable or an SST, whose newest value is not
+    /// a delete. Shadowed older values count once. O(n) in the number of keys stored, which are read with
+    /// `try_scan_for_each` a window at a time, see it for how many are held at once, and not bounded by
+    /// `KVConfig::max_scan_keys`. Returns the `KvError` of the first SST that cannot be read.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to count the keys of.
+    pub fn exact_len(&mut self) -> Result<usize, KvError> {
+        let mut len: usize = 0;
+        self.try_scan_for_each(i64::MIN, i64::MAX, |_, _| {
+            len += 1;
+            ControlFlow::Continue(())
+        })?;
+        Ok(len)
+    }
+
+    /// Check if the `Client` DB holds no live key, that is if `exact_len` would return 0: a DB holding only deletes is
+    /// empty. A fresh DB is answered from the memtable size and SST count, and a live key in the memtable answers
+    /// right away. Otherwise the DB is scanned with `scan_for_each` up to its first live key, so every SST is only
+    /// read through when all of the keys are deleted.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to check.
+    pub fn is_empty(&mut self) -> bool {
+        if self.sst_count == 0 && self.memtable.size() == 0 {
+            return true;
+        }
+        // the memtable holds the newest value of its keys
+        if self
+            .memtable
+            .scan_vec(i64::MIN, i64::MAX)
+            .iter()
+            .any(|a| a.1 != i64::MIN)
+        {
+            return false;
+        }
+        let mut empty: bool = true;
+        self.scan_for_each(i64::MIN, i64::MAX, |_, _| {
+            empty = false;
+            ControlFlow::Break(())
+        });
+        empty
+    }
+
+    /// Estimate how many KV pairs a scan from `start` to `end` returns, to pre-allocate its output. Bounded by the
+    /// width of the range, by the number of KV pairs ever flushed or buffered, and by the `scan_capacity` config.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    fn scan_capacity_hint(&self, start: i64, end: i64) -> usize {
+        let width: u128 = (end as i128 - start as i128 + 1) as u128;
+        let stored: u128 = self.memtable_size as u128 * (self.sst_count as u128 + 1);
+        width.min(stored).min(self.scan_capacity as u128) as usize
+    }
+
+    /// Scan the `Client` DB on a range of keys from `start` to `end` INCLUSIVE into a caller provided `out` map.
+    /// Keys of `out` inside the range are replaced by the current contents of the DB, keys outside of it are kept,
+    /// so several scans can be accumulated into one ordered map.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to scan for values.
+    /// * `start` - The start key range of the scan.
+    /// * `end` - The end key range of the scan.
+    /// * `out` - The map to merge the results into.
+    pub fn scan_into(&mut self, start: i64, end: i64, out: &mut BTreeMap<i64, i64>) {
+        if start > end {
+            return;
+        }
+
+        let mut after: BTreeMap<i64, i64> = out.split_off(&start);
+        if end < i64::MAX {
+            let mut tail: BTreeMap<i64, i64> = after.split_off(&(end + 1));
+            out.append(&mut tail);
+        }
+
+        self.memtable.scan(start, end, out);
+        if self.sst_count > 0 {
+            self.storage.scan(start, end, out);
+        }
+
+        out.retain(|_, value| *value != i64::MIN);
+    }
+
+    /// Iterate over the KV pairs of the run at a `level` of an `LSMTree` DB, in ascending key order. Deleted keys
+    /// show up with the `i64::MIN` tombstone value. Nothing is returned for an empty level or another storage type.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to read.
+    /// * `level` - The level to read, starting from 1.
+    pub fn iter_level(&mut self, level: u32) -> impl Iterator<Item = (i64, i64)> {
+        self.storage.iter_level(level).into_iter()
+    }
+
+    /// Get the bloom filter outcomes of the gets of the `Client` DB so far, all zeros for storages without filters.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    pub fn filter_stats(&self) -> FilterStats {
+        self.storage.filter_stats()
+    }
+
+    /// Get the errors of the corrupt SSTs taken out of the `Client` DB when it was opened, see
+    /// `KVConfig::on_corruption`. Each names the SST whose data was lost.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    pub fn lost_ssts(&self) -> &[KvError] {
+        &self.lost_ssts
+    }
+
+    /// Regenerate the bloom filters of every run of an `LSMTree` DB from the KV pairs of the run, with the bloom
+    /// filter settings of the config, for runs whose filters were lost. All the runs are read once, which is still
+    /// far less work than rewriting them. Does nothing for the other storage types.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to rebuild the filters of.
+    pub fn rebuild_filters(&mut self) {
+        self.storage.rebuild_filters();
+    }
+
+    /// Split the `Client` DB in two by key range: the keys from `pivot` up are moved into a new DB at `dest`, opened
+    /// with the same config, and the keys below `pivot` stay. The moved keys are streamed into the new DB and flushed
+    /// first, then each SST of this DB holding keys from `pivot` up is rewritten without them, under a temporary name
+    /// published over the old SST. Returns the `Client` of the new DB, or an error if `dest` already exists, the DB
+    /// runs on a custom storage, the new DB does not hold as many keys as were moved, in which case this DB is left
+    /// as it was, or an SST cannot be rewritten. The DB must not be used by another process
+    /// meanwhile. If this one dies part way, no key below `pivot` is lost, but some keys from `pivot` up may be left
+    /// in both DBs.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to split.
+    /// * `pivot` - The smallest key moved to the new DB.
+    /// * `dest` - The path of the new DB.
+    pub fn split(&mut self, pivot: i64, dest: &Path) -> io::Result<Client> {
+        let config: KVConfig = self.config.clone().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Client: split is not supported on a custom storage!",
+            )
+        })?;
+        if dest.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!(
+                    "Client: split destination {} already exists!",
+                    dest.display()
+                ),
+            ));
+        }
+
+        let to_io = |err: KvError| {
+            let kind: io::ErrorKind = match &err {
+                KvError::Io { kind, .. } => *kind,
+                _ => io::ErrorKind::Other,
+            };
+            io::Error::new(kind, err)
+        };
+
+        // the moved keys are on disk in the new DB before any SST of this one is rewritten
+        let mut other: Client = Client::try_open(dest.to_string_lossy().into_owned(), config)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        let mut moved: usize = 0;
+        self.try_scan_for_each(pivot, i64::MAX, |key, value| {
+            other.put(key, value);
+            moved += 1;
+            ControlFlow::Continue(())
+        })
+        .map_err(to_io)?;
+        other.flush_memtable();
+
+        // nothing is dropped from this DB unless every moved key reads back from the new one
+        let copied: usize = other.exact_len().map_err(to_io)?;
+        if copied != moved {
+            return Err(io::Error::other(format!(
+                "Client: split copied {copied} of {moved} keys to {}!",
+                dest.display()
+            )));
+        }
+
+        self.sst_count = self.storage.truncate_from(pivot).map_err(to_io)?;
+        let kept: Vec<(i64, i64)> = self
+            .memtable
+            .scan_vec(i64::MIN, i64::MAX)
+            .into_iter()
+            .filter(|a| a.0 < pivot)
+            .collect();
+        self.memtable.clear();
+        for (key, value) in kept {
+            self.memtable.put(key, value);
+        }
+
+        Ok(other)
+    }
+
+    /// Write a snapshot of the `Client` DB to `out` as one self describing stream: a header with the format version,
+    /// page size and key order, then every live KV pair in ascending key order as a 16 byte record. Read it back
+    /// with `import_snapshot`, into a DB of any storage type. The live KV pairs are gathered in memory first.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to export.
+    /// * `out` - Where to write the snapshot.
+    pub fn export_snapshot<W: Write>(&mut self, out: W) -> io::Result<()> {
+        let mut contents: BTreeMap<i64, i64> = BTreeMap::new();
+        self.scan_into(i64::MIN, i64::MAX, &mut contents);
+        let kv_arr: Vec<(i64, i64)> = contents.into_iter().collect();
+        snapshot::write_snapshot(out, &kv_arr)
+    }
+
+    /// Put every KV pair of the snapshot in `input`, written by `export_snapshot`, into the `Client` DB. Keys already
+    /// in the DB are overwritten. Returns the number of KV pairs imported, or an `io::ErrorKind::InvalidData` error if
+    /// the snapshot is malformed, in which case the pairs before the problem are already imported.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to import into.
+    /// * `input` - Where to read the snapshot from.
+    pub fn import_snapshot<R: Read>(&mut self, input: R) -> io::Result<u64> {
+        snapshot::read_snapshot(input, |key, value| self.put(key, value))
+    }
+
+    /// Write every record of the runs of an `LSMTree` DB to `out`, for tools sorting or merging them outside of the
+    /// DB: older values and deletes included, oldest run first, each with its level (a lower level is newer). Read it
+    /// back with `import_records`. The memtable is not written, and the other storage types have no runs to write.
+    /// The format is described by `write_records` in "snapshot.rs", and `export_snapshot` writes only the live KV
+    /// pairs.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to export.
+    /// * `out` - Where to write the records.
+    pub fn export_records<W: Write>(&mut self, out: W) -> io::Result<()> {
+        let runs: Vec<LevelRun> = (1..=u32::BITS)
+            .rev()
+            .map(|level| (level, self.storage.iter_level(level)))
+            .filter(|(_, run)| !run.is_empty())
+            .collect();
+        snapshot::write_records(out, &runs)
+    }
+
+    /// Ingest every run of the records in `input`, written by `export_records`, at its level with
+    /// `ingest_sorted_run`, oldest run first. Returns the number of records ingested, a `KvError::InvalidArgument`
+    /// error if the records are malformed, or the error of the ingest of a run that does not fit, for example on top
+    /// of newer runs already in the DB. The runs before the problem are already ingested.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to ingest into.
+    /// * `input` - Where to read the records from.
+    pub fn import_records<R: Read>(&mut self, input: R) -> Result<u64, KvError> {
+        let runs: Vec<LevelRun> = snapshot::read_records(input)
+            .map_err(|err| KvError::InvalidArgument(format!("malformed records: {}", err)))?;
+        let mut count: u64 = 0;
+        for (level, run) in runs {
+            count += run.len() as u64;
+            self.ingest_sorted_run(run, level)?;
+        }
+        Ok(count)
+    }
+
+    /// Pull the pages of the SSTs holding the keys from `start` to `end` INCLUSIVE into the buffer pool, so the next
+    /// reads of the range hit the cache, for example before a latency sensitive phase. At most as many pages as the
+    /// buffer pool holds are read, the ones of the newest SSTs first. Returns the number of pages read, 0 for a storage
+    /// type without a buffer pool. Only the `BTree` storage is warmed for now.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to warm.
+    /// * `start` - The begining of the range to warm (INCLUSIVE).
+    /// * `end` - The end of the range to warm (INCLUSIVE).
+    pub fn warm_range(&mut self, start: i64, end: i64) -> usize {
+        if start > end || self.sst_count == 0 {
+            return 0;
+        }
+        self.storage.warm_range(start, end)
+    }
+
+    /// Drop the pages of the SST `sst_index` from the buffer pool and close its cached file handle, so the next read
+    /// of it is a miss, for example to measure cold reads or to free the memory of an SST known to be cold. Only the
+    /// `AppendOnlyLog` and `BTree` storages cache SSTs by index, the others are left as they are.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object.
+    /// * `sst_index` - The index of the SST, the `{index}` of its `{prefix}_{index}.{extension}` file name.
+    pub fn evict_sst_cache(&mut self, sst_index: u32) {
+        self.storage.evict_sst(sst_index);
+    }
+
+    /// Free the memory the `Client` DB kept from busier times, such as the buckets of a buffer pool holding fewer
+    /// pages than it once did. Cached pages are kept, so reads stay as fast.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object.
+    pub fn shrink_to_fit(&mut self) {
+        self.storage.shrink_to_fit();
+    }
+
+    /// Get the `(sst_name, page_offset)` of every page in the buffer pool of the `Client` DB, to check what is cached
+    /// when debugging eviction or thrashing. Empty for a storage type without a buffer pool. Only built with the
+    /// `debug` feature.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    #[cfg(feature = "debug")]
+    pub fn cached_pages(&self) -> Vec<(String, usize)> {
+        self.storage.cached_pages()
+    }
+
+    /// Get the median and 99th percentile latencies of the gets, puts and scans of the `Client` DB since it was
+    /// opened, each rounded up by at most 1/8. Only built with the `latency` feature.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    #[cfg(feature = "latency")]
+    pub fn latency_percentiles(&self) -> LatencyReport {
+        self.latencies.report()
+    }
+
+    /// Get the `SstFooter` of every SST of the `Client` DB, newest first, `None` for an SST written without one. Only
+    /// the footers are read, see `KVConfig::sst_footers`. Empty for a DB opened on a custom storage.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object.
+    pub fn sst_footers(&self) -> Result<Vec<Option<SstFooter>>, KvError> {
+        let Some(config) = self.config.as_ref() else {
+            return Ok(Vec::new());
+        };
+        get_sst_names(&self.name, &config.sst_naming())
+            .iter()
+            .map(|name| read_sst_footer(name))
+            .collect()
+    }
+
+    /// Get the number of bytes the `Client` DB takes on disk, summed over all the files in its directory.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object to measure.
+    pub fn disk_usage(&self) -> io::Result<u64> {
+        if !Path::new(&self.name).exists() {
+            return Ok(0);
+        }
+
+        let mut total: u64 = 0;
+        for entry in read_dir(&self.name)? {
+            let metadata = entry?.metadata()?;
+            if metadata.is_file() {
+                total += metadata.len();
+            }
+        }
+        Ok(total)
+    }
+
+    /// Close the `Client` DB. Flush if necessary, which also runs the merges it triggers, and clean up if the config
+    /// asks for it. Returns a `CloseReport` of the DB once flushed. Dropping the `Client` without closing it does the
+    /// same on a best effort basis, closing it returns the error if the DB could not be measured or removed.
+    ///  # Arguments
+    /// * `self` - The `Client` object to close.
+    pub fn close(mut self) -> io::Result<CloseReport> {
+        self.flush_memtable();
+        self.closed = true;
+
+        let report: CloseReport = CloseReport {
+            sst_count: self.sst_count,
+            disk_bytes: self.disk_usage()?,
+            cleaned_up: self.cleanup,
+        };
+        if self.cleanup {
+            self.remove_db_dir()?;
+        }
+        Ok(report)
+    }
+
+    /// Flush the memtable into an SST if it is not empty.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to flush it.
+    fn flush_memtable(&mut self) {
+        if self.memtable.size() > 0 {
+            self.flush();
+        }
+    }
+
+    /// Remove the directory of the `Client` DB. A DB that never wrote to disk has no directory to remove.
+    /// # Arguments
+    /// * `self` - A ref to the `Client` object to clean up.
+    fn remove_db_dir(&self) -> io::Result<()> {
+        files::forget_dir(&self.name);
+        match remove_dir_all(&self.name) {
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Flush the memtable into an SST.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to flush it.
+    fn flush(&mut self) {
+        debug_assert!(
+            self.memtable.is_balanced(),
+            "Client: memtable of height {} is not balanced!",
+            self.memtable.height()
+        );
+        #[cfg(feature = "tracing")]
+        let started: Instant = Instant::now();
+        let output_lst: Vec<(i64, i64)> = self.memtable.drain_sorted();
+        #[cfg(feature = "tracing")]
+        let (sst, keys): (u32, usize) = (self.sst_count, output_lst.len());
+
+        self.stored_entries = self.stored_entries.saturating_add(output_lst.len() as u64);
+        self.storage.flush(self.sst_count, output_lst);
+
+        self.sst_count = self.storage.compact(self.sst_count + 1);
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            db = %self.name,
+            sst,
+            keys,
+            ssts = self.sst_count,
+            elapsed = ?started.elapsed(),
+            "flush"
+        );
+    }
+
+    /// Write an already sorted run directly at a `level` of an `LSMTree` DB, skipping the merges of the levels
+    /// below it. The levels below `level` must be empty, and keys still in the memtable shadow the run.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to ingest into.
+    /// * `sorted` - The KV pairs of the run, in strictly ascending key order.
+    /// * `level` - The level to write the run at, starting from 1.
+    pub fn ingest_sorted_run(
+        &mut self,
+        sorted: Vec<(i64, i64)>,
+        level: u32,
+    ) -> Result<(), KvError> {
+        let entries: u64 = sorted.len() as u64;
+        self.storage.ingest_sorted_run(sorted, level)?;
+        self.stored_entries = self.stored_entries.saturating_add(entries);
+        self.sst_count += 1;
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: i64) {
+        self.memtable.put(key, i64::MIN)
+    }
+
+    /// Delete all the `keys` from the `Client` DB in one pass over the memtable, flushing it whenever it fills like
+    /// `put` does, so a batch no larger than the memtable flushes at most once. Keys that cannot be in the DB (nothing
+    /// flushed yet and not in the memtable) are skipped.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to delete from.
+    /// * `keys` - The keys to delete.
+    pub fn delete_batch(&mut self, keys: &[i64]) {
+        for &key in keys {
+            if self.sst_count == 0 && self.memtable.get(key).is_none() {
+                continue;
+            }
+            self.memtable.put(key, i64::MIN);
+            if self.memtable.size() >= self.memtable_size {
+                self.flush();
+            }
+        }
+    }
+
+    /// Delete every KV pair of the `Client` DB for which `f` returns `false`, keeping the others. The DB is scanned
+    /// with `scan_for_each` and the keys to delete are written with `delete_batch` a memtable size at a time, so at
+    /// most one memtable of them is held at once and the memtable is flushed as it fills. Panics like
+    /// `scan_for_each`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to delete from.
+    /// * `f` - The function called on each key and value, `true` to keep the pair.
+    pub fn retain(&mut self, mut f: impl FnMut(i64, i64) -> bool) {
+        let batch_size: usize = self.memtable_size.max(1) as usize;
+        let mut start: i64 = i64::MIN;
+        loop {
+            let mut deleted: Vec<i64> = Vec::with_capacity(batch_size);
+            let mut last_key: i64 = i64::MAX;
+            self.scan_for_each(start, i64::MAX, |key, value| {
+                if !f(key, value) {
+                    deleted.push(key);
+                }
+                last_key = key;
+                if deleted.len() < batch_size {
+                    ControlFlow::Continue(())
+                } else {
+                    ControlFlow::Break(())
+                }
+            });
+            let full: bool = deleted.len() == batch_size;
+            self.delete_batch(&deleted);
+
+            // the scan picks up after the last key it called `f` on, the deleted keys before it are shadowed
+            if !full || last_key == i64::MAX {
+                break;
+            }
+            start = last_key + 1;
+        }
+    }
+
+    /// Update the value of `key` in the `Client` DB only if the key is already in it. Returns `true` if the key was
+    /// found and its value changed, `false` otherwise without writing anything. Use `put` to insert or update.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Client` object to update a KV pair.
+    /// * `key` - The key to update.
+    /// * `value` - The new value of the key.
+    pub fn update(&mut self, key: i64, value: i64) -> bool {
+        // a live key of the memtable is updated in place, a tombstone there means the key is deleted
+        match self.memtable.get(key) {
+            Some(i64::MIN) => return false,
+            Some(_) => return self.memtable.update_value(key, value),
+            None => {}
+        }
+        if self.get(key).is_none() {
+            return false;
+        }
+        self.put(key, value);
+        true
+    }
+}
+
+// Special implementation of `FromIterator` for the `Client`.
+impl FromIterator<(i64, i64)> for Client {
+    /// Build an ephemeral `Client` DB with the `StorageType::Memory` storage out of KV pairs, later pairs overwrite
+    /// earlier ones with the same key. Nothing is written to disk and the DB is gone once the `Client` is dropped.
+    /// # Arguments
+    /// * `iter` - The KV pairs to insert.
+    fn from_iter<I: IntoIterator<Item = (i64, i64)>>(iter: I) -> Self {
+        let mut client: Client = Client::open(
+            "memory".to_string(),
+            KVConfig::default().storage_type(StorageType::Memory),
+        );
+        for (key, value) in iter {
+            client.put(key, value);
+        }
+        if client.memtable.size() > 0 {
+            client.flush();
+        }
+        client
+    }
+}
+
+// Special implementation of the drop function for the `Client`.
+impl Drop for Client {
+    /// Drop the `Client` DB. Close it and clean up if necessary, unless `close` already did.
+    /// # Argument
+    /// * `self` - A mutable ref to the `Client` object to drop.
+    fn drop(&mut self) {
+        if self.closed {
+            return;
+        }
+        self.flush_memtable();
+
+        if self.cleanup {
+            let _ = self.remove_db_dir();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod binary_tree {
+        mod get_value {
+            use crate::{Client, KVConfig};
+            use std::fs::create_dir_all;
+
+            #[test]
+            fn test_get_from_ssts() {
+                let mut kv: Client = Client::open(
+                    "getTestDB1".to_string(),
+                    KVConfig::default()
+                        .storage_type(crate::StorageType::AppendOnlyLog)
+                        .cleanup(true),
+                );
+
+                let folder_path: &str = "./getTestDB1/";
+                create_dir_all(folder_path).expect("Create dir all has failed!");
+
+                for i in 0..200 {
+                    kv.put(i as i64, (i * 2) as i64);
+                }
+
+                let key1: i64 = 12;
+                let key2: i64 = 110;
+
+                assert_eq!(Some(24), kv.get(key1));
+                assert_eq!(Some(220), kv.get(key2));
+            }
+        }
+
+        mod flush {
+            use crate::Client;
+            use crate::KVConfig;
+            use std::fs::create_dir_all;
+
+            #[test]
+            fn test_memtable_flush() {
                 let db_name: String = "flushTestDB1".to_string();
                 let folder_path_string: String = format!("./{}/", db_name);
-                let folder_path: &str = &folder_path_string.as_str();
+                let folder_path: &str = folder_path_string.as_str();
+
+                create_dir_all(folder_path).expect("Create dir all has failed!");
+
+                let mut kv: Client = Client::open(
+                    db_name,
+                    KVConfig::default()
+                        .storage_type(crate::StorageType::AppendOnlyLog)
+                        .cleanup(true),
+                );
+                for i in 0..=98 {
+                    kv.memtable.put(i, i);
+                }
+            }
+
+            #[test]
+            fn test_memtable_emptied_by_flush() {
+                let mut kv: Client = Client::open(
+                    "flushTestDB2".to_string(),
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(crate::StorageType::AppendOnlyLog)
+                        .cleanup(true),
+                );
+                for round in 1..=3 {
+                    for i in 0..100 {
+                        kv.put(i, i * round);
+                    }
+                    assert_eq!(kv.sst_count, round as u32);
+                    assert_eq!(kv.memtable.size(), 0);
+                    assert_eq!(kv.memtable.height(), 0);
+                    assert_eq!(kv.memtable.get(0), None);
+                }
+
+                // the same memtable keeps taking puts after the flushes
+                kv.put(0, -1);
+                assert_eq!(kv.memtable.size(), 1);
+                assert_eq!(Some(-1), kv.get(0));
+                assert_eq!(Some(99 * 3), kv.get(99));
+            }
+        }
+
+        mod scan_merge {
+            use crate::{Client, KVConfig, StorageType};
+
+            #[test]
+            fn test_scan_merge_matches_scan_hash() {
+                for (db_name, storage_type) in [
+                    ("scanMergeTestDB1", StorageType::AppendOnlyLog),
+                    ("scanMergeTestDB2", StorageType::BTree),
+                ] {
+                    let mut kv: Client = Client::open(
+                        db_name.to_string(),
+                        KVConfig::default()
+                            .memtable_size(100)
+                            .storage_type(storage_type)
+                            .cleanup(true),
+                    );
+
+                    // one SST with 0..100, then newer values, deletes and new keys in the memtable
+                    for i in 0..100 {
+                        kv.put(i, i * 2);
+                    }
+                    assert_eq!(kv.sst_count, 1);
+                    for i in 50..75 {
+                        kv.put(i, i * 3);
+                    }
+                    for i in 10..20 {
+                        kv.delete(i);
+                    }
+                    for i in 150..160 {
+                        kv.put(i, i);
+                    }
+
+                    let merged: Vec<(i64, i64)> = kv.scan_merge(5, 155).unwrap();
+                    let mut hashed: Vec<(i64, i64)> = kv.scan_hash(5, 155).unwrap();
+                    hashed.sort();
+
+                    assert_eq!(merged, hashed);
+                    assert_eq!(merged, kv.scan(5, 155));
+                    assert_eq!(merged.len(), 5 + 80 + 6);
+                    assert_eq!(merged[0], (5, 10));
+                    assert_eq!(merged[5], (20, 40));
+                    assert!(merged.contains(&(60, 180)));
+                }
+            }
+        }
+
+        mod delete {
+            use crate::{Client, KVConfig, StorageType};
+
+            fn open_log(db_name: &str) -> Client {
+                Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(StorageType::AppendOnlyLog)
+                        .cleanup(true),
+                )
+            }
+
+            #[test]
+            fn test_memtable_live_over_sst_tombstone() {
+                let mut kv: Client = open_log("deleteTestDB1");
+                for i in 0..100 {
+                    kv.put(i, i * 2);
+                }
+                for i in 100..199 {
+                    kv.put(i, i * 2);
+                }
+                // tombstone 5 in the second SST, then bring it back in the memtable
+                kv.delete(5);
+                kv.put(5, 55);
+
+                assert_eq!(kv.sst_count, 2);
+                assert_eq!(Some(55), kv.get(5));
+                let output: Vec<(i64, i64)> = kv.scan(0, 9);
+                assert_eq!(output.len(), 10);
+                assert!(output.contains(&(5, 55)));
+            }
+
+            #[test]
+            fn test_sst_tombstone_over_older_sst_live() {
+                let mut kv: Client = open_log("deleteTestDB2");
+                for i in 0..100 {
+                    kv.put(i, i * 2);
+                }
+                kv.delete(5);
+                for i in 100..199 {
+                    kv.put(i, i * 2);
+                }
+
+                assert_eq!(kv.sst_count, 2);
+                assert_eq!(None, kv.get(5));
+                let output: Vec<(i64, i64)> = kv.scan(0, 9);
+                assert_eq!(output.len(), 9);
+                assert!(output.iter().all(|a| a.0 != 5));
+            }
+
+            #[test]
+            fn test_memtable_tombstone_over_sst_live() {
+                let mut kv: Client = open_log("deleteTestDB3");
+                for i in 0..200 {
+                    kv.put(i, i * 2);
+                }
+                kv.delete(5);
+
+                assert_eq!(None, kv.get(5));
+                let output: Vec<(i64, i64)> = kv.scan(0, 9);
+                assert_eq!(output.len(), 9);
+                assert!(output.iter().all(|a| a.0 != 5));
+            }
+
+            #[test]
+            fn test_scan_visits_older_sst_for_last_key() {
+                let mut kv: Client = open_log("deleteTestDB4");
+                for i in 0..200 {
+                    kv.put(i, i * 2);
+                }
+                // all keys of the range but one are in the memtable, the last is only in the oldest SST
+                for i in 0..9 {
+                    kv.put(i, i * 3);
+                }
+
+                let mut output: Vec<(i64, i64)> = kv.scan(0, 9);
+                output.sort();
+                assert_eq!(output.len(), 10);
+                assert_eq!(output[9], (9, 18));
+            }
+        }
+
+        mod publish {
+            use crate::{Client, KVConfig, StorageType};
+            use std::fs::{read_dir, write};
+
+            #[test]
+            fn test_flush_leaves_no_temp_files() {
+                for (db_name, storage_type) in [
+                    ("publishTestDB1", StorageType::AppendOnlyLog),
+                    ("publishTestDB2", StorageType::BTree),
+                    ("publishTestDB3", StorageType::LSMTree),
+                ] {
+                    let mut kv: Client = Client::open(
+                        db_name.to_string(),
+                        KVConfig::default()
+                            .memtable_size(100)
+                            .storage_type(storage_type)
+                            .cleanup(true),
+                    );
+
+                    // two flushes, which also merges the LSM runs
+                    for i in 0..200 {
+                        kv.put(i, i * 2);
+                    }
+
+                    let names: Vec<String> = read_dir(db_name)
+                        .expect("Read dir has failed!")
+                        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                        .collect();
+                    assert!(!names.is_empty());
+                    assert!(names.iter().all(|name| !name.ends_with(".tmp")));
+
+                    for i in 0..200 {
+                        assert_eq!(Some(i * 2), kv.get(i));
+                    }
+                }
+            }
+
+            #[test]
+            fn test_unpublished_temp_file_is_ignored() {
+                let db_name: String = "publishTestDB4".to_string();
+                {
+                    let mut kv: Client = Client::open(
+                        db_name.clone(),
+                        KVConfig::default()
+                            .memtable_size(100)
+                            .storage_type(StorageType::AppendOnlyLog),
+                    );
+                    for i in 0..100 {
+                        kv.put(i, i * 2);
+                    }
+                }
+
+                // a partially written SST, as left behind by a crash mid flush
+                write(format!("{}/output_1.bin.tmp", db_name), [0u8; 100])
+                    .expect("Write has failed!");
+
+                let mut kv: Client = Client::open(
+                    db_name,
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(StorageType::AppendOnlyLog)
+                        .cleanup(true),
+                );
+                assert_eq!(kv.sst_count, 1);
+                assert_eq!(Some(24), kv.get(12));
+
+                for i in 100..200 {
+                    kv.put(i, i * 2);
+                }
+                assert_eq!(kv.sst_count, 2);
+                assert_eq!(Some(24), kv.get(12));
+                assert_eq!(Some(300), kv.get(150));
+            }
+        }
+
+        // mod scan {
+        //     use crate::Client;
+        //     use std::fs::{create_dir_all, remove_dir, remove_file};
+
+        //     #[test]
+        //     fn test_scan_from_tree_and_sst() {
+        //         let mut kv: Client =
+        //             Client::open("scanTestDB1".to_string(), 100, crate::StorageType::SST);
+
+        //         let folder_path: &str = "./scanTestDB1/";
+        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+
+        //         for i in 0..200 {
+        //             kv.put(i as i64, i * 2 as i64);
+        //         }
+
+        //         let output_lst = kv.scan(39, 167);
+
+        //         let mut j: i64 = 39;
+        //         for kv in output_lst.iter() {
+        //             assert_eq!(*kv, (j, j * 2));
+        //             j += 1;
+        //         }
+        //         assert!(j == 168);
+
+        //         for i in 0..kv.sst_num {
+        //             remove_file(format!("{}output_{}.bin", folder_path, i))
+        //                 .expect("Remove file has failed!");
+        //         }
+        //         remove_dir(folder_path).expect("Remove dir has failed!");
+        //     }
+
+        //     #[test]
+        //     fn test_scan_from_tree_and_sst_newer_values_in_tree() {
+        //         let mut kv: Client =
+        //             Client::open("scanTestDB2".to_string(), 100, crate::StorageType::SST);
+
+        //         let folder_path: &str = "./scanTestDB2/";
+        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+
+        //         // put in tree
+        //         for i in 0..50 {
+        //             kv.put(i as i64, i * 10 as i64);
+        //         }
+        //         for i in 50..100 {
+        //             kv.put(i, i * 2);
+        //         }
+        //         for i in 25..50 {
+        //             kv.put(i, i);
+        //         }
+
+        //         let output_lst = kv.scan(0, 125); // 0-25 is i * 10, 25-50 is i, 50-100 is i*2
+
+        //         // check!
+        //         for i in 0..25 {
+        //             assert!(output_lst[i] == (i as i64, i as i64 * 10));
+        //         }
+        //         for i in 25..50 {
+        //             assert!(output_lst[i] == (i as i64, i as i64));
+        //         }
+        //         for i in 50..100 {
+        //             assert!(output_lst[i] == (i as i64, i as i64 * 2));
+        //         }
+
+        //         for i in 0..kv.sst_num {
+        //             remove_file(format!("{}output_{}.bin", folder_path, i))
+        //                 .expect("Remove file has failed!");
+        //         }
+        //         remove_dir(folder_path).expect("Remove dir has failed!");
+        //     }
+        // }
+    }
+
+    mod btree {
+        mod get_value {
+            use crate::{Client, KVConfig};
+            use std::fs::create_dir_all;
+            use std::time::Instant;
+
+            #[test] // 20k
+            fn test_get_from_ssts_medium() {
+                let mut kv: Client = Client::open(
+                    "BTree_getTestDB1".to_string(),
+                    KVConfig::default()
+                        .storage_type(crate::StorageType::BTree)
+                        .cleanup(true),
+                );
+
+                let folder_path: &str = "./BTree_getTestDB1/";
+                create_dir_all(folder_path).expect("Create dir all has failed!");
+
+                for i in 0..20005 {
+                    kv.put(i as i64, (i * 2) as i64);
+                }
+
+                let key1: i64 = 3899;
+                let key2: i64 = 8763;
+                let key3: i64 = 20006;
+                let start_time = Instant::now();
+                assert_eq!(Some(3899 * 2), kv.get(key1));
+                let end_time = Instant::now();
+                // Calculate the time difference
+                let elapsed_time = end_time.duration_since(start_time);
+                // Print the time difference
+                println!("Elapsed time: {:?}", elapsed_time);
+
+                assert_eq!(Some(8763 * 2), kv.get(key2));
+                assert_eq!(None, kv.get(key3));
+            }
+
+            // #[test] // 200k, commented takes long
+            // fn test_get_from_ssts_large() {
+            //     let mut kv: KV = KV::open("BTree_getTestDB2".to_string(), 200000, 1);
+
+            //     let folder_path: &str = "./BTree_getTestDB2/";
+            //     create_dir_all(folder_path).expect("Create dir all has failed!");
+            //     for i in 0..200005 {
+            //         kv.put(i as i64, (i * 2) as i64);
+            //     }
+
+            //     let key1: i64 = 38990;
+            //     let key2: i64 = 8763;
+            //     let key3: i64 = 20006;
+            //     let start_time = Instant::now();
+            //     assert_eq!(Some(38990 * 2), kv.get(key1));
+            //     let end_time = Instant::now();
+            //     // Calculate the time difference
+            //     let elapsed_time = end_time.duration_since(start_time);
+            //     // Print the time difference
+            //     println!("Elapsed time: {:?}", elapsed_time);
+
+            //     // assert_eq!(Some(8763 * 2), kv.get(key2));
+            //     // assert_eq!(None, kv.get(key3));
+
+            //     for i in 0..kv.sst_num {
+            //         remove_file(format!("{}output_{}.bin", folder_path, i))
+            //             .expect("Remove file has failed!");
+            //     }
+            //     remove_dir(folder_path).expect("Remove dir has failed!");
+            // }
+        }
+        // mod scan {
+        //     use crate::Client;
+        //     use std::fs::{create_dir_all, remove_dir, remove_file};
+
+        //     #[test]
+        //     fn test_scan_from_tree_and_sst() {
+        //         let mut kv: Client = Client::open(
+        //             "BTree_scanTestDB1".to_string(),
+        //             20000,
+        //             crate::StorageType::BTree,
+        //         );
+
+        //         let folder_path: &str = "./BTree_scanTestDB1/";
+        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+
+        //         for i in 0..20009 {
+        //             kv.put(i as i64, i * 2 as i64);
+        //         }
+
+        //         let output_lst = kv.scan(13985, 19697);
+
+        //         let mut j: i64 = 13985;
+        //         for kv in output_lst.iter() {
+        //             assert_eq!(*kv, (j, j * 2));
+        //             j += 1;
+        //         }
+        //         assert!(j == 19698);
+
+        //         for i in 0..kv.sst_num {
+        //             remove_file(format!("{}output_{}.bin", folder_path, i))
+        //                 .expect("Remove file has failed!");
+        //         }
+        //         remove_dir(folder_path).expect("Remove dir has failed!");
+        //     }
+
+        //     #[test]
+        //     fn test_scan_from_tree_and_sst_large() {
+        //         // damn my mac cpu fanning vol +++++
+        //         let mut kv: KV = KV::open("BTree_scanTestDB2".to_string(), 50, 0);
+
+        //         let folder_path: &str = "./BTree_scanTestDB2/";
+        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+
+        //         for i in 0..10000007 {
+        //             kv.put(i as i64, i * 2 as i64);
+        //         }
+
+        //         let output_lst = kv.scan(9095, 300007);
+
+        //         let mut j: i64 = 9095;
+        //         for kv in output_lst.iter() {
+        //             assert_eq!(*kv, (j, j * 2));
+        //             j += 1;
+        //         }
+        //         assert!(j == 300008);
+
+        //         for i in 0..kv.sst_num {
+        //             remove_file(format!("{}output_{}.bin", folder_path, i))
+        //                 .expect("Remove file has failed!");
+        //         }
+        //         remove_dir(folder_path).expect("Remove dir has failed!");
+        //     }
+        // }
+    }
+
+    mod null {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_null_storage_discards_flushes() {
+            let mut kv: Client = Client::open(
+                "nullTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::Null)
+                    .cleanup(true),
+            );
+
+            for i in 0..150 {
+                kv.put(i, i * 2);
+            }
+
+            // the first 100 were flushed and discarded, the rest are still in the memtable
+            assert_eq!(None, kv.get(12));
+            assert_eq!(Some(240), kv.get(120));
+            assert_eq!(50, kv.scan(0, 200).len());
+        }
+    }
+
+    mod scan_into {
+        use crate::{Client, KVConfig, StorageType};
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn test_scan_into_accumulates_overlapping_scans() {
+            let mut kv: Client = Client::open(
+                "scanIntoTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+
+            for i in 0..250 {
+                kv.put(i, i * 2);
+            }
+
+            let mut out: BTreeMap<i64, i64> = BTreeMap::new();
+            kv.scan_into(10, 120, &mut out);
+
+            // newer values and deletes are picked up by the second, overlapping scan
+            kv.put(100, 7);
+            kv.delete(110);
+            kv.scan_into(90, 210, &mut out);
+
+            assert_eq!(out.len(), 201 - 1);
+            assert_eq!(out.first_key_value(), Some((&10, &20)));
+            assert_eq!(out.last_key_value(), Some((&210, &420)));
+            assert_eq!(out.get(&100), Some(&7));
+            assert_eq!(out.get(&110), None);
+            assert_eq!(out.get(&150), Some(&300));
+        }
+    }
+
+    mod config {
+        use crate::{Client, KVConfig, KvError, StorageType, MIN_BUFFERPOOL_SIZE};
+        use std::path::Path;
+
+        #[test]
+        fn test_validate_rejects_zero_memtable_size() {
+            let config: KVConfig = KVConfig::default().memtable_size(0);
+            assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+        }
+
+        #[test]
+        fn test_validate_zero_bufferpool_size() {
+            for storage_type in [StorageType::BTree, StorageType::LSMTree] {
+                let config: KVConfig = KVConfig::default()
+                    .bufferpool_size(0)
+                    .storage_type(storage_type);
+                assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+            }
+
+            // no buffer pool is used by the append only log
+            let config: KVConfig = KVConfig::default()
+                .bufferpool_size(0)
+                .storage_type(StorageType::AppendOnlyLog);
+            assert_eq!(Ok(()), config.validate());
+        }
+
+        #[test]
+        fn test_validate_small_bufferpool_size() {
+            for storage_type in [StorageType::BTree, StorageType::LSMTree] {
+                let config: KVConfig = KVConfig::default()
+                    .bufferpool_size(MIN_BUFFERPOOL_SIZE - 1)
+                    .storage_type(storage_type);
+                assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+                assert!(matches!(
+                    Client::try_open("configTestDB3".to_string(), config.cleanup(true)),
+                    Err(KvError::InvalidConfig(_))
+                ));
+
+                let config: KVConfig = KVConfig::default()
+                    .bufferpool_size(MIN_BUFFERPOOL_SIZE)
+                    .storage_type(storage_type);
+                assert_eq!(Ok(()), config.validate());
+            }
+        }
+
+        #[test]
+        fn test_validate_zero_bloom_bits_per_key() {
+            let config: KVConfig = KVConfig::default()
+                .bloom_bits_per_key(0)
+                .storage_type(StorageType::LSMTree);
+            assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+
+            let config: KVConfig = config.storage_type(StorageType::BTree);
+            assert_eq!(Ok(()), config.validate());
+        }
+
+        #[test]
+        fn test_validate_leaf_fill_factor() {
+            for fill_factor in [0.0, -0.5, 1.5, f32::NAN] {
+                let config: KVConfig = KVConfig::default().leaf_fill_factor(fill_factor);
+                assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+            }
+            let config: KVConfig = KVConfig::default().leaf_fill_factor(0.01);
+            assert_eq!(Ok(()), config.validate());
+        }
+
+        #[test]
+        fn test_try_open_invalid_config() {
+            let result = Client::try_open(
+                "configTestDB1".to_string(),
+                KVConfig::default()
+                    .bufferpool_size(0)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            assert!(matches!(result, Err(KvError::InvalidConfig(_))));
+            assert!(!Path::new("configTestDB1").exists());
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_open_invalid_config_panics() {
+            Client::open(
+                "configTestDB2".to_string(),
+                KVConfig::default().memtable_size(0).cleanup(true),
+            );
+        }
+    }
+
+    mod reopen {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_scan_after_reopen_new_writes_win() {
+            for (db_name, storage_type) in [
+                ("reopenTestDB1", StorageType::AppendOnlyLog),
+                ("reopenTestDB2", StorageType::BTree),
+            ] {
+                {
+                    let mut kv: Client = Client::open(
+                        db_name.to_string(),
+                        KVConfig::default()
+                            .memtable_size(100)
+                            .storage_type(storage_type),
+                    );
+                    for i in 0..300 {
+                        kv.put(i, i * 2);
+                    }
+                    // shadow some keys of the first SST with a newer one
+                    for i in 0..100 {
+                        kv.put(i, i * 3);
+                    }
+                }
+
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                for i in 200..250 {
+                    kv.put(i, i * 4);
+                }
+
+                let mut output: Vec<(i64, i64)> = kv.scan(0, 299);
+                output.sort();
+                assert_eq!(output.len(), 300);
+                for (key, value) in output {
+                    let expected: i64 = match key {
+                        0..=99 => key * 3,
+                        200..=249 => key * 4,
+                        _ => key * 2,
+                    };
+                    assert_eq!(expected, value);
+                }
+            }
+        }
+    }
+
+    mod ingest {
+        use crate::{Client, KVConfig, KvError, StorageType};
+
+        #[test]
+        fn test_ingest_two_runs_at_level_three() {
+            let mut kv: Client = Client::open(
+                "ingestTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+
+            let first: Vec<(i64, i64)> = (0..1000).map(|i| (i, i * 2)).collect();
+            let second: Vec<(i64, i64)> = (1000..2000).map(|i| (i, i * 3)).collect();
+            assert_eq!(Ok(()), kv.ingest_sorted_run(first, 3));
+            assert_eq!(Some(24), kv.get(12));
+
+            // the second run fills level 3 again, so both are merged into level 4
+            assert_eq!(Ok(()), kv.ingest_sorted_run(second, 3));
+            assert_eq!(Some(24), kv.get(12));
+            assert_eq!(Some(1998), kv.get(999));
+            assert_eq!(Some(4500), kv.get(1500));
+            assert_eq!(None, kv.get(2000));
+            assert_eq!(kv.scan(990, 1009).len(), 20);
+
+            // regular flushes still work on top of the ingested runs
+            for i in 0..100 {
+                kv.put(i, i * 5);
+            }
+            assert_eq!(Some(60), kv.get(12));
+            assert_eq!(Some(4500), kv.get(1500));
+        }
+
+        #[test]
+        fn test_ingest_rejected() {
+            let mut kv: Client = Client::open(
+                "ingestTestDB2".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            for i in 0..100 {
+                kv.put(i, i);
+            }
+
+            let run: Vec<(i64, i64)> = (200..300).map(|i| (i, i)).collect();
+            // level 1 holds a run, so level 3 is not the newest level
+            assert!(matches!(
+                kv.ingest_sorted_run(run.clone(), 3),
+                Err(KvError::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                kv.ingest_sorted_run(vec![(2, 2), (1, 1)], 2),
+                Err(KvError::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                kv.ingest_sorted_run(run.clone(), 0),
+                Err(KvError::InvalidArgument(_))
+            ));
+
+            let mut kv: Client = Client::open(
+                "ingestTestDB3".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            assert!(matches!(
+                kv.ingest_sorted_run(run, 1),
+                Err(KvError::Unsupported(_))
+            ));
+        }
+    }
+
+    mod disk_usage {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_disk_usage_grows_and_shrinks() {
+            let mut kv: Client = Client::open(
+                "diskUsageTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            assert_eq!(0, kv.disk_usage().unwrap());
+
+            for i in 0..100 {
+                kv.put(i, i);
+            }
+            let one_run: u64 = kv.disk_usage().unwrap();
+            assert!(one_run > 0);
+
+            // overwrite the same keys, the runs at levels 1 and 2 both hold all of them
+            for round in 0..2 {
+                for i in 0..100 {
+                    kv.put(i, i + round);
+                }
+            }
+            let two_runs: u64 = kv.disk_usage().unwrap();
+            assert!(two_runs > one_run);
+
+            // the next flush merges everything into a single run at level 3
+            for i in 0..100 {
+                kv.put(i, i * 2);
+            }
+            assert_eq!(one_run, kv.disk_usage().unwrap());
+        }
+    }
+
+    mod scan_capacity {
+        use crate::{Client, KVConfig};
+
+        #[test]
+        fn test_scan_capacity_hint() {
+            let mut kv: Client = Client::open(
+                "scanCapacityTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .scan_capacity(250)
+                    .cleanup(true),
+            );
+            assert_eq!(10, kv.scan_capacity_hint(0, 9));
+            assert_eq!(100, kv.scan_capacity_hint(0, 999));
+            assert_eq!(100, kv.scan_capacity_hint(i64::MIN, i64::MAX));
+
+            for i in 0..200 {
+                kv.put(i, i);
+            }
+            assert_eq!(250, kv.scan_capacity_hint(0, 999));
+            assert_eq!(0, kv.scan_capacity_hint(5, 4));
+
+            // scans larger than the capacity still return everything
+            assert_eq!(200, kv.scan(0, 999).len());
+        }
+    }
+
+    mod read_your_writes {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_reads_around_flush_boundaries() {
+            for (db_name, storage_type) in [
+                ("readWritesTestDB1", StorageType::AppendOnlyLog),
+                ("readWritesTestDB2", StorageType::BTree),
+                ("readWritesTestDB3", StorageType::LSMTree),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+
+                // keys cycle through a small set so most puts overwrite a key that was just flushed
+                for i in 0..2000 {
+                    let key: i64 = i % 97;
+                    kv.put(key, i);
+                    assert_eq!(Some(i), kv.get(key));
+                    if i >= 97 {
+                        assert_eq!(Some(i - 96), kv.get((i - 96) % 97));
+                    }
+                    if i % 7 == 0 {
+                        kv.delete(key);
+                        assert_eq!(None, kv.get(key));
+                        kv.put(key, i);
+                    }
+                }
+            }
+        }
+    }
+
+    mod sst_naming {
+        use crate::{Client, KVConfig, KvError, StorageType};
+        use std::fs::read_dir;
+
+        #[test]
+        fn test_custom_sst_naming() {
+            for (db_name, storage_type) in [
+                ("sstNamingTestDB1", StorageType::AppendOnlyLog),
+                ("sstNamingTestDB2", StorageType::BTree),
+                ("sstNamingTestDB3", StorageType::LSMTree),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(storage_type)
+                        .sst_prefix("part".to_string())
+                        .sst_extension("sst".to_string())
+                        .cleanup(true),
+                );
+                for i in 0..300 {
+                    kv.put(i, i * 2);
+                }
+
+                let names: Vec<String> = read_dir(db_name)
+                    .expect("Read dir has failed!")
+                    .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                    .collect();
+                assert!(!names.is_empty());
+                assert!(names
+                    .iter()
+                    .all(|name| name.starts_with("part_") && name.ends_with(".sst")));
+
+                assert_eq!(Some(24), kv.get(12));
+                assert_eq!(Some(398), kv.get(199));
+                assert_eq!(300, kv.scan(0, 299).len());
+            }
+        }
+
+        #[test]
+        fn test_invalid_sst_naming_rejected() {
+            for config in [
+                KVConfig::default().sst_prefix("".to_string()),
+                KVConfig::default().sst_prefix("../output".to_string()),
+                KVConfig::default().sst_extension("tmp".to_string()),
+                KVConfig::default().sst_extension("bin.tmp".to_string()),
+                KVConfig::default().sst_extension("".to_string()),
+            ] {
+                assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+            }
+        }
+    }
+
+    mod config_clone {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_clients_from_cloned_config() {
+            let base: KVConfig = KVConfig::default()
+                .memtable_size(100)
+                .storage_type(StorageType::BTree)
+                .cleanup(true);
+
+            let mut kv1: Client = Client::open("configCloneTestDB1".to_string(), base.clone());
+            let mut kv2: Client = Client::open(
+                "configCloneTestDB2".to_string(),
+                base.clone().memtable_size(50),
+            );
+            for i in 0..100 {
+                kv1.put(i, i);
+                kv2.put(i, i * 2);
+            }
+
+            assert_eq!(kv1.sst_count, 1);
+            assert_eq!(kv2.sst_count, 2);
+            assert_eq!(Some(12), kv1.get(12));
+            assert_eq!(Some(24), kv2.get(12));
+            assert_eq!(base.memtable_size, 100);
+        }
+    }
+
+    mod delete_batch {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_delete_batch_then_reinsert() {
+            let mut kv: Client = Client::open(
+                "deleteBatchTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(100)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..250 {
+                kv.put(i, i * 2);
+            }
+
+            // keys both in the SSTs and in the memtable, and some never inserted
+            let keys: Vec<i64> = (0..250).step_by(5).chain(1000..1010).collect();
+            kv.delete_batch(&keys);
+            assert_eq!(kv.sst_count, 3);
+
+            for key in &keys {
+                assert_eq!(None, kv.get(*key));
+            }
+            assert_eq!(Some(2), kv.get(1));
+            assert_eq!(200, kv.scan(0, 249).len());
+
+            for i in (0..250).step_by(10) {
+                kv.put(i, i * 3);
+            }
+            for key in keys {
+                let expected: Option<i64> = (key < 250 && key % 10 == 0).then_some(key * 3);
+                assert_eq!(expected, kv.get(key));
+            }
+        }
+
+        #[test]
+        fn test_delete_batch_skips_absent_keys() {
+            let mut kv: Client = Client::open(
+                "deleteBatchTestDB2".to_string(),
+                KVConfig::default().memtable_size(100).cleanup(true),
+            );
+            for i in 0..10 {
+                kv.put(i, i);
+            }
+
+            // nothing is flushed yet, so only the keys in the memtable need a tombstone
+            kv.delete_batch(&(5..500).collect::<Vec<i64>>());
+            assert_eq!(kv.memtable.size(), 10);
+            assert_eq!(kv.sst_count, 0);
+            assert_eq!(5, kv.scan(0, 500).len());
+        }
+
+        #[test]
+        fn test_delete_batch_larger_than_memtable() {
+            let mut kv: Client = Client::open(
+                "deleteBatchTestDB3".to_string(),
+                KVConfig::default().memtable_size(100).cleanup(true),
+            );
+            for i in 0..100 {
+                kv.put(i, i);
+            }
+
+            // the memtable is flushed each time it fills, so no SST is larger than it
+            kv.delete_batch(&(0..1050).collect::<Vec<i64>>());
+            assert_eq!(kv.sst_count, 11);
+            assert_eq!(kv.memtable.size(), 50);
+            assert_eq!(0, kv.scan(i64::MIN, i64::MAX).len());
+        }
+    }
+
+    mod corrupt_page {
+        use crate::serde::PAGE_SIZE;
+        use crate::{Client, KVConfig, KvError, ScanOutput, StorageType};
+        use std::collections::HashMap;
+        use std::fs::{remove_file, OpenOptions};
+        use std::io::ErrorKind;
+
+        #[test]
+        fn test_truncated_sst_is_an_error() {
+            let db_name: &str = "corruptPageTestDB1";
+            // 3 pages per SST
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(768)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..(768 * 2) {
+                kv.put(i, i * 2);
+            }
+
+            // cut the oldest SST in the middle of its second page
+            let file_path: String = format!("{}/output_0.bin", db_name);
+            OpenOptions::new()
+                .write(true)
+                .open(&file_path)
+                .expect("Open file has failed!")
+                .set_len((PAGE_SIZE + 100) as u64)
+                .expect("Set len has failed!");
+
+            // the newer SST is searched first and is intact
+            assert_eq!(Ok(Some(2000)), kv.try_get(1000));
+            assert_eq!(
+                Err(KvError::CorruptPage {
+                    file: format!("./{}/output_0.bin", db_name),
+                    offset: PAGE_SIZE,
+                }),
+                kv.try_get(10)
+            );
+            assert_eq!(
+                Err(KvError::CorruptPage {
+                    file: format!("./{}/output_0.bin", db_name),
+                    offset: PAGE_SIZE,
+                }),
+                kv.try_scan(0, 2000)
+            );
+        }
+
+        /// A `ScanOutput` removing an SST once the scan found its first KV pair.
+        struct RemoveOnAdd {
+            file_path: Option<String>,
+            found: HashMap<i64, i64>,
+        }
+
+        impl ScanOutput for RemoveOnAdd {
+            fn add(&mut self, key: i64, value: i64) {
+                if let Some(file_path) = self.file_path.take() {
+                    remove_file(file_path).expect("Remove file has failed!");
+                }
+                self.found.add(key, value);
+            }
+
+            fn count(&self) -> usize {
+                self.found.len()
+            }
+        }
+
+        #[test]
+        fn test_sst_removed_mid_scan_is_an_error() {
+            let db_name: &str = "corruptPageTestDB2";
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(256)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..(256 * 2) {
+                kv.put(i, i * 2);
+            }
+
+            // the newer SST is scanned first, the older one is gone by the time the scan gets to it
+            let mut hook: RemoveOnAdd = RemoveOnAdd {
+                file_path: Some(format!("{}/output_0.bin", db_name)),
+                found: HashMap::new(),
+            };
+            assert_eq!(
+                Err(KvError::Io {
+                    file: format!("./{}/output_0.bin", db_name),
+                    kind: ErrorKind::NotFound,
+                }),
+                kv.storage.try_scan(0, 511, &mut hook)
+            );
+            assert_eq!(256, hook.found.len());
+
+            // the SST is no longer listed, so a new scan only sees the newer one
+            assert_eq!(Ok(256), kv.try_scan(0, 511).map(|found| found.len()));
+        }
+    }
+
+    mod max_open_files {
+        use crate::{file_opens, Client, KVConfig};
+
+        #[test]
+        fn test_max_open_files_per_client() {
+            let mut first: Client = Client::open(
+                "maxOpenFilesTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .max_open_files(0)
+                    .cleanup(true),
+            );
+            let mut second: Client = Client::open(
+                "maxOpenFilesTestDB2".to_string(),
+                KVConfig::default().memtable_size(16).cleanup(true),
+            );
+            for key in 0..32 {
+                first.put(key, key);
+                second.put(key, key);
+            }
+
+            // the key is in the newest SST, so each get reads one page, opening the SST again only without open files
+            let opens: u64 = file_opens();
+            for _ in 0..4 {
+                assert_eq!(Some(20), first.get(20));
+            }
+            assert_eq!(4, file_opens() - opens);
+            let opens: u64 = file_opens();
+            for _ in 0..4 {
+                assert_eq!(Some(20), second.get(20));
+            }
+            assert!(file_opens() - opens <= 1);
+        }
+    }
+
+    mod empty_db {
+        use crate::files::file_opens;
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_empty_db_reads_skip_storage() {
+            for (db_name, storage_type) in [
+                ("emptyDBTestDB1", StorageType::AppendOnlyLog),
+                ("emptyDBTestDB2", StorageType::BTree),
+                ("emptyDBTestDB3", StorageType::LSMTree),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+
+                let opens: u64 = file_opens();
+                assert_eq!(None, kv.get(0));
+                assert!(kv.scan(i64::MIN, i64::MAX).is_empty());
+                kv.put(i64::MIN, 1);
+                kv.put(i64::MAX, 2);
+                assert_eq!(
+                    vec![(i64::MIN, 1), (i64::MAX, 2)],
+                    kv.scan(i64::MIN, i64::MAX)
+                );
+                assert_eq!(opens, file_opens());
+
+                // the full key range must not overflow once there are SSTs
+                for i in 0..64 * 3 {
+                    kv.put(i, i);
+                }
+                let mut output: Vec<(i64, i64)> = kv.scan(i64::MIN, i64::MAX);
+                output.sort();
+                assert_eq!(64 * 3 + 2, output.len());
+                assert_eq!((i64::MIN, 1), output[0]);
+                assert_eq!((i64::MAX, 2), output[64 * 3 + 1]);
+            }
+        }
+    }
+
+    mod retain {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_retain_even_keys() {
+            for (db_name, storage_type) in [
+                ("retainTestDB1", StorageType::AppendOnlyLog),
+                ("retainTestDB2", StorageType::BTree),
+                ("retainTestDB3", StorageType::LSMTree),
+                ("retainTestDB4", StorageType::Memory),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                // some keys only in SSTs, some overwritten in the memtable, some already deleted
+                for i in 0..1000 {
+                    kv.put(i, i);
+                }
+                for i in 900..1010 {
+                    kv.put(i, -i);
+                }
+                kv.delete(2);
+
+                let mut seen: usize = 0;
+                kv.retain(|key, _| {
+                    seen += 1;
+                    key % 2 == 0
+                });
+                assert_eq!(1009, seen);
+
+                for i in 0..1010 {
+                    let expected: Option<i64> = match i {
+                        2 => None,
+                        i if i % 2 == 1 => None,
+                        i if i >= 900 => Some(-i),
+                        i => Some(i),
+                    };
+                    assert_eq!(expected, kv.get(i), "key {}", i);
+                }
+                assert_eq!(504, kv.scan(i64::MIN, i64::MAX).len());
+                // the tombstones were flushed as the memtable filled
+                assert!(kv.memtable.size() < 64);
+            }
+        }
+
+        #[test]
+        fn test_retain_with_default_config() {
+            for (db_name, storage_type) in [
+                ("retainTestDB5", StorageType::AppendOnlyLog),
+                ("retainTestDB6", StorageType::BTree),
+                ("retainTestDB7", StorageType::LSMTree),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default().storage_type(storage_type).cleanup(true),
+                );
+                for i in 0..6000 {
+                    kv.put(i, i);
+                }
+
+                let mut seen: usize = 0;
+                kv.retain(|key, _| {
+                    seen += 1;
+                    key % 2 == 0
+                });
+                assert_eq!(6000, seen);
+                let found: Vec<(i64, i64)> = kv.scan(i64::MIN, i64::MAX);
+                assert_eq!(
+                    (0..6000).step_by(2).map(|i| (i, i)).collect::<Vec<_>>(),
+                    found
+                );
+            }
+        }
+    }
+
+    mod update {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_update_existing_keys_only() {
+            for (db_name, storage_type) in [
+                ("updateTestDB1", StorageType::AppendOnlyLog),
+                ("updateTestDB2", StorageType::BTree),
+                ("updateTestDB3", StorageType::LSMTree),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(16)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+
+                assert!(!kv.update(1, 10));
+                assert_eq!(None, kv.get(1));
+
+                // key 0 ends up in an SST, key 20 in the memtable
+                for i in 0..21 {
+                    kv.put(i, i);
+                }
+                assert!(kv.update(0, 100));
+                assert!(kv.update(20, 200));
+                assert_eq!(Some(100), kv.get(0));
+                assert_eq!(Some(200), kv.get(20));
+
+                // deleted keys do not exist anymore
+                kv.delete(5);
+                assert!(!kv.update(5, 500));
+                assert_eq!(None, kv.get(5));
+                assert!(!kv.update(21, 210));
+                assert_eq!(None, kv.get(21));
+            }
+        }
+    }
+
+    mod btree_merge {
+        use crate::{Client, KVConfig, SstFooter, StorageType};
+        use std::fs::read_dir;
+
+        fn sst_file_count(db_name: &str) -> usize {
+            read_dir(db_name)
+                .expect("Read dir has failed!")
+                .filter_map(Result::ok)
+                .count()
+        }
+
+        #[test]
+        fn test_many_flushes_are_merged() {
+            let db_name: &str = "btreeMergeTestDB1";
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .btree_merge_threshold(4)
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+
+            // keys cycle so newer SSTs overwrite and delete keys of older ones
+            for i in 0..16 * 4 {
+                kv.put(i % 40, i);
+            }
+            assert_eq!(4, sst_file_count(db_name));
+            kv.delete(3);
+            for i in 16 * 4..16 * 5 - 1 {
+                kv.put(i % 40, i);
+            }
+            assert_eq!(1, sst_file_count(db_name));
+
+            for i in 0..16 * 3 {
+                kv.put(i % 40 + 40, i);
+            }
+            assert_eq!(4, sst_file_count(db_name));
+            for key in 0..40 {
+                let expected: Option<i64> = match key {
+                    3 => None,
+                    39 => Some(39),
+                    _ => Some(key + 40),
+                };
+                assert_eq!(expected, kv.get(key));
+            }
+            let found: Vec<(i64, i64)> = kv.scan(40, 79);
+            assert_eq!(40, found.len());
+        }
+
+        #[test]
+        fn test_merge_disabled() {
+            let db_name: &str = "btreeMergeTestDB2";
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .btree_merge_threshold(0)
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+            for i in 0..16 * 20 {
+                kv.put(i, i);
+            }
+            assert_eq!(20, sst_file_count(db_name));
+        }
+
+        #[test]
+        fn test_merge_of_many_pages() {
+            let db_name: &str = "btreeMergeTestDB3";
+            let config: KVConfig = KVConfig::default()
+                .memtable_size(4000)
+                .btree_merge_threshold(2)
+                .leaf_fill_factor(0.5)
+                .sst_footers(true)
+                .storage_type(StorageType::BTree);
+            let mut kv: Client = Client::open(db_name.to_string(), config.clone());
+            // the third SST overwrites every other key of the first two, then the merge leaves one SST
+            for i in 0..8000 {
+                kv.put(i, i);
+            }
+            kv.delete_batch(&(0..4000).step_by(2).collect::<Vec<i64>>());
+            for i in (1..8000).step_by(2).take(2000) {
+                kv.put(i, -i);
+            }
+            assert_eq!(1, sst_file_count(db_name));
+            let footers: Vec<Option<SstFooter>> = kv.sst_footers().unwrap();
+            assert_eq!(1, footers.len());
+            let footer: SstFooter = footers[0].unwrap();
+            assert_eq!(
+                (6000, 1, 7999),
+                (footer.entries, footer.min_key, footer.max_key)
+            );
+            kv.close().unwrap();
+
+            let mut kv: Client = Client::try_open(
+                db_name.to_string(),
+                config.verify_on_open(true).cleanup(true),
+            )
+            .unwrap();
+            assert_eq!(None, kv.get(2));
+            assert_eq!(Some(-3), kv.get(3));
+            assert_eq!(Some(4000), kv.get(4000));
+            assert_eq!(6000, kv.scan(i64::MIN, i64::MAX).len());
+        }
+    }
+
+    mod from_iter {
+        use crate::Client;
+        use std::path::Path;
+
+        #[test]
+        fn test_collect_into_client() {
+            let mut kv: Client = (0..1000).map(|i| (i % 600, i)).collect();
+
+            assert_eq!(Some(900), kv.get(300));
+            assert_eq!(Some(599), kv.get(599));
+            assert_eq!(None, kv.get(600));
+            kv.delete(301);
+            let found: Vec<(i64, i64)> = kv.scan(299, 302);
+            assert_eq!(3, found.len());
+            assert!(found.contains(&(302, 902)));
+            assert!(!found.contains(&(301, 901)));
+            assert!(!Path::new("memory").exists());
+        }
+    }
+
+    mod scan_range {
+        use crate::{Client, KVConfig, StorageType};
+        use std::ops::Bound::{Excluded, Included, Unbounded};
+
+        fn sorted_keys(mut found: Vec<(i64, i64)>) -> Vec<i64> {
+            found.sort();
+            found.into_iter().map(|a| a.0).collect()
+        }
+
+        #[test]
+        fn test_scan_range_bounds() {
+            let mut kv: Client = Client::open(
+                "scanRangeTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(8)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for key in [i64::MIN, 1, 2, 3, 4, 5, i64::MAX] {
+                kv.put(key, 0);
+            }
+            for i in 10..30 {
+                kv.put(i, i);
+            }
+
+            assert_eq!(
+                vec![2, 3],
+                sorted_keys(kv.scan_range(Included(2), Excluded(4)))
+            );
+            assert_eq!(
+                vec![3, 4],
+                sorted_keys(kv.scan_range(Excluded(2), Included(4)))
+            );
+            assert_eq!(
+                vec![3],
+                sorted_keys(kv.scan_range(Excluded(2), Excluded(4)))
+            );
+            assert!(kv.scan_range(Excluded(3), Excluded(4)).is_empty());
+            assert_eq!(
+                vec![i64::MIN, 1, 2],
+                sorted_keys(kv.scan_range(Unbounded, Excluded(3)))
+            );
+            assert_eq!(
+                vec![28, 29, i64::MAX],
+                sorted_keys(kv.scan_range(Excluded(27), Unbounded))
+            );
+            assert_eq!(27, kv.scan_range(Unbounded, Unbounded).len());
+
+            // no key can be after i64::MAX or before i64::MIN
+            assert!(kv.scan_range(Excluded(i64::MAX), Unbounded).is_empty());
+            assert!(kv.scan_range(Unbounded, Excluded(i64::MIN)).is_empty());
+        }
+    }
+
+    mod custom_storage {
+        use crate::{Client, DiskStorage, KVConfig, ScanOutput};
+        use std::{
+            cell::{Cell, RefCell},
+            rc::Rc,
+        };
+
+        type SharedSsts = Rc<RefCell<Vec<Vec<(i64, i64)>>>>;
+
+        /// A storage keeping its SSTs in a shared vector, so they outlive the `Client`.
+        struct SharedStorage {
+            ssts: SharedSsts,
+        }
+
+        impl DiskStorage for SharedStorage {
+            fn get(&mut self, key: i64) -> Option<i64> {
+                self.ssts
+                    .borrow()
+                    .iter()
+                    .rev()
+                    .find_map(|sst| sst.iter().find(|a| a.0 == key).map(|a| a.1))
+            }
+
+            fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+                for sst in self.ssts.borrow().iter().rev() {
+                    for &(key, value) in sst.iter().filter(|a| start <= a.0 && a.0 <= end) {
+                        hash.add(key, value);
+                    }
+                }
+            }
+
+            fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+                let mut output: Vec<(i64, i64)> = Vec::new();
+                self.scan(start, end, &mut output);
+                output
+            }
+
+            fn flush(&mut self, _sst_count: u32, contents: Vec<(i64, i64)>) {
+                self.ssts.borrow_mut().push(contents);
+            }
+
+            fn initial_sst_count(&self) -> u32 {
+                self.ssts.borrow().len() as u32
+            }
+        }
+
+        #[test]
+        fn test_open_with_custom_storage() {
+            let ssts: SharedSsts = Rc::new(RefCell::new(Vec::new()));
+            let config: KVConfig = KVConfig::default().memtable_size(10);
+            {
+                let storage = Box::new(SharedStorage { ssts: ssts.clone() });
+                let mut kv: Client = Client::open_with_storage(
+                    "customStorageTestDB1".to_string(),
+                    config.clone(),
+                    storage,
+                )
+                .unwrap();
+                for i in 0..35 {
+                    kv.put(i % 20, i);
+                }
+                kv.delete(1);
+                assert_eq!(Some(30), kv.get(10));
+                assert_eq!(None, kv.get(1));
+                assert_eq!(19, kv.scan(0, 100).len());
+            }
+            // the last SST is flushed on close
+            assert_eq!(4, ssts.borrow().len());
+
+            // a new client sees the SSTs the storage already holds
+            let storage = Box::new(SharedStorage { ssts: ssts.clone() });
+            let mut kv: Client =
+                Client::open_with_storage("customStorageTestDB1".to_string(), config, storage)
+                    .unwrap();
+            assert_eq!(Some(25), kv.get(5));
+            assert_eq!(Some(19), kv.get(19));
+            assert_eq!(None, kv.get(20));
+        }
+
+        /// A `SharedStorage` counting the flushes it has finished.
+        struct CountingStorage {
+            inner: SharedStorage,
+            flushed: Rc<Cell<usize>>,
+        }
+
+        impl DiskStorage for CountingStorage {
+            fn get(&mut self, key: i64) -> Option<i64> {
+                self.inner.get(key)
+            }
+
+            fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+                self.inner.scan(start, end, hash);
+            }
+
+            fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+                self.inner.scan_sorted(start, end)
+            }
+
+            fn flush(&mut self, sst_count: u32, contents: Vec<(i64, i64)>) {
+                self.inner.flush(sst_count, contents);
+                self.flushed.set(self.flushed.get() + 1);
+            }
+        }
+
+        #[test]
+        fn test_flushes_block_puts() {
+            let ssts: SharedSsts = Rc::new(RefCell::new(Vec::new()));
+            let flushed: Rc<Cell<usize>> = Rc::new(Cell::new(0));
+            let storage = Box::new(CountingStorage {
+                inner: SharedStorage { ssts: ssts.clone() },
+                flushed: flushed.clone(),
+            });
+            let mut kv: Client = Client::open_with_storage(
+                "customStorageTestDB2".to_string(),
+                KVConfig::default().memtable_size(10),
+                storage,
+            )
+            .unwrap();
+
+            // the put filling the memtable returns once its flush is done, so no full memtable is ever queued
+            let mut flushes: usize = 0;
+            for i in 0..50 {
+                if kv.put_tracked(i, i).flushed {
+                    flushes += 1;
+                }
+                assert_eq!(flushes, flushed.get(), "put {}", i);
+                assert_eq!(flushes, ssts.borrow().len());
+            }
+            assert_eq!(5, flushes);
+            assert_eq!(Some(49), kv.get(49));
+        }
+    }
+
+    mod filter_stats {
+        use crate::{Client, FilterStats, KVConfig, StorageType};
+
+        fn missed_gets_stats(db_name: &str, bits_per_key: u64, seed: Option<u64>) -> FilterStats {
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .bloom_bits_per_key(bits_per_key)
+                    .bloom_seed(seed)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            // 8 flushes merged into a single run, only even keys
+            for i in 0..64 * 8 {
+                kv.put(i * 2, i);
+            }
+
+            for i in 0..64 * 8 {
+                assert_eq!(Some(i), kv.get(i * 2));
+            }
+            assert_eq!(0, kv.filter_stats().false_positives);
+            assert_eq!(0, kv.filter_stats().rejections);
+
+            for i in 0..1000 {
+                assert_eq!(None, kv.get(i * 2 + 1));
+            }
+            let stats: FilterStats = kv.filter_stats();
+            assert_eq!(64 * 8 + 1000, stats.rejections + stats.passes);
+            assert_eq!(stats.passes - 64 * 8, stats.false_positives);
+            stats
+        }
+
+        #[test]
+        fn test_false_positives_follow_filter_size() {
+            let undersized: FilterStats = missed_gets_stats("filterStatsTestDB1", 1, None);
+            assert!(undersized.false_positives > 500);
+
+            let sized: FilterStats = missed_gets_stats("filterStatsTestDB2", 10, None);
+            assert!(sized.false_positives < 20);
+            assert!(sized.rejections > 980);
+        }
+
+        #[test]
+        fn test_seeds_change_the_false_positives() {
+            // the same keys miss on other bits, and every DB still finds all of its keys
+            let stats: Vec<FilterStats> = [None, Some(1), Some(2)]
+                .into_iter()
+                .enumerate()
+                .map(|(i, seed)| missed_gets_stats(&format!("filterStatsTestDB{}", i + 4), 4, seed))
+                .collect();
+            assert_ne!(stats[0], stats[1]);
+            assert_ne!(stats[1], stats[2]);
+        }
+
+        #[test]
+        fn test_no_filter_stats_without_lsm() {
+            let mut kv: Client = Client::open(
+                "filterStatsTestDB3".to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..200 {
+                kv.put(i, i);
+                kv.get(i + 1000);
+            }
+            assert_eq!(FilterStats::default(), kv.filter_stats());
+        }
+    }
+
+    mod leaf_fill_factor {
+        use crate::serde::PAGE_SIZE;
+        use crate::{Client, KVConfig, StorageType};
+        use std::fs::metadata;
+
+        #[test]
+        fn test_half_full_leaves() {
+            let db_name: &str = "leafFillTestDB1";
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(1024)
+                    .leaf_fill_factor(0.5)
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+            for i in 0..1024 * 2 {
+                kv.put(i * 3, i);
+            }
+
+            // 1 internal page and 8 leaf pages of 128 KV pairs each
+            let file_size: u64 = metadata(format!("{}/output_0.bin", db_name))
+                .expect("Metadata call failed!")
+                .len();
+            assert_eq!(9 * PAGE_SIZE as u64, file_size);
+
+            for i in 0..1024 * 2 {
+                assert_eq!(Some(i), kv.get(i * 3));
+                assert_eq!(None, kv.get(i * 3 + 1));
+            }
+            let found: Vec<(i64, i64)> = kv.scan(100 * 3, 900 * 3 + 1);
+            assert_eq!(801, found.len());
+        }
+    }
+
+    mod iter_level {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_iter_lsm_levels() {
+            let mut kv: Client = Client::open(
+                "iterLevelTestDB1".to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            // 6 flushes in reverse key order: 4 merged at level 3, 2 merged at level 2
+            for i in (0..16 * 6).rev() {
+                kv.put(i, i * 2);
+            }
+            kv.delete(3);
+
+            assert_eq!(0, kv.iter_level(1).count());
+            let level_2: Vec<(i64, i64)> = kv.iter_level(2).collect();
+            assert_eq!(
+                (0..32).map(|i| (i, i * 2)).collect::<Vec<(i64, i64)>>(),
+                level_2
+            );
+            let level_3: Vec<(i64, i64)> = kv.iter_level(3).collect();
+            assert_eq!(
+                (32..96).map(|i| (i, i * 2)).collect::<Vec<(i64, i64)>>(),
+                level_3
+            );
+            assert_eq!(0, kv.iter_level(0).count());
+            assert_eq!(0, kv.iter_level(4).count());
+            assert_eq!(0, kv.iter_level(40).count());
+        }
+
+        #[test]
+        fn test_iter_level_without_levels() {
+            let mut kv: Client = Client::open(
+                "iterLevelTestDB2".to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..64 {
+                kv.put(i, i);
+            }
+            assert_eq!(0, kv.iter_level(1).count());
+        }
+    }
+
+    mod get_with_source {
+        use crate::{read_stats, reset_read_stats, Client, KVConfig, StorageType, ValueSource};
+
+        #[test]
+        fn test_value_sources() {
+            for (db_name, storage_type) in [
+                ("valueSourceTestDB1", StorageType::AppendOnlyLog),
+                ("valueSourceTestDB2", StorageType::BTree),
+                ("valueSourceTestDB3", StorageType::Memory),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(16)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                kv.put(1, 10);
+                assert_eq!(Some((10, ValueSource::Memtable)), kv.get_with_source(1));
+                // keys 1 to 16 in SST 0, 17 to 32 in SST 1
+                for i in 2..33 {
+                    kv.put(i, i);
+                }
+                kv.put(20, 200);
+
+                let sst = |run: u32| ValueSource::Sst { level: 0, run };
+                assert_eq!(Some((10, sst(0))), kv.get_with_source(1));
+                assert_eq!(Some((17, sst(1))), kv.get_with_source(17));
+                assert_eq!(Some((200, ValueSource::Memtable)), kv.get_with_source(20));
+                kv.delete(17);
+                assert_eq!(None, kv.get_with_source(17));
+                assert_eq!(None, kv.get_with_source(100));
+            }
+        }
+
+        #[test]
+        fn test_memtable_hits_skip_the_ssts() {
+            let mut kv: Client = Client::open(
+                "valueSourceTestDB5".to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for i in 0..17 {
+                kv.put(i, i);
+            }
+            kv.put(1, 10);
+            kv.delete(2);
+
+            reset_read_stats();
+            assert_eq!(Some(10), kv.get(1));
+            assert_eq!(None, kv.get(2));
+            assert_eq!(Some(16), kv.get(16));
+            assert_eq!(0, read_stats().pages);
+            assert_eq!(Some(3), kv.get(3));
+            assert_eq!(1, read_stats().pages);
+        }
+
+        #[test]
+        fn test_lsm_value_sources() {
+            let mut kv: Client = Client::open(
+                "valueSourceTestDB4".to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            );
+            // 3 flushes: the first 2 merged at level 2, the last at level 1
+            for i in 0..16 * 3 {
+                kv.put(i, i);
+            }
+            assert_eq!(
+                Some((3, ValueSource::Sst { level: 2, run: 2 })),
+                kv.get_with_source(3)
+            );
+            assert_eq!(
+                Some((40, ValueSource::Sst { level: 1, run: 3 })),
+                kv.get_with_source(40)
+            );
+        }
+    }
+
+    mod get_page_for_key {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_page_holds_the_key() {
+            for (db_name, storage_type) in [
+                ("pageForKeyTestDB1", StorageType::BTree),
+                ("pageForKeyTestDB2", StorageType::LSMTree),
+                ("pageForKeyTestDB3", StorageType::AppendOnlyLog),
+            ] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(1024)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                assert!(kv.get_page_for_key(0).is_empty());
+                // the even keys in 0..4096, 4 leaf pages per SST
+                for key in (0..4096).step_by(2) {
+                    kv.put(key, key);
+                }
+                // a newer SST overwriting a few keys
+                for key in (0..1024).map(|i| i * 4 + 1000) {
+                    kv.put(key, -key);
+                }
+
+                if matches!(storage_type, StorageType::AppendOnlyLog) {
+                    assert!(kv.get_page_for_key(1000).is_empty());
+                    continue;
+                }
+                for key in [0, 500, 1000, 2050, 4094] {
+                    let page: Vec<(i64, i64)> = kv.get_page_for_key(key);
+                    assert!(page.len() > 1 && page.len() <= 256, "{}", page.len());
+                    assert!(page.windows(2).all(|a| a[0].0 < a[1].0));
+                    let value: i64 = page.iter().find(|a| a.0 == key).expect("no key").1;
+                    assert_eq!(kv.get(key), Some(value), "key {}", key);
+                }
+                // an absent key gives the page it would be in
+                let page: Vec<(i64, i64)> = kv.get_page_for_key(501);
+                assert!(page.first().unwrap().0 < 501 && page.last().unwrap().0 > 501);
+            }
+        }
+    }
+
+    mod close {
+        use crate::serde::PAGE_SIZE;
+        use crate::{Client, CloseReport, KVConfig, StorageType};
+        use std::fs::{remove_file, File};
+        use std::path::Path;
+
+        #[test]
+        fn test_close_flushes_and_cleans_up() {
+            let db_name: &str = "closeTestDB1";
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::AppendOnlyLog),
+            );
+            for i in 0..20 {
+                kv.put(i, i);
+            }
+            assert!(kv.close().is_ok());
+
+            let mut kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(16)
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            assert_eq!(Some(19), kv.get(19));
+            assert!(kv.close().is_ok());
+            assert!(!Path::new(db_name).exists());
+
+            // nothing on disk to clean up
+            let kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::Memory)
+                    .cleanup(true),
+            );
+            assert!(kv.close().is_ok());
+        }
+
+        #[test]
+        fn test_close_reports_cleanup_failure() {
+            let db_name: &str = "closeTestDB2";
+            let kv: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            // a file in place of the DB directory cannot be removed as a directory
+            File::create(db_name).expect("File create has failed!");
+            assert!(kv.close().is_err());
+            remove_file(db_name).expect("Remove file has failed!");
+        }
+
+        #[test]
+        fn test_close_report() {
+            let db_name: &str = "closeTestDB3";
+            let config: KVConfig = KVConfig::default()
+                .memtable_size(16)
+                .storage_type(StorageType::AppendOnlyLog);
+            let mut kv: Client = Client::open(db_name.to_string(), config.clone());
+            for i in 0..20 {
+                kv.put(i, i);
+            }
+            let report: CloseReport = kv.close().unwrap();
+            assert_eq!(2, report.sst_count);
+            assert_eq!(2 * PAGE_SIZE as u64, report.disk_bytes);
+            assert!(!report.cleaned_up);
+            assert!(Path::new(db_name).exists());
+
+            let kv: Client = Client::open(db_name.to_string(), config.cleanup(true));
+            let report: CloseReport = kv.close().unwrap();
+            assert_eq!(2, report.sst_count);
+            assert!(report.cleaned_up);
+            assert!(!Path::new(db_name).exists());
+        }
+
+        #[test]
+        fn test_drop_without_close() {
+            let db_name: &str = "closeTestDB4";
+            let config: KVConfig = KVConfig::default()
+                .memtable_size(16)
+                .storage_type(StorageType::AppendOnlyLog);
+            {
+                let mut kv: Client = Client::open(db_name.to_string(), config.clone());
+                kv.put(1, 1);
+            }
+
+            // the drop flushed the memtable
+            let mut kv: Client = Client::open(db_name.to_string(), config.cleanup(true));
+            assert_eq!(Some(1), kv.get(1));
+            drop(kv);
+            assert!(!Path::new(db_name).exists());
+        }
+    }
+
+    mod delta_encoding {
+        use crate::{Client, KVConfig};
+
+        use std::{collections::BTreeMap, fs::metadata};
+
+        /// Helper function to write `keys` with both encodings and return the total SST sizes, fixed first.
+        fn sst_sizes(name: &str, keys: &[i64]) -> (u64, u64) {
+            let mut sizes: Vec<u64> = Vec::new();
+            for delta in [false, true] {
+                let db_name: String = format!("{}_{}", name, delta);
+                let mut client: Client = Client::open(
+                    db_name.clone(),
+                    KVConfig::default()
+                        .memtable_size(1 << 14)
+                        .delta_encoding(delta)
+                        .cleanup(true),
+                );
+                for &key in keys {
+                    client.put(key, key / 3);
+                }
+                client.flush();
+
+                for &key in keys {
+                    assert_eq!(Some(key / 3), client.get(key));
+                }
+                let mut scan: BTreeMap<i64, i64> = BTreeMap::new();
+                client.scan_into(i64::MIN + 1, i64::MAX, &mut scan);
+                assert_eq!(keys.len(), scan.len());
+                assert_eq!(None, client.get(i64::MAX - 7));
+
+                let size: u64 = (0..client.sst_count)
+                    .map(|i| {
+                        metadata(format!("{}/output_{}.bin", db_name, i))
+                            .unwrap()
+                            .len()
+                    })
+                    .sum();
+                sizes.push(size);
+            }
+            (sizes[0], sizes[1])
+        }
+
+        #[test]
+        fn test_delta_encoding_round_trip() {
+            let sequential: Vec<i64> = (0..10_000).collect();
+            let (fixed, delta) = sst_sizes("test_delta_encoding_sequential_DB", &sequential);
+            assert!(delta * 4 < fixed);
+
+            // random 32 bit keys, the deltas are larger so the gain is smaller
+            let mut state: u64 = 42;
+            let random: Vec<i64> = (0..10_000)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    (state >> 32) as i64
+                })
+                .collect();
+            let (fixed, delta) = sst_sizes("test_delta_encoding_random_DB", &random);
+            assert!(delta < fixed);
+        }
+    }
+
+    mod shrink_to_fit {
+        use crate::{read_stats, reset_read_stats, Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_shrink_to_fit_keeps_cached_pages() {
+            for storage_type in [StorageType::BTree, StorageType::LSMTree] {
+                let mut client: Client = Client::open(
+                    "test_shrink_to_fit_DB".to_string(),
+                    KVConfig::default()
+                        .storage_type(storage_type)
+                        .bufferpool_size(1024)
+                        .cleanup(true),
+                );
+                for key in 0..256 * 4 {
+                    client.put(key, -key);
+                }
+                for key in 0..256 * 4 {
+                    assert_eq!(Some(-key), client.get(key));
+                }
+
+                client.shrink_to_fit();
+                reset_read_stats();
+                for key in 0..256 * 4 {
+                    assert_eq!(Some(-key), client.get(key));
+                }
+                assert_eq!(0, read_stats().pages);
+            }
+        }
+    }
+
+    mod split {
+        use crate::{Client, KVConfig, SstFooter, StorageType};
+
+        use std::{collections::BTreeMap, fs::remove_dir_all, io, path::Path};
+
+        #[test]
+        fn test_split_by_pivot() {
+            for (i, storage_type) in [
+                StorageType::AppendOnlyLog,
+                StorageType::BTree,
+                StorageType::LSMTree,
+                StorageType::Memory,
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let db_name: String = format!("test_split_DB_{}", i);
+                let dest_name: String = format!("test_split_dest_DB_{}", i);
+                let config: KVConfig = KVConfig::default().storage_type(storage_type).cleanup(true);
+                let mut client: Client = Client::open(db_name.clone(), config);
+                for key in -1000..1000 {
+                    client.put(key, key * 7);
+                }
+                // shadowed and deleted values must not come back on either side
+                for key in (-1000..1000).step_by(3) {
+                    client.put(key, -key);
+                }
+                for key in (-1000..1000).step_by(10) {
+                    client.delete(key);
+                }
+                let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+                client.scan_into(i64::MIN, i64::MAX, &mut expected);
+
+                let mut other: Client = client.split(100, Path::new(&dest_name)).unwrap();
+
+                let mut kept: BTreeMap<i64, i64> = BTreeMap::new();
+                client.scan_into(i64::MIN, i64::MAX, &mut kept);
+                let mut moved: BTreeMap<i64, i64> = BTreeMap::new();
+                other.scan_into(i64::MIN, i64::MAX, &mut moved);
+                assert!(kept.keys().all(|key| *key < 100));
+                assert!(moved.keys().all(|key| *key >= 100));
+                assert_eq!(expected.len(), kept.len() + moved.len());
+                kept.append(&mut moved);
+                assert_eq!(expected, kept);
+
+                assert_eq!(Some(99 * 7), client.get(99));
+                assert_eq!(None, client.get(100));
+                assert_eq!(Some(102 * 7), other.get(102));
+                assert_eq!(None, other.get(-1));
+                assert_eq!(None, other.get(110));
+
+                // a split side keeps working as a DB of its own
+                client.put(500, 5);
+                assert_eq!(Some(5), client.get(500));
+                assert_eq!(
+                    Some(io::ErrorKind::AlreadyExists),
+                    client
+                        .split(0, Path::new("src"))
+                        .err()
+                        .map(|err| err.kind())
+                );
+            }
+        }
+
+        #[test]
+        fn test_split_rewrites_ssts() {
+            let config: KVConfig = KVConfig::default().memtable_size(10000).sst_footers(true);
+            for (i, config) in [
+                config.clone().storage_type(StorageType::AppendOnlyLog),
+                config
+                    .clone()
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .delta_encoding(true),
+                config
+                    .clone()
+                    .storage_type(StorageType::BTree)
+                    .leaf_fill_factor(0.5),
+                config.clone().storage_type(StorageType::LSMTree),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let db_name: String = format!("test_split_rewrite_DB_{}", i);
+                let dest_name: String = format!("test_split_rewrite_dest_DB_{}", i);
+                let lsm: bool = matches!(config.storage_type, StorageType::LSMTree);
+                // SST s holds the keys from 10000 * s to 10000 * s + 9999
+                let mut client: Client = Client::open(db_name.clone(), config.clone().cleanup(lsm));
+                for key in 0..30000 {
+                    client.put(key, key * 3);
+                }
+
+                let mut other: Client = client.split(15000, Path::new(&dest_name)).unwrap();
+                assert_eq!(15000, other.scan(i64::MIN, i64::MAX).len());
+                drop(other);
+                if !lsm {
+                    remove_dir_all(&dest_name).unwrap();
+                }
+                assert_eq!(15000, client.scan(i64::MIN, i64::MAX).len());
+                if lsm {
+                    // the emptied level 1 run is merged away
+                    assert_eq!(2, client.sst_count);
+                    assert!(client.iter_level(1).next().is_none());
+                    assert_eq!(15000, client.iter_level(2).count());
+                    continue;
+                }
+
+                // the SST of the moved keys only is removed, the one across the pivot is rewritten
+                assert_eq!(2, client.sst_count);
+                assert!(!Path::new(&format!("{}/output_2.bin", db_name)).exists());
+                let footers: Vec<Option<SstFooter>> = client.sst_footers().unwrap();
+                assert_eq!(
+                    vec![(5000, 10000, 14999), (10000, 0, 9999)],
+                    footers
+                        .into_iter()
+                        .map(|footer| footer.unwrap())
+                        .map(|footer| (footer.entries, footer.min_key, footer.max_key))
+                        .collect::<Vec<_>>()
+                );
+                client.close().unwrap();
+
+                let config: KVConfig = config.verify_on_open(true).cleanup(true);
+                let mut client: Client = Client::try_open(db_name.clone(), config).unwrap();
+                assert_eq!(Some(14999 * 3), client.get(14999));
+                assert_eq!(None, client.get(15000));
+                assert_eq!(15000, client.scan(i64::MIN, i64::MAX).len());
+            }
+        }
+
+        #[test]
+        fn test_split_pivot_between_pages() {
+            for (i, config) in [
+                KVConfig::default()
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .memtable_size(512),
+                KVConfig::default()
+                    .storage_type(StorageType::BTree)
+                    .memtable_size(512),
+                KVConfig::default().storage_type(StorageType::LSMTree),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let db_name: String = format!("test_split_gap_DB_{}", i);
+                let dest_name: String = format!("test_split_gap_dest_DB_{}", i);
+                let mut client: Client = Client::open(db_name, config.cleanup(true));
+                for key in 0..2000 {
+                    client.put(key * 1000, key);
+                }
+
+                // 255_000 ends the first page of 256 keys and 256_000 starts the second
+                let mut other: Client = client.split(255_500, Path::new(&dest_name)).unwrap();
+                let moved: Vec<(i64, i64)> = other.scan(i64::MIN, i64::MAX);
+                assert_eq!(
+                    (256..2000).map(|key| (key * 1000, key)).collect::<Vec<_>>(),
+                    moved
+                );
+                assert_eq!(256, client.scan(i64::MIN, i64::MAX).len());
+            }
+        }
+    }
+
+    mod hashed_keys {
+        use crate::{Client, KVConfig};
+
+        #[test]
+        fn test_put_get_hashed() {
+            let mut client: Client = Client::open(
+                "test_hashed_keys_DB".to_string(),
+                KVConfig::default().memtable_size(4).cleanup(true),
+            );
+            let names: [&str; 6] = ["apple", "banana", "cherry", "", "apples", "Apple"];
+            for (i, name) in names.iter().enumerate() {
+                client.put_hashed(name.as_bytes(), i as i64);
+            }
+            client.put_hashed(b"cherry", 20);
+
+            for (i, name) in names.iter().enumerate() {
+                let expected: i64 = if *name == "cherry" { 20 } else { i as i64 };
+                assert_eq!(Some(expected), client.get_hashed(name.as_bytes()));
+            }
+            // keys are only as distinct as their hashes, these do not collide
+            assert_eq!(None, client.get_hashed(b"durian"));
+        }
+    }
+
+    mod warm_range {
+        use crate::{read_stats, reset_read_stats, Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_warm_range_then_gets_hit_cache() {
+            let mut client: Client = Client::open(
+                "test_warm_range_DB".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::BTree)
+                    .memtable_size(256 * 8)
+                    .bufferpool_size(16)
+                    .cleanup(true),
+            );
+            for key in 0..256 * 16 {
+                client.put(key, key + 1);
+            }
+
+            // 2 SSTs of 8 leaves each, the range is in 2 leaves of the oldest
+            let pages: usize = client.warm_range(256 * 2, 256 * 4 - 1);
+            assert!(pages > 0 && pages <= 16);
+            reset_read_stats();
+            for key in (256 * 2..256 * 4).step_by(17) {
+                let expected: Option<i64> = Some(key + 1);
+                assert_eq!(expected, client.get(key));
+            }
+            assert_eq!(0, read_stats().pages);
+
+            // warming more than fits stops at the buffer pool size
+            assert_eq!(16, client.warm_range(0, i64::MAX));
+            assert_eq!(0, client.warm_range(10, 0));
+        }
+    }
+
+    mod verify_on_open {
+        use crate::{Client, CorruptionPolicy, KVConfig, KvError, SstFooter, StorageType};
+
+        use std::{
+            fs::{metadata, remove_dir_all, OpenOptions},
+            os::unix::fs::FileExt,
+            path::Path,
+        };
+
+        #[test]
+        fn test_verify_on_open_finds_corrupt_sst() {
+            for (i, storage_type) in [StorageType::AppendOnlyLog, StorageType::BTree]
+                .into_iter()
+                .enumerate()
+            {
+                let db_name: String = format!("test_verify_on_open_DB_{}", i);
+                let config: KVConfig = KVConfig::default().storage_type(storage_type);
+                let mut client: Client = Client::open(db_name.clone(), config.clone());
+                for key in 0..256 * 8 {
+                    client.put(key, key);
+                }
+                client.close().unwrap();
+
+                // a healthy DB opens either way
+                let client: Client =
+                    Client::try_open(db_name.clone(), config.clone().verify_on_open(true)).unwrap();
+                drop(client);
+
+                // put a too large key at the start of the last page of the oldest SST
+                let file_path: String = format!("{}/output_0.bin", db_name);
+                let file = OpenOptions::new().write(true).open(&file_path).unwrap();
+                let offset: u64 = file.metadata().unwrap().len() - 4096;
+                file.write_all_at(&i64::MAX.to_be_bytes(), offset).unwrap();
+                drop(file);
+
+                let err = Client::try_open(db_name.clone(), config.clone().verify_on_open(true))
+                    .err()
+                    .unwrap();
+                let expected = KvError::CorruptPage {
+                    file: format!("./{}", file_path),
+                    offset: offset as usize,
+                };
+                assert_eq!(expected, err);
+
+                // without the check the DB opens, and the other SSTs still read
+                let mut client: Client = Client::try_open(db_name.clone(), config).unwrap();
+                assert_eq!(Some(256 * 7 + 5), client.get(256 * 7 + 5));
+                drop(client);
+                remove_dir_all(&db_name).unwrap();
+            }
+        }
+
+        #[test]
+        fn test_sst_footers() {
+            for (i, storage_type) in [StorageType::AppendOnlyLog, StorageType::BTree]
+                .into_iter()
+                .enumerate()
+            {
+                let db_name: String = format!("test_sst_footers_DB_{}", i);
+                let config: KVConfig = KVConfig::default()
+                    .storage_type(storage_type)
+                    .sst_footers(true);
+                // SST s holds the keys from 256 * s to 256 * s + 255
+                let mut client: Client = Client::open(db_name.clone(), config.clone());
+                for key in 0..256 * 4 {
+                    client.put(key, key);
+                }
+
+                let footers: Vec<Option<SstFooter>> = client.sst_footers().unwrap();
+                assert_eq!(4, footers.len());
+                for (s, footer) in (0..4).rev().zip(footers) {
+                    let footer: SstFooter = footer.unwrap();
+                    let file_path: String = format!("{}/output_{}.bin", db_name, s);
+                    let pages: u64 = metadata(&file_path).unwrap().len() / 4096;
+                    assert_eq!(1, footer.version);
+                    assert_eq!(pages, footer.pages as u64);
+                    assert_eq!(256, footer.entries);
+                    assert_eq!((256 * s, 256 * s + 255), (footer.min_key, footer.max_key));
+                }
+                client.close().unwrap();
+
+                let config: KVConfig = config.verify_on_open(true);
+                let client: Client = Client::try_open(db_name.clone(), config.clone()).unwrap();
+                drop(client);
+
+                // cut the footer off SST 1, as a truncated write would
+                let file_path: String = format!("{}/output_1.bin", db_name);
+                let file = OpenOptions::new().write(true).open(&file_path).unwrap();
+                let pages_len: u64 = file.metadata().unwrap().len() / 4096 * 4096;
+                file.set_len(pages_len).unwrap();
+                drop(file);
+
+                let err = Client::try_open(db_name.clone(), config.clone())
+                    .err()
+                    .unwrap();
+                let expected = KvError::CorruptPage {
+                    file: format!("./{}", file_path),
+                    offset: pages_len as usize,
+                };
+                assert_eq!(expected, err);
+
+                // the pages are intact, so a DB not expecting footers opens and reads them
+                let mut client: Client =
+                    Client::try_open(db_name.clone(), config.sst_footers(false)).unwrap();
+                assert_eq!(None, client.sst_footers().unwrap()[2]);
+                assert_eq!(Some(256 + 5), client.get(256 + 5));
+                drop(client);
+                remove_dir_all(&db_name).unwrap();
+            }
+        }
+
+        #[test]
+        fn test_corruption_policies() {
+            let policies = [
+                CorruptionPolicy::Fail,
+                CorruptionPolicy::Skip,
+                CorruptionPolicy::Quarantine,
+            ];
+            for (i, storage_type) in [StorageType::AppendOnlyLog, StorageType::BTree]
+                .into_iter()
+                .enumerate()
+            {
+                for (j, policy) in policies.into_iter().enumerate() {
+                    let db_name: String = format!("test_corruption_policy_DB_{}_{}", i, j);
+                    let config: KVConfig = KVConfig::default().storage_type(storage_type);
+                    // SST s holds the keys from 256 * s to 256 * s + 255
+                    let mut client: Client = Client::open(db_name.clone(), config.clone());
+                    for key in 0..256 * 4 {
+                        client.put(key, key);
+                    }
+                    client.close().unwrap();
+
+                    // put a too large key at the start of the last page of SST 1
+                    let file_path: String = format!("{}/output_1.bin", db_name);
+                    let file = OpenOptions::new().write(true).open(&file_path).unwrap();
+                    let offset: u64 = file.metadata().unwrap().len() - 4096;
+                    file.write_all_at(&i64::MAX.to_be_bytes(), offset).unwrap();
+                    drop(file);
+
+                    let config: KVConfig = config.verify_on_open(true).on_corruption(policy);
+                    let opened = Client::try_open(db_name.clone(), config);
+                    if policy == CorruptionPolicy::Fail {
+                        assert!(matches!(opened, Err(KvError::CorruptPage { .. })));
+                        assert!(Path::new(&file_path).exists());
+                        remove_dir_all(&db_name).unwrap();
+                        continue;
+                    }
+
+                    let mut client: Client = opened.unwrap();
+                    let lost = KvError::CorruptPage {
+                        file: format!("./{}", file_path),
+                        offset: offset as usize,
+                    };
+                    assert_eq!(&[lost], client.lost_ssts());
+                    assert!(!Path::new(&file_path).exists());
+                    let quarantined: String = format!("{}/quarantine/output_1.bin", db_name);
+                    assert_eq!(
+                        policy == CorruptionPolicy::Quarantine,
+                        Path::new(&quarantined).exists()
+                    );
+
+                    // the keys of SST 1 are lost, the others still read
+                    assert_eq!(None, client.get(256 + 5));
+                    for sst in [0, 2, 3] {
+                        assert_eq!(Some(256 * sst + 5), client.get(256 * sst + 5));
+                    }
+                    // and new SSTs do not replace the old ones
+                    for key in 0..256 * 2 {
+                        client.put(key + 10_000, key);
+                    }
+                    assert_eq!(Some(256 * 3 + 5), client.get(256 * 3 + 5));
+                    assert_eq!(Some(5), client.get(10_005));
+                    assert_eq!(256 * 5, client.scan(0, i64::MAX).len());
+                    drop(client);
+                    remove_dir_all(&db_name).unwrap();
+                }
+            }
+        }
+
+        #[test]
+        fn test_merge_after_quarantine() {
+            let db_name: &str = "test_merge_after_quarantine_DB";
+            let config: KVConfig = KVConfig::default()
+                .storage_type(StorageType::BTree)
+                .btree_merge_threshold(3);
+            let mut client: Client = Client::open(db_name.to_string(), config.clone());
+            for key in 0..256 * 2 {
+                client.put(key, key);
+            }
+            client.close().unwrap();
+
+            // quarantining output_0 leaves output_1 as the oldest SST
+            let file_path: String = format!("{}/output_0.bin", db_name);
+            let file = OpenOptions::new().write(true).open(&file_path).unwrap();
+            let offset: u64 = file.metadata().unwrap().len() - 4096;
+            file.write_all_at(&i64::MAX.to_be_bytes(), offset).unwrap();
+            drop(file);
+            let config: KVConfig = config
+                .verify_on_open(true)
+                .on_corruption(CorruptionPolicy::Quarantine)
+                .cleanup(true);
+            let mut client: Client = Client::try_open(db_name.to_string(), config).unwrap();
+
+            // the second flush merges into output_1, the third must not be written over it
+            for key in 0..256 * 3 {
+                client.put(key + 100_000, key);
+            }
+            assert_eq!(Some(300), client.get(300));
+            assert_eq!(Some(5), client.get(100_005));
+            assert_eq!(Some(256 * 2 + 5), client.get(100_000 + 256 * 2 + 5));
+            assert_eq!(256 * 4, client.scan(0, i64::MAX).len());
+        }
+    }
+
+    mod snapshot {
+        use crate::{Client, KVConfig, StorageType};
+
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn test_snapshot_to_other_storage_type() {
+            let mut source: Client = Client::open(
+                "test_snapshot_source_DB".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::AppendOnlyLog)
+                    .cleanup(true),
+            );
+            for key in -2000..2000 {
+                source.put(key * 3, key);
+            }
+            for key in (-2000..2000).step_by(7) {
+                source.delete(key * 3);
+            }
+            source.put(i64::MAX, i64::MIN + 1);
+            let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+            source.scan_into(i64::MIN, i64::MAX, &mut expected);
+
+            let mut bytes: Vec<u8> = Vec::new();
+            source.export_snapshot(&mut bytes).unwrap();
+
+            let mut dest: Client = Client::open(
+                "test_snapshot_dest_DB".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+            let count: u64 = dest.import_snapshot(&bytes[..]).unwrap();
+            assert_eq!(expected.len() as u64, count);
+
+            let mut imported: BTreeMap<i64, i64> = BTreeMap::new();
+            dest.scan_into(i64::MIN, i64::MAX, &mut imported);
+            assert_eq!(expected, imported);
+            assert_eq!(None, dest.get(-6000));
+            assert_eq!(Some(1), dest.get(3));
+        }
+    }
+
+    mod btree_descent {
+        use crate::serde::PAGE_SIZE;
+        use crate::{Client, KVConfig, StorageType};
+        use std::fs::metadata;
+
+        #[test]
+        fn test_btree_gets_in_every_leaf() {
+            // SSTs of 1 to 5 leaves, so internal pages have up to 4 separators
+            for leaves in 1..=5 {
+                let mut client: Client = Client::open(
+                    format!("test_btree_descent_DB_{}", leaves),
+                    KVConfig::default()
+                        .storage_type(StorageType::BTree)
+                        .memtable_size(256 * leaves)
+                        .cleanup(true),
+                );
+                for key in 0..256 * leaves as i64 {
+                    client.put(key, key + 1);
+                }
+                for key in 0..256 * leaves as i64 {
+                    assert_eq!(Some(key + 1), client.get(key), "{} leaves", leaves);
+                }
+                assert_eq!(256 * leaves as usize, client.scan(0, i64::MAX).len());
+            }
+        }
+
+        #[test]
+        fn test_btree_gets_in_partial_last_leaf() {
+            // the last leaf page holds 1, 44, 255 and 1 KV pairs, the largest SST needs two internal pages
+            for count in [257_i64, 300, 511, 256 * 256 + 1] {
+                let db_name: String = format!("test_btree_partial_leaf_DB_{}", count);
+                let mut client: Client = Client::open(
+                    db_name.clone(),
+                    KVConfig::default()
+                        .storage_type(StorageType::BTree)
+                        .memtable_size(count as u32)
+                        .cleanup(true),
+                );
+                // even keys, so the odd ones around the last leaf are misses
+                for key in 0..count {
+                    client.put(key * 2, key);
+                }
+                // one internal level per 256 pointers until a single root page
+                let mut pages: u64 = (count as u64).div_ceil(256);
+                let mut level_pages: u64 = pages;
+                while level_pages > 1 {
+                    level_pages = level_pages.div_ceil(256);
+                    pages += level_pages;
+                }
+                let file_pages: u64 =
+                    metadata(format!("{}/output_0.bin", db_name)).unwrap().len() / PAGE_SIZE as u64;
+                assert_eq!(pages, file_pages, "{} keys", count);
+
+                for key in (0..count).rev().take(300) {
+                    assert_eq!(Some(key), client.get(key * 2), "{} keys", count);
+                    assert_eq!(None, client.get(key * 2 + 1), "{} keys", count);
+                }
+                assert_eq!(Some(0), client.get(0));
+                assert_eq!(None, client.get(count * 2));
+                assert_eq!(
+                    vec![(count * 2 - 4, count - 2), (count * 2 - 2, count - 1)],
+                    client.scan(count * 2 - 5, i64::MAX)
+                );
+            }
+        }
+    }
+
+    mod max_scan_keys {
+        use crate::{Client, KVConfig, KvError, StorageType};
+
+        #[test]
+        fn test_max_scan_keys() {
+            for storage_type in [StorageType::AppendOnlyLog, StorageType::BTree] {
+                let mut client: Client = Client::open(
+                    "test_max_scan_keys_DB".to_string(),
+                    KVConfig::default()
+                        .storage_type(storage_type)
+                        .btree_merge_threshold(2)
+                        .max_scan_keys(Some(512))
+                        .cleanup(true),
+                );
+                for key in 0..256 * 6 {
+                    client.put(key, key);
+                }
+
+                // a narrow range is allowed whatever the DB size
+                assert_eq!(100, client.try_scan(1000, 1099).unwrap().len());
+                assert_eq!(512, client.scan(0, 511).len());
+                // swapped bounds return nothing
+                assert_eq!(Ok(Vec::new()), client.try_scan(i64::MAX, i64::MIN));
+
+                // too wide for the DB, also once the BTree SSTs are merged into one
+                let err = client.try_scan(i64::MIN, i64::MAX).unwrap_err();
+                assert!(matches!(err, KvError::InvalidArgument(_)));
+                assert!(client.try_scan(0, 512).is_err());
+            }
+
+            // a DB smaller than the limit can be scanned whole
+            let mut client: Client = Client::open(
+                "test_max_scan_keys_small_DB".to_string(),
+                KVConfig::default().max_scan_keys(Some(512)).cleanup(true),
+            );
+            for key in 0..100 {
+                client.put(key, key);
+            }
+            assert_eq!(100, client.try_scan(i64::MIN, i64::MAX).unwrap().len());
+            assert!(KVConfig::default()
+                .max_scan_keys(Some(0))
+                .validate()
+                .is_err());
+        }
+
+        #[test]
+        fn test_max_scan_keys_counts_merged_ssts() {
+            let mut client: Client = Client::open(
+                "test_max_scan_keys_merged_DB".to_string(),
+                KVConfig::default()
+                    .delta_encoding(true)
+                    .max_ssts(Some(1))
+                    .max_scan_keys(Some(1000))
+                    .cleanup(true),
+            );
+            for key in 0..256 * 6 {
+                client.put(key, key);
+            }
+
+            // the single delta encoded SST takes less room than 1000 records, but holds every KV pair written
+            assert_eq!(1, client.sst_count);
+            assert!(client.disk_usage().unwrap() < 1000 * 16);
+            assert!(client.try_scan(i64::MIN, i64::MAX).is_err());
+            assert_eq!(1000, client.try_scan(0, 999).unwrap().len());
+        }
+
+        #[test]
+        fn test_max_scan_keys_counts_footers_on_reopen() {
+            let db_name: &str = "test_max_scan_keys_footers_DB";
+            let config: KVConfig = KVConfig::default()
+                .delta_encoding(true)
+                .sst_footers(true)
+                .max_ssts(Some(1))
+                .max_scan_keys(Some(1000));
+            let mut client: Client = Client::open(db_name.to_string(), config.clone());
+            for key in 0..256 * 6 {
+                client.put(key, key);
+            }
+            client.close().unwrap();
+
+            // the delta encoded SST takes less room than its records, its footer counts them all
+            let mut client: Client = Client::open(db_name.to_string(), config.cleanup(true));
+            assert!(client.disk_usage().unwrap() < 256 * 6 * 16);
+            assert_eq!(256 * 6, client.stored_entries);
+            assert!(client.try_scan(i64::MIN, i64::MAX).is_err());
+            assert_eq!(1000, client.try_scan(0, 999).unwrap().len());
+        }
+    }
+
+    mod reopen_next_index {
+        use crate::{Client, KVConfig};
+
+        use std::fs::{remove_dir_all, remove_file};
+
+        #[test]
+        fn test_reopen_after_missing_sst_index() {
+            let db_name: &str = "test_reopen_next_index_DB";
+            let config: KVConfig = KVConfig::default().memtable_size(4);
+            let mut client: Client = Client::open(db_name.to_string(), config.clone());
+            for key in 0..12 {
+                client.put(key, key);
+            }
+            client.close().unwrap();
+
+            // SSTs 0 and 2 are left, so 2 files but the next index is 3
+            remove_file(format!("{}/output_1.bin", db_name)).unwrap();
+            let mut client: Client = Client::open(db_name.to_string(), config.clone());
+            for key in 100..104 {
+                client.put(key, key);
+            }
+            client.close().unwrap();
+
+            let mut client: Client = Client::open(db_name.to_string(), config.cleanup(true));
+            for key in (0..4).chain(8..12).chain(100..104) {
+                assert_eq!(Some(key), client.get(key));
+            }
+            assert_eq!(None, client.get(5));
+            drop(client);
+            let _ = remove_dir_all(db_name);
+        }
+    }
+
+    #[cfg(feature = "debug")]
+    mod cached_pages {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_cached_pages_after_get() {
+            let db_name: &str = "test_cached_pages_DB";
+            let config: KVConfig = KVConfig::default()
+                .memtable_size(4)
+                .storage_type(StorageType::BTree)
+                .cleanup(true);
+            let mut client: Client = Client::open(db_name.to_string(), config);
+            for key in 0..4 {
+                client.put(key, key);
+            }
+            client.put(4, 4);
+            assert!(client.cached_pages().is_empty());
+
+            assert_eq!(Some(1), client.get(1));
+            let pages: Vec<(String, usize)> = client.cached_pages();
+            // a single leaf SST, only its page was read
+            assert_eq!(vec![(format!("./{}/output_0.bin", db_name), 0)], pages);
+        }
+    }
+
+    mod scan_prefix {
+        use crate::{Client, KVConfig};
+
+        /// Helper function to pack an `entity` id and a `field` into a key, the id in the high 32 bits.
+        fn pack(entity: i64, field: u32) -> i64 {
+            (entity << 32) | field as i64
+        }
+
+        #[test]
+        fn test_scan_prefix_mid_range() {
+            let db_name: &str = "test_scan_prefix_DB1";
+            let mut client: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default().memtable_size(8).cleanup(true),
+            );
+            for entity in 4..=6 {
+                for field in [0, 1, u32::MAX] {
+                    client.put(pack(entity, field), entity);
+                }
+            }
+
+            let expected: Vec<(i64, i64)> =
+                vec![(pack(5, 0), 5), (pack(5, 1), 5), (pack(5, u32::MAX), 5)];
+            let mut output: Vec<(i64, i64)> = client.scan_prefix(5, 32);
+            output.sort();
+            assert_eq!(expected, output);
+            assert!(client.scan_prefix(7, 32).is_empty());
+
+            // no prefix is every key, a full one is a single key
+            assert_eq!(9, client.scan_prefix(0, 0).len());
+            assert_eq!(vec![(pack(4, 1), 4)], client.scan_prefix(pack(4, 1), 64));
+        }
+
+        #[test]
+        fn test_scan_prefix_top_and_bottom_of_range() {
+            let db_name: &str = "test_scan_prefix_DB2";
+            let mut client: Client =
+                Client::open(db_name.to_string(), KVConfig::default().cleanup(true));
+            client.put(i64::MAX, 1);
+            client.put(i64::MAX - 1, 2);
+            client.put(pack(i32::MAX as i64 - 1, 0), 3);
+            client.put(i64::MIN + 1, 4);
+            client.put(-1, 5);
+
+            // (prefix + 1) << 32 would overflow
+            assert_eq!(
+                vec![(i64::MAX - 1, 2), (i64::MAX, 1)],
+                client.scan_prefix(i32::MAX as i64, 32)
+            );
+            // the sign bit alone splits the keys in half
+            assert_eq!(vec![(i64::MIN + 1, 4), (-1, 5)], client.scan_prefix(1, 1));
+            assert_eq!(vec![(-1, 5)], client.scan_prefix(-1, 32));
+            assert_eq!(vec![(-1, 5)], client.scan_prefix(u32::MAX as i64, 32));
+        }
+    }
+
+    #[cfg(feature = "rayon")]
+    mod encode_threads {
+        use crate::{Client, KVConfig, KvError, StorageType};
+
+        use std::fs::{read, read_dir};
+
+        #[test]
+        fn test_parallel_ingest_matches_sequential() {
+            let run: Vec<(i64, i64)> = (0..256 * 512 + 100).map(|i| (i * 3, i)).collect();
+            let mut files: Vec<Vec<(String, Vec<u8>)>> = Vec::new();
+            for (db_name, threads) in [("encodeThreadsTestDB1", 1), ("encodeThreadsTestDB2", 4)] {
+                let mut kv: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(100)
+                        .storage_type(StorageType::LSMTree)
+                        .encode_threads(threads)
+                        .cleanup(true),
+                );
+                assert_eq!(Ok(()), kv.ingest_sorted_run(run.clone(), 5));
+                for i in 0..200 {
+                    kv.put(i * 7, -i);
+                }
+
+                assert_eq!(Some(1000), kv.get(3000));
+                assert_eq!(Some(-3), kv.get(21));
+                assert_eq!(None, kv.get(3001));
+                assert_eq!(run.len() + 133, kv.scan(i64::MIN, i64::MAX).len());
+
+                // the same runs are written byte for byte
+                let mut db_files: Vec<(String, Vec<u8>)> = read_dir(db_name)
+                    .unwrap()
+                    .map(|entry| entry.unwrap())
+                    .map(|entry| {
+                        let file_name: String = entry.file_name().to_string_lossy().into_owned();
+                        (file_name, read(entry.path()).unwrap())
+                    })
+                    .collect();
+                db_files.sort();
+                files.push(db_files);
+            }
+            assert_eq!(files[0], files[1]);
+        }
+
+        #[test]
+        fn test_zero_encode_threads_rejected() {
+            let config: KVConfig = KVConfig::default().encode_threads(0);
+            assert!(matches!(config.validate(), Err(KvError::InvalidConfig(_))));
+        }
+    }
+
+    #[cfg(feature = "latency")]
+    mod latency_percentiles {
+        use crate::{Client, KVConfig, LatencyReport};
+
+        #[test]
+        fn test_latency_percentiles_count_operations() {
+            let db_name: &str = "test_latency_percentiles_DB";
+            let mut client: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default().memtable_size(16).cleanup(true),
+            );
+            assert_eq!(LatencyReport::default(), client.latency_percentiles());
+
+            for key in 0..100 {
+                client.put(key, key);
+            }
+            for key in 0..50 {
+                client.get(key);
+            }
+            client.scan(0, 99);
+
+            let report: LatencyReport = client.latency_percentiles();
+            assert_eq!(100, report.put.count);
+            assert_eq!(50, report.get.count);
+            assert_eq!(1, report.scan.count);
+            assert!(report.put.p50 <= report.put.p99);
+            assert_eq!(report.scan.p50, report.scan.p99);
+        }
+    }
+
+    mod audit_range {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_audit_range_across_memtable_and_ssts() {
+            for storage_type in [
+                StorageType::AppendOnlyLog,
+                StorageType::BTree,
+                StorageType::LSMTree,
+            ] {
+                let db_name: &str = "test_audit_range_DB";
+                let mut client: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                // multiples of 3 with newer values in later SSTs, deletes, and the last puts left in the memtable
+                for key in (0..1000).step_by(3) {
+                    client.put(key, key);
+                }
+                for key in (0..1000).step_by(9) {
+                    client.put(key, -key);
+                }
+                for key in (0..1000).step_by(27) {
+                    client.delete(key);
+                }
+                for key in 2000..2010 {
+                    client.put(key, key);
+                }
+
+                assert!(client.audit_range(0, 999));
+                assert!(client.audit_range(-10, 2020));
+                assert!(client.audit_range(500, 510));
+                assert!(client.audit_range(10, 0));
+            }
+        }
+    }
+
+    mod exact_len {
+        use crate::{Client, KVConfig, KvError, StorageType};
+        use std::fs::OpenOptions;
+
+        #[test]
+        fn test_exact_len_after_overwrites_and_deletes() {
+            for storage_type in [
+                StorageType::AppendOnlyLog,
+                StorageType::BTree,
+                StorageType::LSMTree,
+                StorageType::Memory,
+            ] {
+                let db_name: &str = "test_exact_len_DB";
+                let mut client: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(32)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                assert_eq!(0, client.exact_len().unwrap());
+
+                for key in 0..500 {
+                    client.put(key, key);
+                }
+                // overwrites across SSTs do not add keys
+                for key in 0..250 {
+                    client.put(key, -key);
+                }
+                // deletes of 50 live keys and of keys never put
+                for key in (0..900).step_by(10).chain(500..510) {
+                    client.delete(key);
+                }
+                // one deleted key comes back
+                client.put(10, 10);
+                client.put(i64::MIN + 1, 0);
+                client.put(i64::MAX, 0);
+
+                assert_eq!(500 - 50 + 1 + 2, client.exact_len().unwrap());
+            }
+        }
+
+        #[test]
+        fn test_exact_len_of_corrupt_sst_is_an_error() {
+            let db_name: &str = "test_exact_len_DB_corrupt";
+            let mut client: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default().memtable_size(32).cleanup(true),
+            );
+            for key in 0..64 {
+                client.put(key, key);
+            }
+            assert_eq!(Ok(64), client.exact_len());
+
+            // a partial page, as left behind by a truncated write
+            let file_path: String = format!("{}/output_0.bin", db_name);
+            OpenOptions::new()
+                .write(true)
+                .open(&file_path)
+                .and_then(|file| file.set_len(100))
+                .unwrap();
+            assert_eq!(
+                Err(KvError::CorruptPage {
+                    file: format!("./{}", file_path),
+                    offset: 0,
+                }),
+                client.exact_len()
+            );
+        }
+    }
+
+    mod is_empty {
+        use crate::{Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_is_empty_agrees_with_exact_len() {
+            for (i, storage_type) in [
+                StorageType::AppendOnlyLog,
+                StorageType::BTree,
+                StorageType::LSMTree,
+                StorageType::Memory,
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let mut client: Client = Client::open(
+                    format!("test_is_empty_DB_{}", i),
+                    KVConfig::default()
+                        .memtable_size(32)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                // fresh
+                assert!(client.is_empty());
+
+                // populated, with live keys in the memtable then only in the SSTs
+                for key in 0..100 {
+                    client.put(key, key);
+                }
+                assert!(!client.is_empty());
+                let deleted: Vec<i64> = (0..96).collect();
+                client.delete_batch(&deleted[..64]);
+                assert!(!client.is_empty());
+
+                // every key deleted, the deletes in the SSTs and the memtable
+                client.delete_batch(&deleted[64..]);
+                for key in 96..100 {
+                    client.delete(key);
+                }
+                assert!(client.is_empty());
+                assert_eq!(0, client.exact_len().unwrap());
+
+                // and a key put back
+                client.put(i64::MAX, 0);
+                assert!(!client.is_empty());
+                assert_eq!(1, client.exact_len().unwrap());
+            }
+        }
+    }
+
+    mod get_range_as_map {
+        use crate::{Client, KVConfig, StorageType};
+        use std::collections::HashMap;
+
+        #[test]
+        fn test_get_range_as_map_matches_scan() {
+            for storage_type in [StorageType::BTree, StorageType::Memory] {
+                let db_name: &str = "test_get_range_as_map_DB";
+                let mut client: Client = Client::open(
+                    db_name.to_string(),
+                    KVConfig::default()
+                        .memtable_size(16)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                for key in 0..100 {
+                    client.put(key, key * 2);
+                }
+                for key in (0..100).step_by(7) {
+                    client.delete(key);
+                }
+                client.put(50, -1);
+
+                for (start, end) in [(0, 99), (20, 40), (95, 200), (-5, -1), (10, 0)] {
+                    let expected: HashMap<i64, i64> = client.scan(start, end).into_iter().collect();
+                    assert_eq!(expected, client.get_range_as_map(start, end));
+                }
+                let map: HashMap<i64, i64> = client.get_range_as_map(0, 99);
+                assert_eq!(100 - 15, map.len());
+                assert_eq!(Some(&-1), map.get(&50));
+                assert_eq!(None, map.get(&7));
+            }
+        }
+    }
+
+    mod dense_keys {
+        use crate::{read_stats, reset_read_stats, Client, KVConfig, StorageType};
+        use std::collections::BTreeMap;
+
+        /// Helper function to check every get and a few scans of `client` against the `expected` KV pairs.
+        fn check(client: &mut Client, expected: &BTreeMap<i64, i64>, keys: std::ops::Range<i64>) {
+            for key in keys.clone() {
+                assert_eq!(expected.get(&key).copied(), client.get(key), "key {}", key);
+            }
+            for (start, end) in [(keys.start, keys.end), (-10, 5), (300, 777), (1000, 1000)] {
+                let mut scanned: Vec<(i64, i64)> = client.scan(start, end);
+                scanned.sort();
+                let wanted: Vec<(i64, i64)> = expected
+                    .range(start..=end)
+                    .map(|(key, value)| (*key, *value))
+                    .collect();
+                assert_eq!(wanted, scanned, "scan from {} to {}", start, end);
+            }
+        }
+
+        #[test]
+        fn test_dense_keys_gets_and_scans() {
+            let mut client: Client = Client::open(
+                "test_dense_keys_DB".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::BTree)
+                    .memtable_size(700)
+                    .dense_keys(Some(0))
+                    .cleanup(true),
+            );
+            let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+            for key in 0..2100 {
+                client.put(key, key * 3);
+                expected.insert(key, key * 3);
+            }
+            check(&mut client, &expected, -5..2105);
+        }
+
+        #[test]
+        fn test_dense_keys_fall_back_on_sparse_data() {
+            for fill_factor in [1.0, 0.5] {
+                let mut client: Client = Client::open(
+                    "test_dense_keys_sparse_DB".to_string(),
+                    KVConfig::default()
+                        .storage_type(StorageType::BTree)
+                        .memtable_size(600)
+                        .leaf_fill_factor(fill_factor)
+                        .dense_keys(Some(0))
+                        .cleanup(true),
+                );
+                let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+                // gaps in every SST, and newer SSTs overwriting and deleting some older keys
+                for key in (0..3000).filter(|key| key % 7 != 3 && key % 11 != 5) {
+                    client.put(key, key + 1);
+                    expected.insert(key, key + 1);
+                }
+                for key in (0..3000).step_by(13) {
+                    client.put(key, -key);
+                    expected.insert(key, -key);
+                }
+                for key in (0..3000).step_by(17) {
+                    client.delete(key);
+                    expected.remove(&key);
+                }
+                check(&mut client, &expected, -5..3005);
+            }
+        }
+
+        #[test]
+        fn test_dense_keys_skip_the_internal_pages() {
+            let mut pages: Vec<u64> = Vec::new();
+            for dense_keys in [None, Some(0)] {
+                // one SST of 4 leaves under an internal page, dropped from the pool before each get
+                let mut client: Client = Client::open(
+                    "test_dense_keys_pages_DB".to_string(),
+                    KVConfig::default()
+                        .storage_type(StorageType::BTree)
+                        .memtable_size(1024)
+                        .dense_keys(dense_keys)
+                        .cleanup(true),
+                );
+                for key in 0..1024 {
+                    client.put(key, key);
+                }
+                client.put(1024, 1024);
+                assert_eq!(Some(0), client.get(0));
+
+                reset_read_stats();
+                for key in (1..1024).step_by(100) {
+                    client.evict_sst_cache(0);
+                    assert_eq!(Some(key), client.get(key));
+                }
+                pages.push(read_stats().pages);
+            }
+            // 11 gets of 2 pages each, against 1 page each
+            assert_eq!(vec![22, 11], pages);
+        }
+    }
+
+    mod scan_order {
+        use crate::{Client, KVConfig, StorageType};
+        use std::collections::BTreeMap;
+
+        #[test]
+        fn test_scan_results_identical_across_storage_types() {
+            let ranges: [(i64, i64); 5] = [
+                (i64::MIN, i64::MAX),
+                (0, 99),
+                (250, 1250),
+                (-40, 3),
+                (1999, 1999),
+            ];
+            let mut results: Vec<Vec<Vec<(i64, i64)>>> = Vec::new();
+            for (i, storage_type) in [
+                StorageType::AppendOnlyLog,
+                StorageType::BTree,
+                StorageType::LSMTree,
+                StorageType::Memory,
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let mut client: Client = Client::open(
+                    format!("test_scan_order_DB_{}", i),
+                    KVConfig::default()
+                        .memtable_size(128)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+                // keys out of order across several SSTs, newer values and deletes in later SSTs and the memtable
+                for i in 0..2000 {
+                    let key: i64 = (i * 7919) % 2000 - 20;
+                    client.put(key, i);
+                    expected.insert(key, i);
+                }
+                for key in (0..2000).step_by(3) {
+                    client.put(key, -key);
+                    expected.insert(key, -key);
+                }
+                for key in (-20..2000).step_by(5) {
+                    client.delete(key);
+                    expected.remove(&key);
+                }
+
+                let scans: Vec<Vec<(i64, i64)>> = ranges
+                    .iter()
+                    .map(|&(start, end)| client.scan(start, end))
+                    .collect();
+                for (&(start, end), scan) in ranges.iter().zip(&scans) {
+                    let wanted: Vec<(i64, i64)> = expected
+                        .range(start..=end)
+                        .map(|(key, value)| (*key, *value))
+                        .collect();
+                    assert_eq!(&wanted, scan, "scan from {} to {}", start, end);
+                }
+                results.push(scans);
+            }
+            assert!(results.windows(2).all(|pair| pair[0] == pair[1]));
+        }
+    }
 
-                create_dir_all(folder_path).expect("Create dir all has failed!");
+    mod max_ssts {
+        use crate::{Client, KVConfig, KvError, StorageType};
+        use std::collections::BTreeMap;
+        use std::fs::read_dir;
 
-                let mut kv: Client = Client::open(
-                    db_name,
+        #[test]
+        fn test_max_ssts_caps_the_sst_count() {
+            for (i, (storage_type, delta_encoding)) in [
+                (StorageType::AppendOnlyLog, false),
+                (StorageType::AppendOnlyLog, true),
+                (StorageType::BTree, false),
+            ]
+            .into_iter()
+            .enumerate()
+            {
+                let db_name: String = format!("test_max_ssts_DB_{}", i);
+                let mut client: Client = Client::open(
+                    db_name.clone(),
                     KVConfig::default()
-                        .storage_type(crate::StorageType::AppendOnlyLog)
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .delta_encoding(delta_encoding)
+                        .max_ssts(Some(3))
                         .cleanup(true),
                 );
-                for i in 0..=98 {
-                    kv.memtable.put(i, i);
+                let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+                // 40 flushes, with newer values and deletes of keys in the merged SSTs
+                for i in 0..64 * 40 {
+                    let key: i64 = (i * 37) % 1000;
+                    if i % 9 == 0 {
+                        client.delete(key);
+                        expected.remove(&key);
+                    } else {
+                        client.put(key, i);
+                        expected.insert(key, i);
+                    }
+                    assert!(client.sst_count <= 3, "{} SSTs", client.sst_count);
+                }
+                assert_eq!(3, client.sst_count);
+                assert_eq!(3, read_dir(&db_name).unwrap().count());
+
+                for key in 0..1000 {
+                    assert_eq!(expected.get(&key).copied(), client.get(key), "key {}", key);
                 }
+                let wanted: Vec<(i64, i64)> = expected.into_iter().collect();
+                assert_eq!(wanted, client.scan(i64::MIN, i64::MAX));
             }
         }
 
-        // mod scan {
-        //     use crate::Client;
-        //     use std::fs::{create_dir_all, remove_dir, remove_file};
+        #[test]
+        fn test_max_ssts_rejects_zero() {
+            assert!(matches!(
+                KVConfig::default().max_ssts(Some(0)).validate(),
+                Err(KvError::InvalidConfig(_))
+            ));
+        }
+    }
 
-        //     #[test]
-        //     fn test_scan_from_tree_and_sst() {
-        //         let mut kv: Client =
-        //             Client::open("scanTestDB1".to_string(), 100, crate::StorageType::SST);
+    mod get_with_neighbors {
+        use crate::{Client, KVConfig, StorageType};
 
-        //         let folder_path: &str = "./scanTestDB1/";
-        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+        #[test]
+        fn test_neighbors_around_a_key() {
+            for (i, storage_type) in [StorageType::AppendOnlyLog, StorageType::LSMTree]
+                .into_iter()
+                .enumerate()
+            {
+                let mut client: Client = Client::open(
+                    format!("test_get_with_neighbors_DB_{}", i),
+                    KVConfig::default()
+                        .memtable_size(64)
+                        .storage_type(storage_type)
+                        .cleanup(true),
+                );
+                // dense keys 0..1000, with the multiples of 7 deleted, and sparse keys far above them
+                for key in 0..1000 {
+                    client.put(key, key * 10);
+                }
+                for key in (0..1000).step_by(7) {
+                    client.delete(key);
+                }
+                for key in 1..=3 {
+                    client.put(key * 1_000_000_000, key);
+                }
 
-        //         for i in 0..200 {
-        //             kv.put(i as i64, i * 2 as i64);
-        //         }
+                let (before, found, after) = client.get_with_neighbors(500, 3);
+                assert_eq!(vec![(496, 4960), (498, 4980), (499, 4990)], before);
+                assert_eq!(Some((500, 5000)), found);
+                assert_eq!(vec![(501, 5010), (502, 5020), (503, 5030)], after);
 
-        //         let output_lst = kv.scan(39, 167);
+                // a deleted key has neighbors but no value
+                let (before, found, after) = client.get_with_neighbors(497, 1);
+                assert_eq!(
+                    (vec![(496, 4960)], None, vec![(498, 4980)]),
+                    (before, found, after)
+                );
 
-        //         let mut j: i64 = 39;
-        //         for kv in output_lst.iter() {
-        //             assert_eq!(*kv, (j, j * 2));
-        //             j += 1;
-        //         }
-        //         assert!(j == 168);
+                // the sparse keys are found by widening, and the ends of the keys cut the sides short
+                let (before, found, after) = client.get_with_neighbors(999, 3);
+                assert_eq!(vec![(996, 9960), (997, 9970), (998, 9980)], before);
+                assert_eq!(Some((999, 9990)), found);
+                assert_eq!(
+                    vec![(1_000_000_000, 1), (2_000_000_000, 2), (3_000_000_000, 3)],
+                    after
+                );
+                let (before, found, after) = client.get_with_neighbors(i64::MIN, 2);
+                assert_eq!(
+                    (Vec::new(), None, vec![(1, 10), (2, 20)]),
+                    (before, found, after)
+                );
+                let (before, _, after) = client.get_with_neighbors(2_000_000_000, 5);
+                assert_eq!(
+                    vec![(997, 9970), (998, 9980), (999, 9990), (1_000_000_000, 1)],
+                    before[1..]
+                );
+                assert_eq!(vec![(3_000_000_000, 3)], after);
+                assert_eq!(
+                    (Vec::new(), Some((5, 50)), Vec::new()),
+                    client.get_with_neighbors(5, 0)
+                );
+            }
+        }
+    }
 
-        //         for i in 0..kv.sst_num {
-        //             remove_file(format!("{}output_{}.bin", folder_path, i))
-        //                 .expect("Remove file has failed!");
-        //         }
-        //         remove_dir(folder_path).expect("Remove dir has failed!");
-        //     }
+    mod scan_for_each {
+        use crate::{read_stats, reset_read_stats, Client, KVConfig, StorageType};
+        use std::ops::ControlFlow;
 
-        //     #[test]
-        //     fn test_scan_from_tree_and_sst_newer_values_in_tree() {
-        //         let mut kv: Client =
-        //             Client::open("scanTestDB2".to_string(), 100, crate::StorageType::SST);
+        fn open(db_name: &str) -> Client {
+            let mut client: Client = Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .scan_capacity(100)
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+            // dense keys 0..1000 with the odd ones deleted, and sparse keys at both ends of the keys
+            for key in 0..1000 {
+                client.put(key, key);
+            }
+            let deleted: Vec<i64> = (1..1000).step_by(2).collect();
+            client.delete_batch(&deleted);
+            client.put(i64::MIN, 1);
+            client.put(i64::MAX, 2);
+            client
+        }
 
-        //         let folder_path: &str = "./scanTestDB2/";
-        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+        #[test]
+        fn test_sums_every_pair() {
+            let mut client: Client = open("test_scan_for_each_DB_1");
+            let mut sum: i128 = 0;
+            let mut keys: Vec<i64> = Vec::new();
+            client.scan_for_each(i64::MIN, i64::MAX, |key, value| {
+                sum += value as i128;
+                keys.push(key);
+                ControlFlow::Continue(())
+            });
+            assert_eq!((0..1000).step_by(2).sum::<i128>() + 3, sum);
+            let scanned: Vec<i64> = client
+                .scan(i64::MIN, i64::MAX)
+                .into_iter()
+                .map(|a| a.0)
+                .collect();
+            assert_eq!(scanned, keys);
+        }
 
-        //         // put in tree
-        //         for i in 0..50 {
-        //             kv.put(i as i64, i * 10 as i64);
-        //         }
-        //         for i in 50..100 {
-        //             kv.put(i, i * 2);
-        //         }
-        //         for i in 25..50 {
-        //             kv.put(i, i);
-        //         }
+        #[test]
+        fn test_breaks_early() {
+            let mut client: Client = open("test_scan_for_each_DB_2");
+            reset_read_stats();
+            client.scan_for_each(-10, i64::MAX, |_, _| ControlFlow::Continue(()));
+            let full_pages: u64 = read_stats().pages;
 
-        //         let output_lst = kv.scan(0, 125); // 0-25 is i * 10, 25-50 is i, 50-100 is i*2
+            let mut keys: Vec<i64> = Vec::new();
+            reset_read_stats();
+            client.scan_for_each(-10, i64::MAX, |key, _| {
+                keys.push(key);
+                if keys.len() == 300 {
+                    return ControlFlow::Break(());
+                }
+                ControlFlow::Continue(())
+            });
+            assert_eq!((0..600).step_by(2).collect::<Vec<i64>>(), keys);
+            // the windows past the break are not read
+            assert!(
+                read_stats().pages < full_pages,
+                "{} pages",
+                read_stats().pages
+            );
 
-        //         // check!
-        //         for i in 0..25 {
-        //             assert!(output_lst[i] == (i as i64, i as i64 * 10));
-        //         }
-        //         for i in 25..50 {
-        //             assert!(output_lst[i] == (i as i64, i as i64));
-        //         }
-        //         for i in 50..100 {
-        //             assert!(output_lst[i] == (i as i64, i as i64 * 2));
-        //         }
+            // and an empty range calls nothing
+            client.scan_for_each(5, 4, |_, _| panic!("called on an empty range"));
+        }
+    }
 
-        //         for i in 0..kv.sst_num {
-        //             remove_file(format!("{}output_{}.bin", folder_path, i))
-        //                 .expect("Remove file has failed!");
-        //         }
-        //         remove_dir(folder_path).expect("Remove dir has failed!");
-        //     }
-        // }
+    mod export_records {
+        use crate::{Client, KVConfig, KvError, StorageType};
+
+        fn open(db_name: &str) -> Client {
+            Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .storage_type(StorageType::LSMTree)
+                    .cleanup(true),
+            )
+        }
+
+        #[test]
+        fn test_records_round_trip() {
+            let mut source: Client = open("test_export_records_DB_1");
+            // 7 flushes, so runs at levels 1 to 3, with overwrites and deletes shadowing the older runs
+            for i in 0..64 * 7 {
+                let key: i64 = (i * 13) % 300;
+                if i % 5 == 0 {
+                    source.delete(key);
+                }
+                source.put(key + 1000 * (i % 3), i);
+            }
+            let mut bytes: Vec<u8> = Vec::new();
+            source.export_records(&mut bytes).unwrap();
+
+            let mut copy: Client = open("test_export_records_DB_2");
+            let count: u64 = copy.import_records(&bytes[..]).unwrap();
+            let mut records: u64 = 0;
+            for level in 1..=4 {
+                let run: Vec<(i64, i64)> = source.iter_level(level).collect();
+                records += run.len() as u64;
+                assert_eq!(run, copy.iter_level(level).collect::<Vec<(i64, i64)>>());
+            }
+            assert_eq!(records, count);
+            assert!(source.iter_level(3).any(|a| a.1 == i64::MIN));
+            // the memtable is not exported
+            assert_ne!(
+                source.scan(i64::MIN, i64::MAX),
+                copy.scan(i64::MIN, i64::MAX)
+            );
+
+            // the runs do not fit on top of the ones already ingested
+            assert!(matches!(
+                copy.import_records(&bytes[..]),
+                Err(KvError::InvalidArgument(_))
+            ));
+            assert!(matches!(
+                copy.import_records(&bytes[1..]),
+                Err(KvError::InvalidArgument(_))
+            ));
+        }
     }
 
-    mod btree {
-        mod get_value {
-            use crate::{Client, KVConfig};
-            use std::fs::create_dir_all;
-            use std::time::Instant;
+    mod put_tracked {
+        use crate::{Client, KVConfig, PutOutcome, StorageType};
 
-            #[test] // 20k
-            fn test_get_from_ssts_medium() {
-                let mut kv: Client = Client::open(
-                    "BTree_getTestDB1".to_string(),
+        #[test]
+        fn test_flushed_on_the_put_filling_the_memtable() {
+            let mut client: Client = Client::open(
+                "test_put_tracked_DB".to_string(),
+                KVConfig::default()
+                    .memtable_size(8)
+                    .storage_type(StorageType::BTree)
+                    .cleanup(true),
+            );
+            for i in 0..24 {
+                // overwriting a key does not grow the memtable
+                if i % 8 == 3 {
+                    assert_eq!(PutOutcome { flushed: false }, client.put_tracked(i - 1, i));
+                }
+                let outcome: PutOutcome = client.put_tracked(i, i);
+                assert_eq!(i % 8 == 7, outcome.flushed, "put {}", i);
+                assert_eq!(((i + 1) / 8) as u32, client.sst_count);
+            }
+        }
+    }
+
+    mod list_databases {
+        use crate::{list_databases, Client, KVConfig, StorageType};
+        use std::fs::{create_dir_all, remove_dir_all, write};
+        use std::path::Path;
+
+        #[test]
+        fn test_lists_only_databases() {
+            let root: &str = "listDatabasesRoot";
+            create_dir_all(format!("{}/notes", root)).unwrap();
+            write(format!("{}/notes/todo.txt", root), "not a DB").unwrap();
+            create_dir_all(format!("{}/empty", root)).unwrap();
+            for (name, storage_type) in [
+                ("log", StorageType::AppendOnlyLog),
+                ("btree", StorageType::BTree),
+                ("lsm", StorageType::LSMTree),
+            ] {
+                let mut client: Client = Client::open(
+                    format!("{}/{}", root, name),
                     KVConfig::default()
-                        .storage_type(crate::StorageType::BTree)
-                        .cleanup(true),
+                        .memtable_size(16)
+                        .storage_type(storage_type),
                 );
+                for key in 0..32 {
+                    client.put(key, key);
+                }
+            }
 
-                let folder_path: &str = "./BTree_getTestDB1/";
-                create_dir_all(folder_path).expect("Create dir all has failed!");
+            let expected: Vec<String> = ["btree", "log", "lsm"]
+                .iter()
+                .map(|name| format!("{}/{}", root, name))
+                .collect();
+            assert_eq!(expected, list_databases(Path::new(root)));
+            let mut client: Client = Client::open(expected[1].clone(), KVConfig::default());
+            assert_eq!(Some(3), client.get(3));
+            drop(client);
 
-                for i in 0..20005 {
-                    kv.put(i as i64, (i * 2) as i64);
+            assert!(list_databases(Path::new("listDatabasesMissingRoot")).is_empty());
+            remove_dir_all(root).expect("Remove dir all has failed!");
+        }
+    }
+
+    mod tombstone_compaction {
+        use crate::{Client, KVConfig, KvError, StorageType};
+
+        fn open(db_name: &str, ratio: Option<f32>) -> Client {
+            Client::open(
+                db_name.to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .storage_type(StorageType::LSMTree)
+                    .tombstone_compaction_ratio(ratio)
+                    .cleanup(true),
+            )
+        }
+
+        #[test]
+        fn test_deletes_trigger_a_full_merge() {
+            let mut plain: Client = open("test_tombstone_compaction_DB_1", None);
+            let mut merged: Client = open("test_tombstone_compaction_DB_2", Some(0.25));
+            for client in [&mut plain, &mut merged] {
+                // 32 flushes of puts then 24 of deletes of three quarters of the keys
+                for key in 0..64 * 32 {
+                    client.put(key, key * 2);
                 }
+                let deleted: Vec<i64> = (0..64 * 24).collect();
+                for batch in deleted.chunks(64) {
+                    client.delete_batch(batch);
+                }
+            }
+            assert!(
+                merged.disk_usage().unwrap() < plain.disk_usage().unwrap() / 2,
+                "{} bytes against {}",
+                merged.disk_usage().unwrap(),
+                plain.disk_usage().unwrap()
+            );
+            // the merges dropped the tombstones, only the ones of the deletes since the last merge are left
+            for level in 1..=8 {
+                let tombstones: usize =
+                    merged.iter_level(level).filter(|a| a.1 == i64::MIN).count();
+                assert!(
+                    tombstones < 64,
+                    "{} tombstones at level {}",
+                    tombstones,
+                    level
+                );
+            }
 
-                let key1: i64 = 3899;
-                let key2: i64 = 8763;
-                let key3: i64 = 20006;
-                let start_time = Instant::now();
-                assert_eq!(Some(3899 * 2), kv.get(key1));
-                let end_time = Instant::now();
-                // Calculate the time difference
-                let elapsed_time = end_time.duration_since(start_time);
-                // Print the time difference
-                println!("Elapsed time: {:?}", elapsed_time);
+            for key in (0..64 * 32).step_by(7) {
+                assert_eq!(plain.get(key), merged.get(key), "key {}", key);
+            }
+            assert_eq!(
+                plain.scan(i64::MIN, i64::MAX),
+                merged.scan(i64::MIN, i64::MAX)
+            );
+            // writes after the merge land on top of the merged run
+            merged.put(5, 50);
+            merged.delete(64 * 31);
+            for key in 64 * 24..64 * 32 + 64 {
+                merged.put(key, key);
+            }
+            assert_eq!(Some(50), merged.get(5));
+            assert_eq!(Some(64 * 31), merged.get(64 * 31));
+            assert_eq!(None, merged.get(6));
+        }
 
-                assert_eq!(Some(8763 * 2), kv.get(key2));
-                assert_eq!(None, kv.get(key3));
+        #[test]
+        fn test_deleting_every_key_empties_the_tree() {
+            let mut client: Client = open("test_tombstone_compaction_DB_3", Some(0.5));
+            for key in 0..64 * 4 {
+                client.put(key, key);
+            }
+            let deleted: Vec<i64> = (0..64 * 4).collect();
+            for batch in deleted.chunks(64) {
+                client.delete_batch(batch);
             }
+            assert_eq!(0, client.disk_usage().unwrap());
+            assert_eq!(Vec::<(i64, i64)>::new(), client.scan(i64::MIN, i64::MAX));
+            client.put(1, 1);
+            for key in 64 * 4..64 * 5 {
+                client.put(key, key);
+            }
+            assert_eq!(Some(1), client.get(1));
+        }
 
-            // #[test] // 200k, commented takes long
-            // fn test_get_from_ssts_large() {
-            //     let mut kv: KV = KV::open("BTree_getTestDB2".to_string(), 200000, 1);
+        #[test]
+        fn test_tombstone_compaction_ratio_is_checked() {
+            for ratio in [0.0, -0.5, 1.5, f32::NAN] {
+                assert!(matches!(
+                    KVConfig::default()
+                        .tombstone_compaction_ratio(Some(ratio))
+                        .validate(),
+                    Err(KvError::InvalidConfig(_))
+                ));
+            }
+            assert!(KVConfig::default()
+                .tombstone_compaction_ratio(Some(1.0))
+                .validate()
+                .is_ok());
+        }
+    }
 
-            //     let folder_path: &str = "./BTree_getTestDB2/";
-            //     create_dir_all(folder_path).expect("Create dir all has failed!");
-            //     for i in 0..200005 {
-            //         kv.put(i as i64, (i * 2) as i64);
-            //     }
+    mod on_compaction {
+        use crate::{Client, CompactionEvent, KVConfig, StorageType};
+        use std::{cell::RefCell, rc::Rc};
 
-            //     let key1: i64 = 38990;
-            //     let key2: i64 = 8763;
-            //     let key3: i64 = 20006;
-            //     let start_time = Instant::now();
-            //     assert_eq!(Some(38990 * 2), kv.get(key1));
-            //     let end_time = Instant::now();
-            //     // Calculate the time difference
-            //     let elapsed_time = end_time.duration_since(start_time);
-            //     // Print the time difference
-            //     println!("Elapsed time: {:?}", elapsed_time);
+        #[test]
+        fn test_merges_call_the_callback() {
+            let events: Rc<RefCell<Vec<CompactionEvent>>> = Rc::new(RefCell::new(Vec::new()));
+            let seen: Rc<RefCell<Vec<CompactionEvent>>> = events.clone();
+            let mut client: Client = Client::open(
+                "test_on_compaction_DB".to_string(),
+                KVConfig::default()
+                    .memtable_size(64)
+                    .storage_type(StorageType::LSMTree)
+                    .tombstone_compaction_ratio(Some(0.2))
+                    .on_compaction(Box::new(move |event| seen.borrow_mut().push(event)))
+                    .cleanup(true),
+            );
 
-            //     // assert_eq!(Some(8763 * 2), kv.get(key2));
-            //     // assert_eq!(None, kv.get(key3));
+            // two flushes sharing half of their keys make one merge
+            for key in (0..64).chain(32..96) {
+                client.put(key, key);
+            }
+            assert_eq!(1, events.borrow().len());
+            let event: CompactionEvent = events.borrow()[0].clone();
+            assert_eq!(
+                vec![1, 1],
+                event.inputs.iter().map(|a| a.1).collect::<Vec<u32>>()
+            );
+            assert!(
+                event.inputs[0].0.ends_with("leaf_1_1.bin"),
+                "{:?}",
+                event.inputs
+            );
+            assert!(
+                event.inputs[1].0.ends_with("leaf_1_2.bin"),
+                "{:?}",
+                event.inputs
+            );
+            assert!(event.output.ends_with("leaf_2_2.bin"), "{}", event.output);
+            assert_eq!(2, event.output_level);
+            assert_eq!(
+                (128, 96, 0),
+                (
+                    event.entries_in,
+                    event.entries_out,
+                    event.tombstones_dropped
+                )
+            );
 
-            //     for i in 0..kv.sst_num {
-            //         remove_file(format!("{}output_{}.bin", folder_path, i))
-            //             .expect("Remove file has failed!");
-            //     }
-            //     remove_dir(folder_path).expect("Remove dir has failed!");
-            // }
+            // two more make a merge at level 1 then one at level 2
+            for key in 96..224 {
+                client.put(key, key);
+            }
+            let levels: Vec<(u32, u64, u64)> = events
+                .borrow()
+                .iter()
+                .map(|event| (event.output_level, event.entries_in, event.entries_out))
+                .collect();
+            assert_eq!(vec![(2, 128, 96), (2, 128, 128), (3, 224, 224)], levels);
+
+            // and a flush of deletes past the tombstone ratio merges every run, dropping the tombstones
+            let deleted: Vec<i64> = (0..64).collect();
+            client.delete_batch(&deleted);
+            assert_eq!(4, events.borrow().len());
+            let event: CompactionEvent = events.borrow()[3].clone();
+            assert_eq!(
+                vec![3, 1],
+                event.inputs.iter().map(|a| a.1).collect::<Vec<u32>>()
+            );
+            assert_eq!(3, event.output_level);
+            assert_eq!(
+                (288, 160, 64),
+                (
+                    event.entries_in,
+                    event.entries_out,
+                    event.tombstones_dropped
+                )
+            );
+            assert_eq!(160, client.scan(i64::MIN, i64::MAX).len());
         }
-        // mod scan {
-        //     use crate::Client;
-        //     use std::fs::{create_dir_all, remove_dir, remove_file};
+    }
 
-        //     #[test]
-        //     fn test_scan_from_tree_and_sst() {
-        //         let mut kv: Client = Client::open(
-        //             "BTree_scanTestDB1".to_string(),
-        //             20000,
-        //             crate::StorageType::BTree,
-        //         );
+    #[cfg(feature = "tracing")]
+    mod tracing_events {
+        use crate::{Client, KVConfig, StorageType};
+        use std::fmt::Debug;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
 
-        //         let folder_path: &str = "./BTree_scanTestDB1/";
-        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+        /// A `Subscriber` keeping the fields of every event, as `name=value` strings.
+        struct Capture(Arc<Mutex<Vec<Vec<String>>>>);
 
-        //         for i in 0..20009 {
-        //             kv.put(i as i64, i * 2 as i64);
-        //         }
+        /// A `Visit` collecting the fields of one event.
+        struct Fields(Vec<String>);
 
-        //         let output_lst = kv.scan(13985, 19697);
+        impl Visit for Fields {
+            fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+                self.0.push(format!("{}={:?}", field.name(), value));
+            }
+        }
 
-        //         let mut j: i64 = 13985;
-        //         for kv in output_lst.iter() {
-        //             assert_eq!(*kv, (j, j * 2));
-        //             j += 1;
-        //         }
-        //         assert!(j == 19698);
+        impl Subscriber for Capture {
+            fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+                true
+            }
 
-        //         for i in 0..kv.sst_num {
-        //             remove_file(format!("{}output_{}.bin", folder_path, i))
-        //                 .expect("Remove file has failed!");
-        //         }
-        //         remove_dir(folder_path).expect("Remove dir has failed!");
-        //     }
+            fn new_span(&self, _span: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
 
-        //     #[test]
-        //     fn test_scan_from_tree_and_sst_large() {
-        //         // damn my mac cpu fanning vol +++++
-        //         let mut kv: KV = KV::open("BTree_scanTestDB2".to_string(), 50, 0);
+            fn record(&self, _span: &Id, _values: &Record<'_>) {}
 
-        //         let folder_path: &str = "./BTree_scanTestDB2/";
-        //         create_dir_all(folder_path).expect("Create dir all has failed!");
+            fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
 
-        //         for i in 0..10000007 {
-        //             kv.put(i as i64, i * 2 as i64);
-        //         }
+            fn event(&self, event: &Event<'_>) {
+                let mut fields: Fields = Fields(Vec::new());
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
 
-        //         let output_lst = kv.scan(9095, 300007);
+            fn enter(&self, _span: &Id) {}
 
-        //         let mut j: i64 = 9095;
-        //         for kv in output_lst.iter() {
-        //             assert_eq!(*kv, (j, j * 2));
-        //             j += 1;
-        //         }
-        //         assert!(j == 300008);
+            fn exit(&self, _span: &Id) {}
+        }
 
-        //         for i in 0..kv.sst_num {
-        //             remove_file(format!("{}output_{}.bin", folder_path, i))
-        //                 .expect("Remove file has failed!");
-        //         }
-        //         remove_dir(folder_path).expect("Remove dir has failed!");
-        //     }
-        // }
+        #[test]
+        fn test_flush_fires_an_event() {
+            let events: Arc<Mutex<Vec<Vec<String>>>> = Arc::new(Mutex::new(Vec::new()));
+            tracing::subscriber::with_default(Capture(events.clone()), || {
+                let mut client: Client = Client::open(
+                    "test_tracing_events_DB".to_string(),
+                    KVConfig::default()
+                        .memtable_size(16)
+                        .storage_type(StorageType::BTree)
+                        .cleanup(true),
+                );
+                for key in 0..16 {
+                    client.put(key, key);
+                }
+                assert_eq!(Some(3), client.get(3));
+                assert_eq!(4, client.scan(0, 3).len());
+            });
+
+            let events: Vec<Vec<String>> = events.lock().unwrap().clone();
+            let flush: &Vec<String> = events
+                .iter()
+                .find(|fields| fields.contains(&"message=flush".to_string()))
+                .expect("no flush event");
+            assert!(flush.contains(&"db=test_tracing_events_DB".to_string()));
+            assert!(flush.contains(&"sst=0".to_string()));
+            assert!(flush.contains(&"keys=16".to_string()));
+            assert!(flush.contains(&"ssts=1".to_string()));
+            // the get is traced as slow if it took over a millisecond
+            for messages in [["get", "slow get"], ["scan", "scan"]] {
+                assert!(events.iter().any(|fields| messages
+                    .iter()
+                    .any(|message| fields.contains(&format!("message={}", message)))));
+            }
+        }
+    }
+
+    mod evict_sst_cache {
+        use crate::{file_opens, read_stats, reset_read_stats, Client, KVConfig, StorageType};
+
+        #[test]
+        fn test_evict_sst_cache_makes_the_next_read_a_miss() {
+            let mut client: Client = Client::open(
+                "test_evict_sst_cache_DB".to_string(),
+                KVConfig::default()
+                    .storage_type(StorageType::BTree)
+                    .memtable_size(512)
+                    .cleanup(true),
+            );
+            // two SSTs of two leaves each under an internal page
+            for key in 0..1024 {
+                client.put(key, key);
+            }
+            assert_eq!(6, client.warm_range(0, 1023));
+
+            reset_read_stats();
+            assert_eq!(Some(10), client.get(10));
+            assert_eq!(Some(700), client.get(700));
+            assert_eq!(0, read_stats().pages);
+
+            // SST 0 holds the keys from 0 to 511, SST 1 stays cached
+            let opens: u64 = file_opens();
+            client.evict_sst_cache(0);
+            assert_eq!(Some(10), client.get(10));
+            assert_eq!(2, read_stats().pages);
+            assert_eq!(opens + 1, file_opens());
+            assert_eq!(Some(700), client.get(700));
+            assert_eq!(2, read_stats().pages);
+
+            // a missing SST has nothing to evict
+            client.evict_sst_cache(7);
+            assert_eq!(Some(700), client.get(700));
+            assert_eq!(2, read_stats().pages);
+        }
     }
 }
@@ -1,5 +1,5 @@
 use crate::memtable::node::AVLTreeNode;
-use std::collections::HashMap;
+use crate::storage::ScanOutput;
 
 /*
     The following functions are helper functions for the main ones further below.
@@ -111,41 +111,81 @@ fn get_value(root: &Option<Box<AVLTreeNode>>, key: i64) -> Option<i64> {
     })
 }
 
+/// Helper function to return a mutable ref to the node with corresponding `key` starting at `root`.
+/// # Arguments
+/// * `root` - The root node where to start the search.
+/// * `key` - The key of the node we want.
+fn find_node_mut(root: &mut Option<Box<AVLTreeNode>>, key: i64) -> Option<&mut AVLTreeNode> {
+    let mut curr_node: Option<&mut AVLTreeNode> = root.as_deref_mut();
+    while let Some(node) = curr_node {
+        curr_node = match key.cmp(&node.key) {
+            std::cmp::Ordering::Less => node.left.as_deref_mut(),
+            std::cmp::Ordering::Greater => node.right.as_deref_mut(),
+            std::cmp::Ordering::Equal => return Some(node),
+        };
+    }
+    None
+}
+
 /// Helper function to return values with corresponding range of keys (`start` to `end` INCLUSIVE) starting at at `root`.
+/// Walks the tree with an explicit stack instead of recursing, so the depth of the tree does not matter.
 /// # Arguments
 /// * `root` - The root node where to start the scan process.
 /// * `start` - The begining of the scan range (INCLUSIVE).
 /// * `end` - The end of the scan range (INCLUSIVE).
-/// * `kv_hash` - The HashMap to store the output so we do not have duplicates.
-fn scan_tree(
+/// * `kv_hash` - The `ScanOutput` to store the output. Keys are visited in ascending order.
+fn scan_tree<T: ScanOutput + ?Sized>(
     root: &Option<Box<AVLTreeNode>>,
     start: i64,
     end: i64,
-    kv_hash: &mut HashMap<i64, i64>,
+    kv_hash: &mut T,
 ) {
-    if let Some(node) = root {
-        if start < node.key {
-            scan_tree(&node.left, start, end, kv_hash);
+    let mut stack: Vec<&AVLTreeNode> = Vec::new();
+    let mut curr_node: Option<&AVLTreeNode> = root.as_deref();
+
+    loop {
+        // go down the left side as long as it can hold keys in range
+        while let Some(node) = curr_node {
+            stack.push(node);
+            curr_node = if start < node.key {
+                node.left.as_deref()
+            } else {
+                None
+            };
         }
 
-        if start <= node.key && node.key <= end {
-            kv_hash.insert(node.key, node.value);
+        match stack.pop() {
+            Some(node) => {
+                if start <= node.key && node.key <= end {
+                    kv_hash.add(node.key, node.value);
+                }
+                if node.key >= end {
+                    // every node left on the stack has a larger key
+                    break;
+                }
+                curr_node = node.right.as_deref();
+            }
+            None => break,
         }
-        scan_tree(&node.right, start, end, kv_hash);
     }
 }
 
-/// Helper function to return all values in the AVL tree starting at `root`.
+/// Helper function to check the AVL invariant on every node starting at `root`. Returns the height of `root` if every
+/// node has a balance factor between -1 and 1 and a correct stored height, `None` otherwise.
 /// # Arguments
-/// * `root` - The root node where to start the scan process.
-fn scan_all_tree(root: &Option<Box<AVLTreeNode>>) -> Vec<(i64, i64)> {
+/// * `root` - The root node where to start the check.
+fn checked_height(root: &Option<Box<AVLTreeNode>>) -> Option<u32> {
     match root {
-        None => vec![],
-        Some(a) => {
-            let mut r = scan_all_tree(&a.left);
-            r.push((a.key, a.value));
-            r.extend(scan_all_tree(&a.right));
-            r
+        None => Some(0),
+        Some(node) => {
+            let left_height: u32 = checked_height(&node.left)?;
+            let right_height: u32 = checked_height(&node.right)?;
+            let height: u32 = 1 + left_height.max(right_height);
+
+            if left_height.abs_diff(right_height) > 1 || node.height != height {
+                return None;
+            }
+            Some(height)
         }
     }
 }
@@ -186,6 +226,22 @@ impl AVLTree {
         }
     }
 
+    /// Function to replace the value of `key` in place if it is in the `AVLTree`, without going through the insert
+    /// and rebalancing of `put`. Returns `true` if the key was found, `false` otherwise without changing anything.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `AVLTree` struct to update.
+    /// * `key` - The key to update.
+    /// * `value` - The new value of the key.
+    pub fn update_value(&mut self, key: i64, value: i64) -> bool {
+        match find_node_mut(&mut self.root, key) {
+            Some(node) => {
+                node.value = value;
+                true
+            }
+            None => false,
+        }
+    }
+
     /// Primary function to get a value from the `AVLTree` structure.
     /// # Arguments
     /// * `self` - A ref to the `AVLTree` struct to get the value.
@@ -194,21 +250,79 @@ impl AVLTree {
         get_value(&self.root, key)
     }
 
-    /// Primary function to scan for keys in the `AVLTree` structure. Stores the values in `kv_hash` to
-    /// eliminate duplicates. Scan range from `start` to `end` keys INCLUSIVE.
+    /// Primary function to scan for keys in the `AVLTree` structure. Stores the values in `kv_hash`, in ascending
+    /// key order. Scan range from `start` to `end` keys INCLUSIVE.
     /// # Arguments
     /// * `self` - A ref to the `AVLTree` struct to get the values.
     /// * `start` - The begining of the scan range (INCLUSIVE).
     /// * `end` - The end of the scan range (INCLUSIVE).
-    /// * `kv_hash` - The HashMap to store the output so we do not have duplicates.
-    pub fn scan(&self, start: i64, end: i64, kv_hash: &mut HashMap<i64, i64>) {
+    /// * `kv_hash` - The `ScanOutput` to store the output.
+    pub fn scan<T: ScanOutput + ?Sized>(&self, start: i64, end: i64, kv_hash: &mut T) {
         scan_tree(&self.root, start, end, kv_hash);
     }
 
-    /// Primary function to return all values in the `AVLTree` starting at `self.root`.
+    /// Primary function to scan for keys in the `AVLTree` structure into a new vector, in ascending key order.
+    /// Scan range from `start` to `end` keys INCLUSIVE.
+    /// # Arguments
     /// * `self` - A ref to the `AVLTree` struct to get the values.
+    /// * `start` - The begining of the scan range (INCLUSIVE).
+    /// * `end` - The end of the scan range (INCLUSIVE).
+    pub fn scan_vec(&self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        scan_tree(&self.root, start, end, &mut output);
+        output
+    }
+
+    /// Primary function to return all values in the `AVLTree` starting at `self.root`. Flushes use `drain_sorted`.
+    /// * `self` - A ref to the `AVLTree` struct to get the values.
+    #[cfg(test)]
     pub fn scan_all(&self) -> Vec<(i64, i64)> {
-        scan_all_tree(&self.root)
+        let mut output: Vec<(i64, i64)> = Vec::with_capacity(self.size as usize);
+        scan_tree(&self.root, i64::MIN, i64::MAX, &mut output);
+        output
+    }
+
+    /// Function to take every KV pair out of the `AVLTree`, leaving it empty. Returns an iterator over the pairs in
+    /// ascending key order that frees the nodes as it goes, pairs not iterated over are dropped with it.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `AVLTree` struct to empty.
+    pub fn drain(&mut self) -> Drain {
+        self.size = 0;
+        let mut drain: Drain = Drain { stack: Vec::new() };
+        drain.push_left(self.root.take());
+        drain
+    }
+
+    /// Function to take every KV pair out of the `AVLTree` into a new vector in ascending key order, leaving it empty.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `AVLTree` struct to empty.
+    pub fn drain_sorted(&mut self) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::with_capacity(self.size as usize);
+        output.extend(self.drain());
+        output
+    }
+
+    /// Function to remove every KV pair from the `AVLTree`, leaving it empty like a new one.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `AVLTree` struct to empty.
+    pub fn clear(&mut self) {
+        self.root = None;
+        self.size = 0;
+    }
+
+    /// Function to get the height of the AVL tree, 0 when empty.
+    /// # Arguments
+    /// * `self` - A ref to the `AVLTree` struct to get the height.
+    pub fn height(&self) -> u32 {
+        self.root.as_ref().map_or(0, |node| node.height)
+    }
+
+    /// Function to check that the AVL invariant holds: every node has a balance factor between -1 and 1 and a
+    /// correct stored height.
+    /// # Arguments
+    /// * `self` - A ref to the `AVLTree` struct to check.
+    pub fn is_balanced(&self) -> bool {
+        checked_height(&self.root).is_some()
     }
 
     /// Helper function to get the current size of the AVL tree.
@@ -219,6 +333,38 @@ impl AVLTree {
     }
 }
 
+/// Struct of the iterator returned by `AVLTree::drain`. Holds the nodes not yet visited with an explicit stack, like
+/// `scan_tree`.
+pub struct Drain {
+    /// The nodes whose key and right subtree are still to visit, the smallest key last.
+    stack: Vec<AVLTreeNode>,
+}
+
+// Implementation of the `Drain`.
+impl Drain {
+    /// Helper function to push `node` and its chain of left children on the stack.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `Drain`.
+    /// * `node` - The root of the subtree to visit next.
+    fn push_left(&mut self, mut node: Option<Box<AVLTreeNode>>) {
+        while let Some(mut curr_node) = node {
+            node = curr_node.left.take();
+            self.stack.push(*curr_node);
+        }
+    }
+}
+
+// The implementation of the `Drain` as an `Iterator`, in ascending key order.
+impl Iterator for Drain {
+    type Item = (i64, i64);
+
+    fn next(&mut self) -> Option<(i64, i64)> {
+        let mut node: AVLTreeNode = self.stack.pop()?;
+        self.push_left(node.right.take());
+        Some((node.key, node.value))
+    }
+}
+
 // Special default `AVLTree` implementation.
 impl Default for AVLTree {
     /// The default `AVLTree` implementation.
@@ -328,7 +474,8 @@ mod tests {
     }
 
     mod avl_tree {
-        use super::super::AVLTree;
+        use super::super::{scan_tree, AVLTree, AVLTreeNode};
+        use std::collections::HashMap;
 
         #[test]
         fn test_insert_and_get_value() {
@@ -348,6 +495,45 @@ mod tests {
             assert!(stored_value.is_none());
         }
 
+        #[test]
+        fn test_update_value_in_place() {
+            let mut tree: AVLTree = AVLTree::new();
+            for i in 0..=127 {
+                tree.put(i * 2, i);
+            }
+            let height: u32 = tree.height();
+
+            for i in 0..=127 {
+                assert!(tree.update_value(i * 2, -i));
+                assert!(!tree.update_value(i * 2 + 1, i));
+            }
+            for i in 0..=127 {
+                assert_eq!(tree.get(i * 2), Some(-i));
+                assert_eq!(tree.get(i * 2 + 1), None);
+            }
+            // nothing was inserted nor moved
+            assert_eq!(tree.size(), 128);
+            assert_eq!(tree.height(), height);
+            assert!(tree.is_balanced());
+        }
+
+        #[test]
+        fn test_clear() {
+            let mut tree: AVLTree = AVLTree::new();
+            for i in 0..=127 {
+                tree.put(i, i);
+            }
+            tree.clear();
+            assert_eq!(tree.size(), 0);
+            assert_eq!(tree.height(), 0);
+            assert_eq!(tree.get(5), None);
+
+            // the cleared tree takes new keys like a new one
+            tree.put(5, 6);
+            assert_eq!(tree.size(), 1);
+            assert_eq!(tree.get(5), Some(6));
+        }
+
         #[test]
         fn test_avl_tree_repeated_puts() {
             let mut tree = AVLTree::new();
@@ -388,6 +574,67 @@ mod tests {
         //     assert_eq!(*output_lst, Vec::<(i64, i64)>::new())
         // }
 
+        #[test]
+        fn test_scan_vec_matches_sorted_hash_scan() {
+            let mut tree = AVLTree::new();
+            // insert out of order so the tree is rebalanced along the way
+            for i in 0..=255 {
+                tree.put((i * 37) % 256, i);
+            }
+
+            for (start, end) in [
+                (99, 113),
+                (0, 255),
+                (-10, 5),
+                (250, 300),
+                (300, 400),
+                (7, 7),
+            ] {
+                let mut kv_hash: HashMap<i64, i64> = HashMap::new();
+                tree.scan(start, end, &mut kv_hash);
+                let mut expected: Vec<(i64, i64)> = kv_hash.into_iter().collect();
+                expected.sort();
+
+                assert_eq!(expected, tree.scan_vec(start, end));
+            }
+        }
+
+        #[test]
+        fn test_height_and_balance_after_sorted_inserts() {
+            let mut tree = AVLTree::new();
+            assert_eq!(tree.height(), 0);
+            assert!(tree.is_balanced());
+
+            let n: i64 = 100_000;
+            for i in 0..n {
+                tree.put(i, i);
+            }
+            for i in (0..n).rev() {
+                tree.put(-i - 1, i);
+            }
+
+            // upper bound on the height of an AVL tree with 2n nodes
+            let max_height: f64 = 1.44 * ((2 * n + 2) as f64).log2();
+            assert!(tree.is_balanced());
+            assert!(tree.height() as f64 <= max_height);
+            assert!(tree.height() >= ((2 * n) as f64).log2().ceil() as u32);
+        }
+
+        #[test]
+        fn test_is_balanced_detects_unbalanced_tree() {
+            let mut tree = AVLTree::new();
+            let mut root = Box::new(AVLTreeNode::new(1, 1));
+            let mut right = Box::new(AVLTreeNode::new(2, 2));
+            right.right = Some(Box::new(AVLTreeNode::new(3, 3)));
+            right.update_height();
+            root.right = Some(right);
+            root.update_height();
+            tree.root = Some(root);
+
+            assert_eq!(tree.height(), 3);
+            assert!(!tree.is_balanced());
+        }
+
         #[test]
         fn test_avl_tree_size_none() {
             let tree = AVLTree::new();
@@ -404,5 +651,59 @@ mod tests {
 
             assert_eq!(tree.size(), 128)
         }
+
+        #[test]
+        fn test_scan_large_tree() {
+            let mut tree = AVLTree::new();
+            let count: i64 = 256 * 256;
+            for i in 0..count {
+                tree.put((i * 7919) % count, i);
+            }
+
+            let all: Vec<(i64, i64)> = tree.scan_all();
+            assert_eq!(count as usize, all.len());
+            assert!(all.windows(2).all(|pair| pair[0].0 < pair[1].0));
+            assert_eq!(all[1000..2001], tree.scan_vec(1000, 2000)[..]);
+            assert_eq!(all, tree.scan_vec(i64::MIN, i64::MAX));
+        }
+
+        #[test]
+        fn test_scan_unbalanced_tree() {
+            // a chain of right children, as deep as it is long
+            let mut root: Option<Box<AVLTreeNode>> = None;
+            for i in (0..1000).rev() {
+                let mut node = Box::new(AVLTreeNode::new(i, -i));
+                node.right = root;
+                root = Some(node);
+            }
+
+            let mut output: Vec<(i64, i64)> = Vec::new();
+            scan_tree(&root, 10, 19, &mut output);
+            let expected: Vec<(i64, i64)> = (10..=19).map(|i| (i, -i)).collect();
+            assert_eq!(expected, output);
+        }
+
+        #[test]
+        fn test_drain_sorted() {
+            let mut tree = AVLTree::new();
+            for i in (0..1000).rev() {
+                tree.put((i * 7) % 1000, i);
+            }
+            let expected: Vec<(i64, i64)> = tree.scan_all();
+
+            assert_eq!(expected, tree.drain_sorted());
+            assert_eq!(0, tree.size());
+            assert_eq!(0, tree.height());
+            assert!(tree.scan_all().is_empty());
+            assert_eq!(None, tree.get(7));
+
+            // the tree is reusable and a partly used drain still empties it
+            tree.put(2, 2);
+            tree.put(1, 1);
+            tree.put(3, 3);
+            assert_eq!(Some((1, 1)), tree.drain().next());
+            assert_eq!(0, tree.size());
+            assert!(tree.drain_sorted().is_empty());
+        }
     }
 }
@@ -0,0 +1,215 @@
+use crate::serde::PAGE_SIZE;
+
+/// The size of a serialized KV pair: an 8 byte big endian key followed by an 8 byte big endian value.
+pub const RECORD_SIZE: usize = 16;
+
+// Pages hold a whole number of records.
+const _: () = assert!(PAGE_SIZE.is_multiple_of(RECORD_SIZE));
+
+/// Given a `key` and `value`, encode them into the on disk record format.
+/// # Arguments
+/// * `key` - The key of the KV pair.
+/// * `value` - The value of the KV pair.
+pub fn encode(key: i64, value: i64) -> [u8; RECORD_SIZE] {
+    let mut bytes: [u8; RECORD_SIZE] = [0u8; RECORD_SIZE];
+    bytes[..8].copy_from_slice(&key.to_be_bytes());
+    bytes[8..].copy_from_slice(&value.to_be_bytes());
+    bytes
+}
+
+/// Given the `bytes` of a record, decode them into a KV pair.
+/// # Arguments
+/// * `bytes` - The bytes of a single record, must be `RECORD_SIZE` long.
+pub fn decode(bytes: &[u8]) -> (i64, i64) {
+    assert_eq!(bytes.len(), RECORD_SIZE, "Record: invalid record size!");
+
+    let key: i64 = i64::from_be_bytes(
+        bytes[..8]
+            .try_into()
+            .expect("Record: invalid key chunk size!"),
+    );
+    let value: i64 = i64::from_be_bytes(
+        bytes[8..]
+            .try_into()
+            .expect("Record: invalid value chunk size!"),
+    );
+    (key, value)
+}
+
+/// The first bytes of a delta encoded page. Like the padding record, a fixed encoding page whose first key has these
+/// bytes would be mistaken for a delta encoded page.
+pub const DELTA_PAGE_MAGIC: [u8; 8] = *b"KVDELTA1";
+
+/// The size of the header of a delta encoded page: the magic followed by the big endian u32 number of KV pairs.
+const DELTA_HEADER_SIZE: usize = 12;
+
+/// Helper function to map an `i64` to a `u64` so that values close to 0 have small varints.
+/// # Arguments
+/// * `n` - The number to map.
+fn zigzag(n: i64) -> u64 {
+    ((n << 1) ^ (n >> 63)) as u64
+}
+
+/// Helper function to undo `zigzag`.
+/// # Arguments
+/// * `n` - The number to map back.
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+/// Helper function to append `n` to `bytes` as a varint, 7 bits per byte with the high bit set on all but the last.
+/// # Arguments
+/// * `bytes` - The bytes to append to.
+/// * `n` - The number to encode.
+fn push_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+/// Helper function to read the varint at `*pos` of `bytes` and move `pos` after it. Returns `None` if it is cut off
+/// or too long.
+/// # Arguments
+/// * `bytes` - The bytes to read from.
+/// * `pos` - The position of the varint, moved past it.
+fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut n: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let byte: u8 = *bytes.get(*pos)?;
+        *pos += 1;
+        n |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// Given KV pairs `kv_arr` in ascending key order, encode as many of them as fit into one delta encoded page. The
+/// first key is stored whole, every other key as the varint of its difference with the previous one, and values as
+/// zigzag varints. Returns the `PAGE_SIZE` bytes of the page and the number of KV pairs in it.
+/// # Arguments
+/// * `kv_arr` - The KV pairs to encode, sorted by key.
+pub fn encode_delta_page(kv_arr: &[(i64, i64)]) -> (Vec<u8>, usize) {
+    let mut body: Vec<u8> = Vec::with_capacity(PAGE_SIZE);
+    let mut record: Vec<u8> = Vec::with_capacity(20);
+    let mut count: usize = 0;
+
+    for (i, &(key, value)) in kv_arr.iter().enumerate() {
+        record.clear();
+        if i == 0 {
+            push_varint(&mut record, zigzag(key));
+        } else {
+            push_varint(&mut record, key.wrapping_sub(kv_arr[i - 1].0) as u64);
+        }
+        push_varint(&mut record, zigzag(value));
+
+        if DELTA_HEADER_SIZE + body.len() + record.len() > PAGE_SIZE {
+            break;
+        }
+        body.extend_from_slice(&record);
+        count += 1;
+    }
+
+    let mut bytes: Vec<u8> = Vec::with_capacity(PAGE_SIZE);
+    bytes.extend_from_slice(&DELTA_PAGE_MAGIC);
+    bytes.extend_from_slice(&(count as u32).to_be_bytes());
+    bytes.extend_from_slice(&body);
+    bytes.resize(PAGE_SIZE, 0);
+    (bytes, count)
+}
+
+/// Given the `bytes` of a page, return `true` if it is delta encoded.
+/// # Arguments
+/// * `bytes` - The bytes of the page.
+pub fn is_delta_page(bytes: &[u8]) -> bool {
+    bytes.starts_with(&DELTA_PAGE_MAGIC)
+}
+
+/// Given the `bytes` of a delta encoded page, decode its KV pairs. Returns `None` if the page is malformed.
+/// # Arguments
+/// * `bytes` - The bytes of the page, see `encode_delta_page`.
+pub fn decode_delta_page(bytes: &[u8]) -> Option<Vec<(i64, i64)>> {
+    if !is_delta_page(bytes) || bytes.len() < DELTA_HEADER_SIZE {
+        return None;
+    }
+    let count: usize = u32::from_be_bytes(bytes[8..DELTA_HEADER_SIZE].try_into().ok()?) as usize;
+
+    let mut kv_arr: Vec<(i64, i64)> = Vec::with_capacity(count);
+    let mut pos: usize = DELTA_HEADER_SIZE;
+    let mut key: i64 = 0;
+    for i in 0..count {
+        let encoded_key: u64 = read_varint(bytes, &mut pos)?;
+        key = if i == 0 {
+            unzigzag(encoded_key)
+        } else {
+            key.wrapping_add(encoded_key as i64)
+        };
+        let value: i64 = unzigzag(read_varint(bytes, &mut pos)?);
+        kv_arr.push((key, value));
+    }
+    Some(kv_arr)
+}
+
+#[cfg(test)]
+mod tests {
+    mod record {
+        use crate::record::{
+            decode, decode_delta_page, encode, encode_delta_page, is_delta_page, RECORD_SIZE,
+        };
+        use crate::serde::PAGE_SIZE;
+
+        #[test]
+        fn test_encode_decode_round_trip() {
+            for (key, value) in [
+                (0, 0),
+                (1, -1),
+                (-42, 42),
+                (i64::MIN, i64::MAX),
+                (i64::MAX, i64::MIN),
+                (-1, -1),
+            ] {
+                assert_eq!((key, value), decode(&encode(key, value)));
+            }
+        }
+
+        #[test]
+        fn test_encode_is_big_endian() {
+            let bytes: [u8; RECORD_SIZE] = encode(1, -2);
+            assert_eq!(bytes[..8], [0, 0, 0, 0, 0, 0, 0, 1]);
+            assert_eq!(bytes[8..], [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe]);
+
+            // negative keys keep their sign bit in the first byte
+            assert_eq!(encode(-1, 0)[0], 0xff);
+        }
+
+        #[test]
+        #[should_panic]
+        fn test_decode_wrong_size() {
+            decode(&[0u8; 8]);
+        }
+
+        #[test]
+        fn test_delta_page_round_trip() {
+            let sequential: Vec<(i64, i64)> = (1000..3000).map(|i| (i, i * 2)).collect();
+            let (bytes, count) = encode_delta_page(&sequential);
+            assert_eq!(PAGE_SIZE, bytes.len());
+            assert!(is_delta_page(&bytes));
+            // several times the 256 KV pairs of a fixed encoding page
+            assert!(count > 1000);
+            assert_eq!(sequential[..count], decode_delta_page(&bytes).unwrap()[..]);
+
+            let extremes: Vec<(i64, i64)> =
+                vec![(i64::MIN, i64::MAX), (-1, i64::MIN), (0, 0), (i64::MAX, -1)];
+            let (bytes, count) = encode_delta_page(&extremes);
+            assert_eq!(4, count);
+            assert_eq!(extremes, decode_delta_page(&bytes).unwrap());
+
+            // fixed encoding pages and cut off pages are not decoded
+            assert!(!is_delta_page(&encode(1, 1)));
+            assert_eq!(None, decode_delta_page(&bytes[..20]));
+        }
+    }
+}
@@ -1,12 +1,30 @@
 use std::cmp::Ordering;
-use std::collections::HashMap;
-use std::fs::{create_dir_all, metadata, read_dir, File, OpenOptions};
-use std::io::{Read, Seek, SeekFrom, Write};
-use std::os::unix::fs::OpenOptionsExt;
+use std::fs::{
+    create_dir_all, metadata, read_dir, remove_file, rename, DirEntry, File, OpenOptions,
+};
+use std::io::{self, Write};
+use std::os::unix::fs::{FileExt, OpenOptionsExt};
+use std::path::Path;
 use std::slice::ChunksExact;
 
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use twox_hash::xxh3::hash64;
+
+use crate::files;
+use crate::record::{
+    decode, decode_delta_page, encode, encode_delta_page, is_delta_page, RECORD_SIZE,
+};
+use crate::storage::ScanOutput;
+use crate::KvError;
+
 pub const PAGE_SIZE: usize = 4096;
-const O_DIRECT: libc::c_int = 0x4000;
+pub const TEMP_EXTENSION: &str = ".tmp";
+pub const O_DIRECT: libc::c_int = 0x4000;
+
+/// A page-aligned block of bytes. Reads and writes on files opened with `O_DIRECT` must use aligned memory.
+#[repr(C, align(4096))]
+struct AlignedPage([u8; PAGE_SIZE]);
 
 /*
     The following functions are for the serialization and deserialization processes.
@@ -19,13 +37,13 @@ const O_DIRECT: libc::c_int = 0x4000;
 /// * `bytes` - Vector with length multiple 16 of serialized KV pairs.
 pub fn pad_page_bytes(bytes: &mut Vec<u8>) {
     let mut padding_size: usize = 0;
-    if bytes.len() % PAGE_SIZE != 0 {
+    if !bytes.len().is_multiple_of(PAGE_SIZE) {
         padding_size = PAGE_SIZE - (bytes.len() % PAGE_SIZE);
     }
-    assert!(padding_size % 16 == 0);
+    assert!(padding_size.is_multiple_of(16));
 
     while padding_size > 0 {
-        let padding: [u8; 16] = [
+        let padding: [u8; RECORD_SIZE] = [
             0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad,
             0xbe, 0xef,
         ];
@@ -33,7 +51,7 @@ pub fn pad_page_bytes(bytes: &mut Vec<u8>) {
         bytes.extend_from_slice(&padding[..16]);
         padding_size -= 16;
     }
-    assert!(bytes.len() % 4096 == 0);
+    assert!(bytes.len().is_multiple_of(4096));
 }
 
 /// Given `file_path` and `page_offset`, deserialize the data at the location in the file and return the vector of KV pairs.
@@ -41,76 +59,150 @@ pub fn pad_page_bytes(bytes: &mut Vec<u8>) {
 /// * `file_path` - The path to the file.
 /// * `page_offset` - The offset to the wanted page in the file.
 pub fn deserialize_page(file_path: &str, page_offset: usize) -> Vec<(i64, i64)> {
-    let mut file: File = OpenOptions::new()
-        .read(true)
-        .custom_flags(O_DIRECT) // libc::O_DIRECT
-        .open(file_path)
-        .expect("Deserializer: open file failed!");
-
-    file.seek(SeekFrom::Start(page_offset as u64))
-        .expect("Deserializer: file seek failed!");
+    try_deserialize_page(file_path, page_offset)
+        .unwrap_or_else(|error| panic!("Deserializer: {}!", error))
+}
 
-    let mut bytes: Vec<u8> = vec![0u8; PAGE_SIZE];
-    file.read_exact(&mut bytes)
-        .expect("Deserializer: file exact read failed!");
+/// Given `file_path` and `page_offset`, deserialize the data at the location in the file and return the vector of KV
+/// pairs. Returns `KvError::CorruptPage` if the page cannot be read in full, for example from a truncated file, and
+/// `KvError::Io` if the file cannot be opened or read at all, for example once removed.
+/// # Arguments
+/// * `file_path` - The path to the file.
+/// * `page_offset` - The offset to the wanted page in the file.
+pub fn try_deserialize_page(
+    file_path: &str,
+    page_offset: usize,
+) -> Result<Vec<(i64, i64)>, KvError> {
+    let read_error = |error: io::Error| match error.kind() {
+        io::ErrorKind::UnexpectedEof => KvError::CorruptPage {
+            file: file_path.to_string(),
+            offset: page_offset,
+        },
+        kind => KvError::Io {
+            file: file_path.to_string(),
+            kind,
+        },
+    };
+
+    let mut aligned: Box<AlignedPage> = Box::new(AlignedPage([0u8; PAGE_SIZE]));
+    files::read_exact_at(file_path, &mut aligned.0, page_offset as u64).map_err(read_error)?;
+    let bytes: &[u8] = &aligned.0;
+
+    if is_delta_page(bytes) {
+        return decode_delta_page(bytes).ok_or(KvError::CorruptPage {
+            file: file_path.to_string(),
+            offset: page_offset,
+        });
+    }
 
-    let padding: [u8; 16] = [
+    let padding: [u8; RECORD_SIZE] = [
         0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe, 0xef, 0xde, 0xad, 0xbe,
         0xef,
     ];
     let mut non_padding_idx: usize = bytes.len();
-    while non_padding_idx >= 16 && bytes[non_padding_idx - 16..non_padding_idx] == padding {
-        non_padding_idx -= 16;
+    while non_padding_idx >= RECORD_SIZE
+        && bytes[non_padding_idx - RECORD_SIZE..non_padding_idx] == padding
+    {
+        non_padding_idx -= RECORD_SIZE;
     }
 
     let bytes_without_padding: &[u8] = &bytes[..non_padding_idx];
-    let iter: ChunksExact<'_, u8> = bytes_without_padding.chunks_exact(16);
-
-    let kv_arr: Vec<(i64, i64)> = iter
-        .map(|chunk| {
-            // Convert the first 8 bytes to i64 for key
-            let key_bytes: &[u8] = &chunk[..8];
-            let key: i64 = i64::from_be_bytes(
-                key_bytes
-                    .try_into()
-                    .expect("Deserializer: invalid key chunk size!"),
-            );
+    let iter: ChunksExact<'_, u8> = bytes_without_padding.chunks_exact(RECORD_SIZE);
 
-            // Fetch the next chunk (8 bytes) for the value
-            let value_bytes: &[u8] = &chunk[8..];
-            let value: i64 = i64::from_be_bytes(
-                value_bytes
-                    .try_into()
-                    .expect("Deserializer: invalid value chunk size!"),
-            );
+    let kv_arr: Vec<(i64, i64)> = iter.map(decode).collect();
 
-            // Return the key-value pair
-            (key, value)
-        })
-        .collect();
+    Ok(kv_arr)
+}
 
-    kv_arr
+/// Given the `file_path` of an SST, return its size in number of pages, its `SstFooter` excluded. Returns
+/// `KvError::CorruptPage` pointing at the last page if the SST is empty or ends with a partial page, as left behind by
+/// a truncated write, and `KvError::Io` if the SST cannot be found.
+/// # Arguments
+/// * `file_path` - The path to the SST file in question.
+pub fn sst_total_pages(file_path: &str) -> Result<usize, KvError> {
+    let file_size: usize = metadata(file_path)
+        .map_err(|error| KvError::Io {
+            file: file_path.to_string(),
+            kind: error.kind(),
+        })?
+        .len() as usize;
+    let footer_size: usize = file_size % PAGE_SIZE;
+    if file_size < PAGE_SIZE || (footer_size != 0 && footer_size != FOOTER_SIZE) {
+        return Err(KvError::CorruptPage {
+            file: file_path.to_string(),
+            offset: (file_size / PAGE_SIZE) * PAGE_SIZE,
+        });
+    }
+    Ok(file_size / PAGE_SIZE)
 }
 
 /// Given `file_path` and `kv_arr`, serialize the `kv_arr` vector and store it in the sst at `file_path`.
 /// # Arguments
 /// * `file_path` - The path to the file.
 /// * `kv_arr` - The vector of KV pairs.
-pub fn serialize_kv_to_file(file_path: &str, kv_arr: &Vec<(i64, i64)>) {
-    let mut bytes: Vec<u8> = Vec::new();
+pub fn serialize_kv_to_file(file_path: &str, kv_arr: &[(i64, i64)]) {
+    write_pages_to_file(file_path, &encode_pages(kv_arr));
+}
+
+/// Given `kv_arr`, encode it into pages of fixed size records, the last page padded (see `pad_page_bytes`).
+/// # Arguments
+/// * `kv_arr` - The vector of KV pairs.
+pub fn encode_pages(kv_arr: &[(i64, i64)]) -> Vec<u8> {
+    let mut bytes: Vec<u8> = Vec::with_capacity(kv_arr.len() * RECORD_SIZE);
 
     for (key, value) in kv_arr {
-        bytes.extend_from_slice(&key.to_be_bytes());
-        bytes.extend_from_slice(&value.to_be_bytes());
+        bytes.extend_from_slice(&encode(*key, *value));
     }
 
     pad_page_bytes(&mut bytes);
+    bytes
+}
+
+/// Given `kv_arr`, encode it into the same pages as `encode_pages`, each page encoded in parallel on the current
+/// `rayon` thread pool. Only built with the `rayon` feature.
+/// # Arguments
+/// * `kv_arr` - The vector of KV pairs.
+#[cfg(feature = "rayon")]
+pub fn encode_pages_parallel(kv_arr: &[(i64, i64)]) -> Vec<u8> {
+    let pages: Vec<Vec<u8>> = kv_arr
+        .par_chunks(PAGE_SIZE / RECORD_SIZE)
+        .map(encode_pages)
+        .collect();
+    pages.concat()
+}
+
+/// Given `file_path` and `kv_arr` in ascending key order, serialize the `kv_arr` vector into delta encoded pages (see
+/// `encode_delta_page`) and store it in the sst at `file_path`. The pages are read back by `deserialize_page` like
+/// fixed encoding ones.
+/// # Arguments
+/// * `file_path` - The path to the file.
+/// * `kv_arr` - The vector of KV pairs, sorted by key.
+pub fn serialize_kv_to_file_delta(file_path: &str, kv_arr: &[(i64, i64)]) {
+    let mut bytes: Vec<u8> = Vec::new();
 
+    let mut remaining: &[(i64, i64)] = kv_arr;
+    while !remaining.is_empty() {
+        let (page, count) = encode_delta_page(remaining);
+        bytes.extend_from_slice(&page);
+        remaining = &remaining[count..];
+    }
+
+    write_pages_to_file(file_path, &bytes);
+}
+
+/// Given `file_path`, append the pages `bytes` to the file at `file_path`, creating it and its directories if needed.
+/// # Arguments
+/// * `file_path` - The path to the file.
+/// * `bytes` - The bytes to write, a multiple of `PAGE_SIZE` long.
+pub fn write_pages_to_file(file_path: &str, bytes: &[u8]) {
     // Create directories if they don't exist
     if let Some(parent_dir) = std::path::Path::new(&file_path).parent() {
         create_dir_all(parent_dir).expect("Serializer: file dir not found + failed to create!");
     }
 
+    // A handle opened for reading before the file was replaced would see stale pages
+    files::forget_file(file_path);
+
     let mut file: File = OpenOptions::new()
         .create(true)
         .append(true)
@@ -118,8 +210,191 @@ pub fn serialize_kv_to_file(file_path: &str, kv_arr: &Vec<(i64, i64)>) {
         .open(file_path)
         .expect("Serializer: failed to create / append if file exists!");
 
-    file.write_all(&bytes)
-        .expect("Serializer: file write failed!");
+    let aligned: Vec<AlignedPage> = bytes
+        .chunks_exact(PAGE_SIZE)
+        .map(|chunk| AlignedPage(chunk.try_into().expect("Serializer: invalid page size!")))
+        .collect();
+    for page in &aligned {
+        file.write_all(&page.0)
+            .expect("Serializer: file write failed!");
+    }
+}
+
+/// Given the final `file_path` of a file, return the temporary path it should be written to before
+/// being published with `publish_file`. A stale temporary file left behind by an interrupted write is
+/// removed so the new write starts from an empty file.
+/// # Arguments
+/// * `file_path` - The final path of the file.
+pub fn temp_file_path(file_path: &str) -> String {
+    let temp_path: String = format!("{}{}", file_path, TEMP_EXTENSION);
+    if metadata(&temp_path).is_ok() {
+        files::forget_file(&temp_path);
+        remove_file(&temp_path).expect("Publisher: remove stale temp file failed!");
+    }
+    temp_path
+}
+
+/// Given the final `file_path` of a file that was fully written at its `temp_file_path`, sync it and atomically
+/// rename it to `file_path`. Readers therefore only ever see complete files.
+/// # Arguments
+/// * `file_path` - The final path of the file.
+pub fn publish_file(file_path: &str) {
+    let temp_path: String = format!("{}{}", file_path, TEMP_EXTENSION);
+
+    File::open(&temp_path)
+        .expect("Publisher: open temp file failed!")
+        .sync_all()
+        .expect("Publisher: temp file sync failed!");
+
+    rename(&temp_path, file_path).expect("Publisher: rename temp file failed!");
+    files::forget_file(&temp_path);
+    files::forget_file(file_path);
+}
+
+/// Given a directory `entry`, return `true` if it is a temporary file that has not been published yet.
+/// # Arguments
+/// * `entry` - The directory entry in question.
+pub fn is_temp_file(entry: &DirEntry) -> bool {
+    entry
+        .file_name()
+        .to_string_lossy()
+        .ends_with(TEMP_EXTENSION)
+}
+
+/*
+    The following functions are for the footers at the end of the SSTs.
+*/
+
+/// The first bytes of an SST footer.
+pub const FOOTER_MAGIC: [u8; 8] = *b"KVFOOTR1";
+
+/// The format version written in the SST footers.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// The size of an SST footer: the magic, the big endian u32 format version and page count, u64 entry count, i64 min
+/// and max keys, and the u64 checksum of all the bytes before it. It is not a multiple of `PAGE_SIZE`, so an SST with
+/// a footer is told apart by its size alone and the pages before it keep their offsets.
+pub const FOOTER_SIZE: usize = 48;
+
+/// Struct of the footer written after the pages of an SST, its metadata read without scanning the pages.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SstFooter {
+    /// The format version of the SST.
+    pub version: u32,
+    /// The number of pages of the SST before the footer.
+    pub pages: u32,
+    /// The number of KV pairs in the leaf pages of the SST.
+    pub entries: u64,
+    /// The smallest key of the SST, 0 if it is empty.
+    pub min_key: i64,
+    /// The largest key of the SST, 0 if it is empty.
+    pub max_key: i64,
+}
+
+// Implementation of the `SstFooter`.
+impl SstFooter {
+    /// Helper function to encode the `SstFooter` into its `FOOTER_SIZE` bytes, checksum included.
+    /// # Arguments
+    /// * `self` - A ref to the `SstFooter` to encode.
+    fn encode(&self) -> [u8; FOOTER_SIZE] {
+        let mut bytes: [u8; FOOTER_SIZE] = [0u8; FOOTER_SIZE];
+        bytes[..8].copy_from_slice(&FOOTER_MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.pages.to_be_bytes());
+        bytes[16..24].copy_from_slice(&self.entries.to_be_bytes());
+        bytes[24..32].copy_from_slice(&self.min_key.to_be_bytes());
+        bytes[32..40].copy_from_slice(&self.max_key.to_be_bytes());
+        let checksum: u64 = hash64(&bytes[..FOOTER_SIZE - 8]);
+        bytes[FOOTER_SIZE - 8..].copy_from_slice(&checksum.to_be_bytes());
+        bytes
+    }
+
+    /// Helper function to decode the `bytes` of a footer. Returns `None` if the magic or the checksum is wrong.
+    /// # Arguments
+    /// * `bytes` - The `FOOTER_SIZE` bytes of the footer.
+    fn decode(bytes: &[u8; FOOTER_SIZE]) -> Option<Self> {
+        let checksum: u64 = u64::from_be_bytes(bytes[FOOTER_SIZE - 8..].try_into().ok()?);
+        if bytes[..8] != FOOTER_MAGIC || hash64(&bytes[..FOOTER_SIZE - 8]) != checksum {
+            return None;
+        }
+        Some(SstFooter {
+            version: u32::from_be_bytes(bytes[8..12].try_into().ok()?),
+            pages: u32::from_be_bytes(bytes[12..16].try_into().ok()?),
+            entries: u64::from_be_bytes(bytes[16..24].try_into().ok()?),
+            min_key: i64::from_be_bytes(bytes[24..32].try_into().ok()?),
+            max_key: i64::from_be_bytes(bytes[32..40].try_into().ok()?),
+        })
+    }
+}
+
+/// Given the `file_path` of an SST whose pages were all written and its KV pairs `kv_arr` in ascending key order,
+/// append its `SstFooter`. Must be called before the SST is published.
+/// # Arguments
+/// * `file_path` - The path to the SST.
+/// * `kv_arr` - The KV pairs of the SST, sorted by key.
+pub fn write_sst_footer(file_path: &str, kv_arr: &[(i64, i64)]) {
+    write_sst_footer_counts(
+        file_path,
+        kv_arr.len() as u64,
+        kv_arr.first().map_or(0, |a| a.0),
+        kv_arr.last().map_or(0, |a| a.0),
+    );
+}
+
+/// Given the `file_path` of an SST whose pages were all written, its number of KV pairs `entries` and its smallest
+/// and largest keys, append its `SstFooter`. For SSTs whose KV pairs are not all held in memory, see
+/// `write_sst_footer`.
+/// # Arguments
+/// * `file_path` - The path to the SST.
+/// * `entries` - The number of KV pairs of the SST.
+/// * `min_key` - The smallest key of the SST, 0 if it is empty.
+/// * `max_key` - The largest key of the SST, 0 if it is empty.
+pub fn write_sst_footer_counts(file_path: &str, entries: u64, min_key: i64, max_key: i64) {
+    let pages: usize = sst_total_pages(file_path).expect("Serializer: SST pages not found!");
+    let footer: SstFooter = SstFooter {
+        version: FORMAT_VERSION,
+        pages: pages as u32,
+        entries,
+        min_key,
+        max_key,
+    };
+
+    // the footer is not page aligned so it is not written with O_DIRECT
+    files::forget_file(file_path);
+    OpenOptions::new()
+        .append(true)
+        .open(file_path)
+        .and_then(|mut file| file.write_all(&footer.encode()))
+        .expect("Serializer: footer write failed!");
+}
+
+/// Given the `file_path` of an SST, read its `SstFooter`. Returns `None` if the SST has no footer, its size being a
+/// multiple of `PAGE_SIZE`, and `KvError::CorruptPage` pointing at the footer if it is unreadable or does not match
+/// the pages of the SST.
+/// # Arguments
+/// * `file_path` - The path to the SST.
+pub fn read_sst_footer(file_path: &str) -> Result<Option<SstFooter>, KvError> {
+    let total_pages: usize = sst_total_pages(file_path)?;
+    let offset: usize = total_pages * PAGE_SIZE;
+    let io_error = |error: io::Error| KvError::Io {
+        file: file_path.to_string(),
+        kind: error.kind(),
+    };
+    if metadata(file_path).map_err(io_error)?.len() as usize == offset {
+        return Ok(None);
+    }
+
+    let mut bytes: [u8; FOOTER_SIZE] = [0u8; FOOTER_SIZE];
+    File::open(file_path)
+        .and_then(|file| file.read_exact_at(&mut bytes, offset as u64))
+        .map_err(io_error)?;
+    match SstFooter::decode(&bytes) {
+        Some(footer) if footer.pages as usize == total_pages => Ok(Some(footer)),
+        _ => Err(KvError::CorruptPage {
+            file: file_path.to_string(),
+            offset,
+        }),
+    }
 }
 
 /*
@@ -128,22 +403,193 @@ pub fn serialize_kv_to_file(file_path: &str, kv_arr: &Vec<(i64, i64)>) {
     Note that some of these functions are also used in the "storage/btree.rs" file.
 */
 
-/// Given `db_name`, output all the names of SSTs inside.
+/// Struct of the naming convention of the SST files of a DB: `{prefix}_{index}.{extension}`.
+pub struct SstNaming {
+    /// The prefix of every SST file name.
+    prefix: String,
+    /// The extension of every SST file name, without the dot.
+    extension: String,
+}
+
+// Implementation of the `SstNaming`.
+impl SstNaming {
+    /// Creating a new `SstNaming` given the `prefix` and `extension`.
+    /// # Arguments
+    /// * `prefix` - The prefix of the SST file names.
+    /// * `extension` - The extension of the SST file names, without the dot.
+    pub fn new(prefix: String, extension: String) -> Self {
+        Self { prefix, extension }
+    }
+
+    /// Checking that the names are usable: the prefix and extension are non empty, only use ASCII letters, digits,
+    /// `-` and `_` (the extension no `_`), and the extension does not clash with temporary files.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming` to validate.
+    pub fn validate(&self) -> Result<(), String> {
+        let valid_chars = |name: &str, extra: &[char]| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || extra.contains(&c))
+        };
+        if !valid_chars(&self.prefix, &['-', '_']) {
+            return Err(format!("invalid sst prefix {:?}", self.prefix));
+        }
+        if !valid_chars(&self.extension, &['-']) || format!(".{}", self.extension) == TEMP_EXTENSION
+        {
+            return Err(format!("invalid sst extension {:?}", self.extension));
+        }
+        Ok(())
+    }
+
+    /// Given an `index`, return the file name of the SST.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming`.
+    /// * `index` - The index of the SST.
+    pub fn sst_file_name(&self, index: u32) -> String {
+        format!("{}_{}.{}", self.prefix, index, self.extension)
+    }
+
+    /// Given a `level` and `index`, return the file name of the leaf file of an `LSMTree` run.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming`.
+    /// * `level` - The level of the run.
+    /// * `index` - The index of the run.
+    pub fn leaf_file_name(&self, level: u32, index: u32) -> String {
+        format!(
+            "{}_leaf_{}_{}.{}",
+            self.prefix, level, index, self.extension
+        )
+    }
+
+    /// Given a `level` and `index`, return the file name of the internal file of an `LSMTree` run.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming`.
+    /// * `level` - The level of the run.
+    /// * `index` - The index of the run.
+    pub fn internal_file_name(&self, level: u32, index: u32) -> String {
+        format!(
+            "{}_internal_{}_{}.{}",
+            self.prefix, level, index, self.extension
+        )
+    }
+
+    /// Given the `file_name` of an SST, return its index if the name is one.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming`.
+    /// * `file_name` - The file name in question, without the directory.
+    pub fn parse_index(&self, file_name: &str) -> Option<u32> {
+        file_name
+            .strip_prefix(self.prefix.as_str())?
+            .strip_prefix('_')?
+            .strip_suffix(self.extension.as_str())?
+            .strip_suffix('.')?
+            .parse::<u32>()
+            .ok()
+    }
+
+    /// Given a `file_name`, return `true` if it is an SST of any storage type: an indexed SST or the leaf or
+    /// internal file of an `LSMTree` run.
+    /// # Arguments
+    /// * `self` - A ref to the `SstNaming`.
+    /// * `file_name` - The file name in question, without the directory.
+    pub fn is_sst_file(&self, file_name: &str) -> bool {
+        if self.parse_index(file_name).is_some() {
+            return true;
+        }
+        let run: Option<&str> = file_name
+            .strip_prefix(self.prefix.as_str())
+            .and_then(|name| name.strip_prefix('_'))
+            .and_then(|name| name.strip_suffix(self.extension.as_str()))
+            .and_then(|name| name.strip_suffix('.'))
+            .and_then(|name| {
+                name.strip_prefix("leaf_")
+                    .or_else(|| name.strip_prefix("internal_"))
+            });
+        run.and_then(|run| run.split_once('_'))
+            .is_some_and(|(level, index)| {
+                level.parse::<u32>().is_ok() && index.parse::<u32>().is_ok()
+            })
+    }
+}
+
+// Special default implementation of the `SstNaming`, `output_{index}.bin`.
+impl Default for SstNaming {
+    fn default() -> Self {
+        Self::new("output".to_string(), "bin".to_string())
+    }
+}
+
+/// Given `db_name`, output the indices and names of all the SSTs inside, newest (highest index) first.
+/// The order comes from the indices in the file names, so other files in the directory are ignored.
 /// # Arguments
 /// * `db_name` - The path to the database in question.
-pub fn get_sst_names(db_name: &str) -> Vec<String> {
+/// * `naming` - The naming convention of the SSTs.
+pub fn get_indexed_sst_names(db_name: &str, naming: &SstNaming) -> Vec<(u32, String)> {
     let db_path: String = format!("./{}/", db_name);
 
-    let mut sst_names: Vec<String> = vec![];
+    let mut sst_indices: Vec<u32> = vec![];
     if let Ok(entries) = read_dir(&db_path) {
-        let num_sst: usize = entries.count();
-
-        sst_names = (0..num_sst)
-            .rev()
-            .map(|i: usize| format!("{}output_{}.bin", db_path, i))
+        sst_indices = entries
+            .filter_map(Result::ok)
+            .filter_map(|entry| naming.parse_index(&entry.file_name().to_string_lossy()))
             .collect();
     }
-    sst_names
+    sst_indices.sort_unstable_by(|a, b| b.cmp(a));
+
+    sst_indices
+        .into_iter()
+        .map(|i: u32| (i, format!("{}{}", db_path, naming.sst_file_name(i))))
+        .collect()
+}
+
+/// Given `db_name`, output the index the next SST is written at: one past the newest SST, 0 when there is none.
+/// # Arguments
+/// * `db_name` - The path to the database in question.
+/// * `naming` - The naming convention of the SSTs.
+pub fn next_sst_index(db_name: &str, naming: &SstNaming) -> u32 {
+    get_indexed_sst_names(db_name, naming)
+        .first()
+        .map_or(0, |(index, _)| index + 1)
+}
+
+/// Given `db_name`, output all the names of SSTs inside, newest (highest index) first.
+/// # Arguments
+/// * `db_name` - The path to the database in question.
+/// * `naming` - The naming convention of the SSTs.
+pub fn get_sst_names(db_name: &str, naming: &SstNaming) -> Vec<String> {
+    get_indexed_sst_names(db_name, naming)
+        .into_iter()
+        .map(|(_, name)| name)
+        .collect()
+}
+
+/// Given a `root` directory, output the paths of the DBs directly inside it, sorted, to open each with
+/// `Client::open`. A subdirectory is a DB if it holds an SST with the default file names, so a DB with other SST
+/// names, or one that never flushed, is not listed.
+/// # Arguments
+/// * `root` - The path to the directory holding the DBs.
+pub fn list_databases(root: &Path) -> Vec<String> {
+    let naming: SstNaming = SstNaming::default();
+    let mut databases: Vec<String> = match read_dir(root) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_ok_and(|kind| kind.is_dir()))
+            .filter(|entry| {
+                read_dir(entry.path()).is_ok_and(|mut files| {
+                    files.any(|file| {
+                        file.is_ok_and(|file| {
+                            naming.is_sst_file(&file.file_name().to_string_lossy())
+                        })
+                    })
+                })
+            })
+            .map(|entry| entry.path().to_string_lossy().into_owned())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    databases.sort_unstable();
+    databases
 }
 
 /*
@@ -154,7 +600,7 @@ pub fn get_sst_names(db_name: &str) -> Vec<String> {
 /// # Arguments
 /// * `kv_arr` - The vector of KV pairs.
 /// * `key` - The key who's value we want.
-fn binary_search_array(kv_arr: &Vec<(i64, i64)>, key: i64) -> Option<i64> {
+fn binary_search_array(kv_arr: &[(i64, i64)], key: i64) -> Option<i64> {
     let mut left: usize = 0;
     let mut right: usize = kv_arr.len() - 1;
 
@@ -170,52 +616,76 @@ fn binary_search_array(kv_arr: &Vec<(i64, i64)>, key: i64) -> Option<i64> {
     None
 }
 
-/// Given `file_path`, `total_pages`, and a `key`. Find the value of the `key` in the page at `file_path`.
+/// Given `file_path`, `total_pages`, and a `key`. Find the value of the `key` in the page at `file_path`. An SST of a
+/// single page, as small flushes write, is searched directly instead of going through the page search.
 /// # Arguments
 /// * `file_path` - The path to the SST file in question.
 /// * `total_pages` - The size of `file_path` in number of pages.
 /// * `key` - The key who's value to find.
-pub fn binary_search_file(file_path: &str, total_pages: usize, key: i64) -> Option<i64> {
+pub fn binary_search_file(
+    file_path: &str,
+    total_pages: usize,
+    key: i64,
+) -> Result<Option<i64>, KvError> {
+    if total_pages == 1 {
+        let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, 0)?;
+        let in_range: bool = kv_arr.first().unwrap().0 <= key && key <= kv_arr.last().unwrap().0;
+        return Ok(in_range
+            .then(|| binary_search_array(&kv_arr, key))
+            .flatten());
+    }
+
     let mut left: usize = 0;
     let mut right: usize = total_pages - 1;
 
     while left <= right {
         let mid: usize = left + (right - left) / 2;
 
-        let kv_arr: Vec<(i64, i64)> = deserialize_page(file_path, mid * PAGE_SIZE);
+        let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, mid * PAGE_SIZE)?;
         let first_key: i64 = kv_arr.first().unwrap().0;
         let last_key: i64 = kv_arr.last().unwrap().0;
 
         if first_key <= key && key <= last_key {
-            return binary_search_array(&kv_arr, key);
+            return Ok(binary_search_array(&kv_arr, key));
         } else if first_key > key {
             if mid == 0 {
-                return None;
+                return Ok(None);
             }
             right = mid - 1;
         } else {
             left = mid + 1;
         }
     }
-    None
+    Ok(None)
 }
 
 /// This is the primary call from the Client code to search through the SSTs in the DB `db_name` to find the value of `key`.
 /// # Arguments
 /// * `db_name` - The name of the database to search.
+/// * `naming` - The naming convention of the SSTs.
 /// * `key` - The key who's value to find.
-pub fn get_value_ssts(db_name: &str, key: i64) -> Option<i64> {
-    let sst_names: Vec<String> = get_sst_names(db_name);
+pub fn get_value_ssts(db_name: &str, naming: &SstNaming, key: i64) -> Result<Option<i64>, KvError> {
+    Ok(get_value_ssts_with_index(db_name, naming, key)?.map(|(value, _)| value))
+}
 
-    for name in sst_names {
-        let total_pages: usize =
-            (metadata(&name).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
-        let value: Option<i64> = binary_search_file(&name, total_pages, key);
-        if value.is_some() {
-            return value;
+/// Search through the SSTs in the DB `db_name` to find the value of `key`, along with the index of the SST it was
+/// found in.
+/// # Arguments
+/// * `db_name` - The name of the database to search.
+/// * `naming` - The naming convention of the SSTs.
+/// * `key` - The key who's value to find.
+pub fn get_value_ssts_with_index(
+    db_name: &str,
+    naming: &SstNaming,
+    key: i64,
+) -> Result<Option<(i64, u32)>, KvError> {
+    for (index, name) in get_indexed_sst_names(db_name, naming) {
+        let total_pages: usize = sst_total_pages(&name)?;
+        if let Some(value) = binary_search_file(&name, total_pages, key)? {
+            return Ok(Some((value, index)));
         }
     }
-    None
+    Ok(None)
 }
 
 /*
@@ -226,7 +696,7 @@ pub fn get_value_ssts(db_name: &str, key: i64) -> Option<i64> {
 /// # Arguments
 /// * `kv_arr` - The array of KV pairs.
 /// * `key` - The key in question.
-pub fn binary_search_array_start_index(kv_arr: &Vec<(i64, i64)>, key: i64) -> Option<usize> {
+pub fn binary_search_array_start_index(kv_arr: &[(i64, i64)], key: i64) -> Option<usize> {
     let mut found_arr_idx: Option<usize> = None;
 
     let mut left: usize = 0;
@@ -262,43 +732,47 @@ pub fn binary_search_sst_start_index(
     total_pages: &usize,
     start: i64,
     end: i64,
-) -> (Option<usize>, Option<usize>) {
+) -> Result<(Option<usize>, Option<usize>), KvError> {
     let mut start_page_idx: Option<usize> = None;
     let mut start_arr_idx: Option<usize> = None;
 
-    let first_page_arr: Vec<(i64, i64)> = deserialize_page(file_path, 0);
-    let last_page_arr: Vec<(i64, i64)> = deserialize_page(file_path, (total_pages - 1) * PAGE_SIZE);
+    let first_page_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, 0)?;
+    let last_page_arr: Vec<(i64, i64)> =
+        try_deserialize_page(file_path, (total_pages - 1) * PAGE_SIZE)?;
 
     if first_page_arr[0].0 <= start && start <= last_page_arr[last_page_arr.len() - 1].0 {
-        // case start in sst
+        // case start in sst, the first page whose last key is >= start holds the first key of the range
+        // even when start falls in the gap between two pages
         let mut left: usize = 0;
         let mut right: usize = total_pages - 1;
-        let mut kv_arr: Vec<(i64, i64)> = Vec::new();
 
         // find start_page_idx
-        while left <= right {
+        while left < right {
             let mid: usize = left + (right - left) / 2;
 
-            kv_arr = deserialize_page(file_path, mid * PAGE_SIZE);
+            let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, mid * PAGE_SIZE)?;
 
-            if kv_arr[0].0 <= start && start <= kv_arr[kv_arr.len() - 1].0 {
-                start_page_idx = Some(mid);
-                break;
-            } else if start < kv_arr[0].0 {
-                right = mid - 1;
-            } else {
+            if kv_arr[kv_arr.len() - 1].0 < start {
                 left = mid + 1;
+            } else {
+                right = mid;
             }
         }
 
         // find start_arr_idx
-        start_arr_idx = binary_search_array_start_index(&kv_arr, start);
+        let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, left * PAGE_SIZE)?;
+        if let Some(arr_idx) = binary_search_array_start_index(&kv_arr, start) {
+            if kv_arr[arr_idx].0 <= end {
+                start_page_idx = Some(left);
+                start_arr_idx = Some(arr_idx);
+            }
+        }
     } else if start < first_page_arr[0].0 && first_page_arr[0].0 <= end {
         start_page_idx = Some(0_usize);
         start_arr_idx = Some(0_usize);
     }
 
-    (start_page_idx, start_arr_idx)
+    Ok((start_page_idx, start_arr_idx))
 }
 
 /// Given a `file_path`, keep adding values to the `kv_hash` result structure until the scan range is exit
@@ -309,70 +783,207 @@ pub fn binary_search_sst_start_index(
 /// * `page_idx` - The index of the page to scan.
 /// * `arr_idx` - The index of where to start the scan in the page.
 /// * `end` - The end of the scan range.
-/// * `kv_hash` - The HashMap to store the results.
-pub fn scan_file(
+/// * `kv_hash` - The `ScanOutput` to store the results.
+pub fn scan_file<T: ScanOutput + ?Sized>(
     file_path: &str,
     total_pages: usize,
     mut page_idx: usize,
     mut arr_idx: usize,
     end: i64,
-    kv_hash: &mut HashMap<i64, i64>,
-) {
+    kv_hash: &mut T,
+) -> Result<(), KvError> {
     while page_idx != total_pages {
-        let kv_arr: Vec<(i64, i64)> = deserialize_page(file_path, page_idx * PAGE_SIZE);
+        let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, page_idx * PAGE_SIZE)?;
         let kv_arr_len: usize = kv_arr.len();
 
         while arr_idx < kv_arr_len && kv_arr[arr_idx].0 <= end {
-            kv_hash
-                .entry(kv_arr[arr_idx].0)
-                .or_insert(kv_arr[arr_idx].1);
+            kv_hash.add(kv_arr[arr_idx].0, kv_arr[arr_idx].1);
             arr_idx += 1;
         }
 
         arr_idx = 0;
         page_idx += 1;
     }
+    Ok(())
 }
 
 /// This is the primary call from the Client code to scan through the SSTs in the DB `db_name` to find the values
 /// from `start` to `end` (both INCLUSIVE). It stores its findings in `kv_hash` as to eliminate any duplicates.
 /// # Arguments
 /// * `db_name` - The name of the database to search.
+/// * `naming` - The naming convention of the SSTs.
 /// * `start` - The start key range of the scan.
 /// * `end` - The end key range of the scan.
-/// * `kv_hash` - The HashMap to store the results.
-pub fn scan_ssts(db_name: &str, start: i64, end: i64, kv_hash: &mut HashMap<i64, i64>) {
-    let num_elements_in_range: usize = (end - start) as usize;
+/// * `kv_hash` - The `ScanOutput` to store the results.
+pub fn scan_ssts<T: ScanOutput + ?Sized>(
+    db_name: &str,
+    naming: &SstNaming,
+    start: i64,
+    end: i64,
+    kv_hash: &mut T,
+) -> Result<(), KvError> {
+    // both ends are INCLUSIVE, widened so the full key range does not overflow
+    let num_elements_in_range: u128 = (end as i128 - start as i128 + 1) as u128;
 
-    let sst_names: Vec<String> = get_sst_names(db_name);
+    let sst_names: Vec<String> = get_sst_names(db_name, naming);
     for name in sst_names {
-        let total_pages: usize =
-            (metadata(&name).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
+        let total_pages: usize = sst_total_pages(&name)?;
 
         if let (Some(page_idx), Some(arr_idx)) =
-            binary_search_sst_start_index(&name, &total_pages, start, end)
+            binary_search_sst_start_index(&name, &total_pages, start, end)?
         {
-            scan_file(&name, total_pages, page_idx, arr_idx, end, kv_hash);
+            scan_file(&name, total_pages, page_idx, arr_idx, end, kv_hash)?;
         }
 
-        if kv_hash.len() == num_elements_in_range {
+        if kv_hash.count() as u128 == num_elements_in_range {
             break;
         }
     }
+    Ok(())
+}
+
+/*
+    The following functions are specifically for checking the SSTs of a DB.
+*/
+
+/// Given the `file_path` to an SST, check that all of its pages can be read and hold their keys in order. Pages whose
+/// first two keys are equal are `BTree` internal pages, their keys after the first must be ascending and point inside
+/// the SST. All the other pages are leaves, their keys must be strictly ascending across the whole SST. An SST with an
+/// `SstFooter` must match it, and one without is taken as truncated if `footers`. Returns `KvError::CorruptPage` for
+/// the first page that fails, or pointing at the end of the pages for a footer that fails.
+/// # Arguments
+/// * `file_path` - The path to the SST to check.
+/// * `footers` - Whether the SST must end with a footer.
+pub fn verify_sst(file_path: &str, footers: bool) -> Result<(), KvError> {
+    let total_pages: usize = sst_total_pages(file_path)?;
+    let footer: Option<SstFooter> = read_sst_footer(file_path)?;
+    let corrupt_footer = || KvError::CorruptPage {
+        file: file_path.to_string(),
+        offset: total_pages * PAGE_SIZE,
+    };
+    if footers && footer.is_none() {
+        return Err(corrupt_footer());
+    }
+
+    let mut first_leaf_key: Option<i64> = None;
+    let mut last_leaf_key: Option<i64> = None;
+    let mut leaf_entries: u64 = 0;
+
+    for page_idx in 0..total_pages {
+        let corrupt_page = || KvError::CorruptPage {
+            file: file_path.to_string(),
+            offset: page_idx * PAGE_SIZE,
+        };
+        let kv_arr: Vec<(i64, i64)> = try_deserialize_page(file_path, page_idx * PAGE_SIZE)?;
+        if kv_arr.is_empty() {
+            return Err(corrupt_page());
+        }
+
+        if last_leaf_key.is_none() && kv_arr.len() > 1 && kv_arr[0].0 == kv_arr[1].0 {
+            // case internal node page
+            let in_order: bool = kv_arr[1..].windows(2).all(|pair| pair[0].0 < pair[1].0);
+            let in_file: bool = kv_arr
+                .iter()
+                .all(|a| a.1 > page_idx as i64 && a.1 < total_pages as i64);
+            if !in_order || !in_file {
+                return Err(corrupt_page());
+            }
+        } else {
+            // case leaf page
+            let in_order: bool = kv_arr.windows(2).all(|pair| pair[0].0 < pair[1].0);
+            if !in_order || last_leaf_key.is_some_and(|key| key >= kv_arr[0].0) {
+                return Err(corrupt_page());
+            }
+            first_leaf_key = first_leaf_key.or(Some(kv_arr[0].0));
+            last_leaf_key = kv_arr.last().map(|a| a.0);
+            leaf_entries += kv_arr.len() as u64;
+        }
+    }
+
+    if let Some(footer) = footer {
+        let matches: bool = footer.entries == leaf_entries
+            && footer.min_key == first_leaf_key.unwrap_or(0)
+            && footer.max_key == last_leaf_key.unwrap_or(0);
+        if !matches {
+            return Err(corrupt_footer());
+        }
+    }
+    Ok(())
+}
+
+/// Check every SST of the DB `db_name`, see `verify_sst`.
+/// # Arguments
+/// * `db_name` - The name of the database to check.
+/// * `naming` - The naming convention of the SSTs.
+/// * `footers` - Whether the SSTs must end with a footer.
+pub fn verify_ssts(db_name: &str, naming: &SstNaming, footers: bool) -> Result<(), KvError> {
+    for name in get_sst_names(db_name, naming) {
+        verify_sst(&name, footers)?;
+    }
+    Ok(())
+}
+
+/// The name of the directory of a DB that `drop_corrupt_ssts` moves the quarantined SSTs into.
+pub const QUARANTINE_DIR: &str = "quarantine";
+
+/// Check every SST of the DB `db_name` like `verify_ssts`, but take the corrupt ones out of the DB instead of failing:
+/// they are moved into its `QUARANTINE_DIR` directory if `quarantine`, removed otherwise. Returns the error found in
+/// each SST taken out, or the IO error of an SST that could not be taken out.
+/// # Arguments
+/// * `db_name` - The name of the database to check.
+/// * `naming` - The naming convention of the SSTs.
+/// * `footers` - Whether the SSTs must end with a footer.
+/// * `quarantine` - Whether to keep the corrupt SSTs aside instead of removing them.
+pub fn drop_corrupt_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    footers: bool,
+    quarantine: bool,
+) -> Result<Vec<KvError>, KvError> {
+    let mut lost: Vec<KvError> = Vec::new();
+    for name in get_sst_names(db_name, naming) {
+        let Err(error) = verify_sst(&name, footers) else {
+            continue;
+        };
+        let io_error = |error: io::Error| KvError::Io {
+            file: name.clone(),
+            kind: error.kind(),
+        };
+        files::forget_file(&name);
+        if quarantine {
+            let quarantine_dir: String = format!("./{}/{}", db_name, QUARANTINE_DIR);
+            create_dir_all(&quarantine_dir).map_err(io_error)?;
+            let file_name = Path::new(&name).file_name().unwrap_or_default();
+            rename(&name, Path::new(&quarantine_dir).join(file_name)).map_err(io_error)?;
+        } else {
+            remove_file(&name).map_err(io_error)?;
+        }
+        lost.push(error);
+    }
+    Ok(lost)
 }
 
 #[cfg(test)]
 mod tests {
     mod serde {
+        use crate::files::{read_stats, reset_read_stats};
         use crate::serde::{
             binary_search_array, binary_search_array_start_index, binary_search_file,
             binary_search_sst_start_index, deserialize_page, get_sst_names, get_value_ssts,
-            pad_page_bytes, scan_file, scan_ssts, serialize_kv_to_file, PAGE_SIZE,
+            pad_page_bytes, publish_file, read_sst_footer, scan_file, scan_ssts,
+            serialize_kv_to_file, serialize_kv_to_file_delta, sst_total_pages, temp_file_path,
+            verify_sst, write_sst_footer, SstFooter, SstNaming, FOOTER_SIZE, FORMAT_VERSION,
+            PAGE_SIZE,
         };
+        use crate::KvError;
 
         use std::{
             collections::HashMap,
-            fs::{create_dir_all, metadata, remove_dir, remove_file, File},
+            fs::{
+                create_dir_all, metadata, remove_dir, remove_dir_all, remove_file, File,
+                OpenOptions,
+            },
+            os::unix::fs::FileExt,
         };
 
         #[test]
@@ -429,6 +1040,70 @@ mod tests {
             remove_dir(folder_path).expect("Remove dir has failed!");
         }
 
+        #[test]
+        fn test_publish_file() {
+            let folder_path: &str = "./publishFileTestDB/";
+            let file_path_string: String = format!("{}output_0.bin", folder_path);
+            let file_path: &str = file_path_string.as_str();
+            let temp_path: String = temp_file_path(file_path);
+
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+
+            let kv_vec: Vec<(i64, i64)> = (0..((PAGE_SIZE / 16) * 3) as i64)
+                .map(|i| (i, i * 2))
+                .collect();
+            serialize_kv_to_file(&temp_path, &kv_vec);
+
+            // not visible under the final name until published
+            assert!(metadata(file_path).is_err());
+            assert!(get_sst_names("publishFileTestDB", &SstNaming::default()).is_empty());
+
+            publish_file(file_path);
+
+            assert!(metadata(&temp_path).is_err());
+            assert_eq!(
+                (PAGE_SIZE * 3) as u64,
+                metadata(file_path).expect("Metadata call failed!").len()
+            );
+            assert_eq!(
+                Some(20),
+                get_value_ssts("publishFileTestDB", &SstNaming::default(), 10).unwrap()
+            );
+
+            remove_file(file_path).expect("Remove file has failed!");
+            remove_dir(folder_path).expect("Remove dir has failed!");
+        }
+
+        #[test]
+        fn test_get_sst_names_ordered_by_index() {
+            let folder_path: &str = "./sstNamesTestDB/";
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+
+            for i in [3, 10, 0, 9, 1, 2, 4, 5, 6, 7, 8, 11] {
+                File::create(format!("{}output_{}.bin", folder_path, i))
+                    .expect("File create has failed!");
+            }
+            File::create(format!("{}output_12.bin.tmp", folder_path))
+                .expect("File create has failed!");
+            File::create(format!("{}notes.txt", folder_path)).expect("File create has failed!");
+
+            let expected: Vec<String> = (0..12)
+                .rev()
+                .map(|i| format!("{}output_{}.bin", folder_path, i))
+                .collect();
+            assert_eq!(
+                expected,
+                get_sst_names("sstNamesTestDB", &SstNaming::default())
+            );
+
+            let naming: SstNaming = SstNaming::default();
+            assert_eq!(Some(12), naming.parse_index("output_12.bin"));
+            assert_eq!(None, naming.parse_index("output_12.bin.tmp"));
+            assert_eq!(None, naming.parse_index("output_leaf_1_0.bin"));
+
+            remove_dir_all(folder_path).expect("Remove dir all has failed!");
+        }
+
         #[test]
         fn test_get_db_sst_names() {
             let db_name: String = "sstNameTestDB".to_string();
@@ -444,7 +1119,7 @@ mod tests {
                 expected.insert(0, format!("{}{}", folder_path, file_name));
             }
 
-            let names: Vec<String> = get_sst_names(&db_name);
+            let names: Vec<String> = get_sst_names(&db_name, &SstNaming::default());
             assert_eq!(names, expected);
 
             for i in 0..10 {
@@ -485,18 +1160,120 @@ mod tests {
                 (metadata(file_path).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
 
             for key in 0..((PAGE_SIZE / 16) * 5) as i64 {
-                assert_eq!(Some(key * 2), binary_search_file(file_path, file_size, key));
+                assert_eq!(
+                    Some(key * 2),
+                    binary_search_file(file_path, file_size, key).unwrap()
+                );
             }
 
             assert_eq!(
                 None,
-                binary_search_file(file_path, file_size, ((PAGE_SIZE / 16) * 5) as i64)
+                binary_search_file(file_path, file_size, ((PAGE_SIZE / 16) * 5) as i64).unwrap()
             );
 
             remove_file(file_path).expect("Remove file has failed!");
             remove_dir(folder_path).expect("Remove dir has failed!");
         }
 
+        #[cfg(feature = "rayon")]
+        #[test]
+        fn test_encode_pages_parallel() {
+            use crate::serde::{encode_pages, encode_pages_parallel};
+
+            for len in [0, 1, 255, 256, 257, 256 * 100 + 17] {
+                let kv_vec: Vec<(i64, i64)> = (0..len).map(|i| (i * 2, -i)).collect();
+                let bytes: Vec<u8> = encode_pages_parallel(&kv_vec);
+                assert_eq!(encode_pages(&kv_vec), bytes, "{} KV pairs", len);
+                assert!(bytes.len().is_multiple_of(PAGE_SIZE));
+            }
+        }
+
+        #[test]
+        fn test_sst_footer() {
+            let folder_path: &str = "./sstFooterTestDB1/";
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+            let file_path: String = format!("{}output_0.bin", folder_path);
+
+            // 2 full pages and one with a single KV pair
+            let kv_vec: Vec<(i64, i64)> = (0..513).map(|i| (i * 3 - 100, i)).collect();
+            serialize_kv_to_file(&file_path, &kv_vec);
+            write_sst_footer(&file_path, &kv_vec);
+
+            let expected: SstFooter = SstFooter {
+                version: FORMAT_VERSION,
+                pages: 3,
+                entries: 513,
+                min_key: -100,
+                max_key: 1436,
+            };
+            assert_eq!(Some(expected), read_sst_footer(&file_path).unwrap());
+            assert_eq!(
+                3 * PAGE_SIZE + FOOTER_SIZE,
+                metadata(&file_path).unwrap().len() as usize
+            );
+            // the footer is not a page
+            assert_eq!(3, sst_total_pages(&file_path).unwrap());
+            assert_eq!(
+                kv_vec[512..],
+                deserialize_page(&file_path, 2 * PAGE_SIZE)[..]
+            );
+            assert_eq!(Ok(()), verify_sst(&file_path, true));
+
+            // a changed footer byte fails its checksum
+            let file = OpenOptions::new().write(true).open(&file_path).unwrap();
+            file.write_all_at(&[0xff], 3 * PAGE_SIZE as u64 + 20)
+                .unwrap();
+            let corrupt_footer = || KvError::CorruptPage {
+                file: file_path.clone(),
+                offset: 3 * PAGE_SIZE,
+            };
+            assert_eq!(Err(corrupt_footer()), read_sst_footer(&file_path));
+
+            // a truncated SST has no footer left
+            file.set_len(3 * PAGE_SIZE as u64).unwrap();
+            drop(file);
+            assert_eq!(None, read_sst_footer(&file_path).unwrap());
+            assert_eq!(Err(corrupt_footer()), verify_sst(&file_path, true));
+            assert_eq!(Ok(()), verify_sst(&file_path, false));
+
+            remove_file(&file_path).expect("Remove file has failed!");
+            remove_dir(folder_path).expect("Remove dir has failed!");
+        }
+
+        #[test]
+        fn test_get_from_single_page_sst() {
+            let folder_path: &str = "./getBinarySearchTestDB3/";
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+
+            // the even keys from 0 to 398, in a full page then in a delta encoded one
+            let kv_vec: Vec<(i64, i64)> = (0..200).map(|i| (i * 2, -i)).collect();
+            let file_paths: [String; 2] = [
+                format!("{}output_1.bin", folder_path),
+                format!("{}output_2.bin", folder_path),
+            ];
+            serialize_kv_to_file(&file_paths[0], &kv_vec);
+            serialize_kv_to_file_delta(&file_paths[1], &kv_vec);
+
+            for file_path in &file_paths {
+                assert_eq!(1, metadata(file_path).unwrap().len() as usize / PAGE_SIZE);
+                reset_read_stats();
+                for key in -1..=400 {
+                    let expected: Option<i64> =
+                        ((0..400).contains(&key) && key % 2 == 0).then_some(-key / 2);
+                    assert_eq!(
+                        expected,
+                        binary_search_file(file_path, 1, key).unwrap(),
+                        "key {}",
+                        key
+                    );
+                }
+                // one page read per get
+                assert_eq!(402, read_stats().pages);
+                remove_file(file_path).expect("Remove file has failed!");
+            }
+            remove_dir(folder_path).expect("Remove dir has failed!");
+        }
+
         #[test]
         fn test_get_from_ssts_binary_search() {
             let db_name: String = "getBinarySearchTestDB2".to_string();
@@ -523,13 +1300,21 @@ mod tests {
 
             for i in 0..5 {
                 for j in i * pages..(i + 1) * pages {
-                    assert_eq!(Some(j * 2), get_value_ssts(&db_name, j));
+                    assert_eq!(
+                        Some(j * 2),
+                        get_value_ssts(&db_name, &SstNaming::default(), j).unwrap()
+                    );
                 }
             }
 
             assert_eq!(
                 None,
-                get_value_ssts(&db_name, (((PAGE_SIZE / 16) * 5) * 5) as i64)
+                get_value_ssts(
+                    &db_name,
+                    &SstNaming::default(),
+                    (((PAGE_SIZE / 16) * 5) * 5) as i64
+                )
+                .unwrap()
             );
 
             for i in 0..5 {
@@ -578,28 +1363,28 @@ mod tests {
             let mut end: i64 = 40;
             assert_eq!(
                 (Some(0), Some(10)),
-                binary_search_sst_start_index(&file_path, &total_pages, start, end)
+                binary_search_sst_start_index(&file_path, &total_pages, start, end).unwrap()
             );
 
             start = 20 + ((PAGE_SIZE / 16) * 2) as i64;
             end = 40 + ((PAGE_SIZE / 16) * 4) as i64;
             assert_eq!(
                 (Some(2), Some(10)),
-                binary_search_sst_start_index(&file_path, &total_pages, start, end)
+                binary_search_sst_start_index(&file_path, &total_pages, start, end).unwrap()
             );
 
             start = 2;
             end = 40;
             assert_eq!(
                 (Some(0), Some(0)),
-                binary_search_sst_start_index(&file_path, &total_pages, start, end)
+                binary_search_sst_start_index(&file_path, &total_pages, start, end).unwrap()
             );
 
             start = 1;
             end = 5;
             assert_eq!(
                 (None, None),
-                binary_search_sst_start_index(&file_path, &total_pages, start, end)
+                binary_search_sst_start_index(&file_path, &total_pages, start, end).unwrap()
             );
 
             remove_file(file_path).expect("Remove file has failed!");
@@ -608,7 +1393,7 @@ mod tests {
 
         #[test]
         fn test_scan_from_sst_binary_search() {
-            let folder_path: String = format!("./scanBinarySearchTestDB2/");
+            let folder_path: String = "./scanBinarySearchTestDB2/".to_string();
             let file_path: String = format!("{}output_1.bin", &folder_path);
 
             create_dir_all(&folder_path).expect("Create dir all has failed!");
@@ -654,7 +1439,7 @@ mod tests {
 
             let mut kv_ret1: HashMap<i64, i64> = HashMap::new();
             if let (Some(page_idx), Some(arr_idx)) =
-                binary_search_sst_start_index(&file_path, &total_pages, start1, end1)
+                binary_search_sst_start_index(&file_path, &total_pages, start1, end1).unwrap()
             {
                 scan_file(
                     &file_path,
@@ -663,9 +1448,10 @@ mod tests {
                     arr_idx,
                     end1,
                     &mut kv_ret1,
-                );
+                )
+                .unwrap();
             } else {
-                assert!(false, "Not supposed to get here!");
+                panic!("Not supposed to get here!");
             }
 
             assert_eq!(
@@ -678,16 +1464,16 @@ mod tests {
             for (key, value) in kv_expected1 {
                 if let Some(val) = kv_ret1.get(&key) {
                     if *val != value {
-                        assert!(false);
+                        panic!();
                     }
                 } else {
-                    assert!(false);
+                    panic!();
                 }
             }
 
             let mut kv_ret2: HashMap<i64, i64> = HashMap::new();
             if let (Some(page_idx), Some(arr_idx)) =
-                binary_search_sst_start_index(&file_path, &total_pages, start2, end2)
+                binary_search_sst_start_index(&file_path, &total_pages, start2, end2).unwrap()
             {
                 scan_file(
                     &file_path,
@@ -696,9 +1482,10 @@ mod tests {
                     arr_idx,
                     end2,
                     &mut kv_ret2,
-                );
+                )
+                .unwrap();
             } else {
-                assert!(false, "Not supposed to get here!");
+                panic!("Not supposed to get here!");
             }
 
             assert_eq!(
@@ -711,16 +1498,16 @@ mod tests {
             for (key, value) in kv_expected2 {
                 if let Some(val) = kv_ret2.get(&key) {
                     if *val != value {
-                        assert!(false);
+                        panic!();
                     }
                 } else {
-                    assert!(false);
+                    panic!();
                 }
             }
 
             let mut kv_ret3: HashMap<i64, i64> = HashMap::new();
             if let (Some(page_idx), Some(arr_idx)) =
-                binary_search_sst_start_index(&file_path, &total_pages, start3, end3)
+                binary_search_sst_start_index(&file_path, &total_pages, start3, end3).unwrap()
             {
                 print!("{}, {}", page_idx, arr_idx);
                 scan_file(
@@ -730,7 +1517,8 @@ mod tests {
                     arr_idx,
                     end3,
                     &mut kv_ret3,
-                );
+                )
+                .unwrap();
                 assert!(kv_ret3.is_empty());
             }
 
@@ -776,7 +1564,7 @@ mod tests {
             }
 
             let mut kv_ret1: HashMap<i64, i64> = HashMap::new();
-            scan_ssts(&db_name, start1, end1, &mut kv_ret1);
+            scan_ssts(&db_name, &SstNaming::default(), start1, end1, &mut kv_ret1).unwrap();
             assert_eq!(
                 kv_expected1.len(),
                 kv_ret1.len(),
@@ -787,15 +1575,15 @@ mod tests {
             for (key, value) in kv_expected1 {
                 if let Some(val) = kv_ret1.get(&key) {
                     if *val != value {
-                        assert!(false);
+                        panic!();
                     }
                 } else {
-                    assert!(false);
+                    panic!();
                 }
             }
 
             let mut kv_ret2: HashMap<i64, i64> = HashMap::new();
-            scan_ssts(&db_name, start2, end2, &mut kv_ret2);
+            scan_ssts(&db_name, &SstNaming::default(), start2, end2, &mut kv_ret2).unwrap();
             assert_eq!(
                 kv_expected2.len(),
                 kv_ret2.len(),
@@ -806,15 +1594,15 @@ mod tests {
             for (key, value) in kv_expected2 {
                 if let Some(val) = kv_ret2.get(&key) {
                     if *val != value {
-                        assert!(false);
+                        panic!();
                     }
                 } else {
-                    assert!(false);
+                    panic!();
                 }
             }
 
             let mut kv_ret3: HashMap<i64, i64> = HashMap::new();
-            scan_ssts(&db_name, start3, end3, &mut kv_ret3);
+            scan_ssts(&db_name, &SstNaming::default(), start3, end3, &mut kv_ret3).unwrap();
             assert!(kv_ret3.is_empty());
 
             for i in 0..5 {
@@ -823,5 +1611,31 @@ mod tests {
             }
             remove_dir(&db_path).expect("Remove dir has failed!");
         }
+
+        #[test]
+        fn test_mixed_encodings_in_one_db() {
+            let db_name: &str = "mixedEncodingsTestDB";
+            // an older fixed encoding SST of the even keys and a newer delta encoded one of the multiples of 3
+            let evens: Vec<(i64, i64)> = (0..2000).map(|i| (i * 2, 0)).collect();
+            let threes: Vec<(i64, i64)> = (0..2000).map(|i| (i * 3, 1)).collect();
+            serialize_kv_to_file(&format!("{}/output_0.bin", db_name), &evens);
+            serialize_kv_to_file_delta(&format!("{}/output_1.bin", db_name), &threes);
+
+            let naming: SstNaming = SstNaming::default();
+            assert_eq!(Some(1), get_value_ssts(db_name, &naming, 6).unwrap());
+            assert_eq!(Some(0), get_value_ssts(db_name, &naming, 3998).unwrap());
+            assert_eq!(Some(1), get_value_ssts(db_name, &naming, 5997).unwrap());
+            assert_eq!(None, get_value_ssts(db_name, &naming, 5).unwrap());
+
+            let mut kv_ret: HashMap<i64, i64> = HashMap::new();
+            scan_ssts(db_name, &naming, 3900, 4100, &mut kv_ret).unwrap();
+            let expected: HashMap<i64, i64> = (3900..=4100)
+                .filter(|i| i % 3 == 0 || (i % 2 == 0 && *i < 4000))
+                .map(|i| (i, if i % 3 == 0 { 1 } else { 0 }))
+                .collect();
+            assert_eq!(expected, kv_ret);
+
+            remove_dir_all(db_name).expect("Remove dir all has failed!");
+        }
     }
 }
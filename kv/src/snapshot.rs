@@ -0,0 +1,244 @@
+use std::io::{self, Read, Write};
+
+use crate::record::{decode, encode, RECORD_SIZE};
+use crate::serde::PAGE_SIZE;
+
+/// The first bytes of a snapshot.
+pub const SNAPSHOT_MAGIC: [u8; 8] = *b"KVSNAP\0\0";
+
+/// The version of the snapshot format written by `write_snapshot`.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// The only key order so far: ascending `i64` keys.
+const COMPARATOR_I64_ASCENDING: u8 = 0;
+
+/// The size of the header of a snapshot: the magic, the big endian u32 version and page size, the comparator byte,
+/// and the big endian u64 number of KV pairs.
+const SNAPSHOT_HEADER_SIZE: usize = 25;
+
+/// Helper function to make the error of a malformed snapshot.
+/// # Arguments
+/// * `reason` - What is wrong with the snapshot.
+fn invalid_snapshot(reason: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Snapshot: {}!", reason))
+}
+
+/// Given `kv_arr` in ascending key order, write a snapshot of it to `out`: a header describing the format, then every
+/// KV pair as an on disk record (see `encode`).
+/// # Arguments
+/// * `out` - Where to write the snapshot.
+/// * `kv_arr` - The KV pairs, sorted by key.
+pub fn write_snapshot<W: Write>(mut out: W, kv_arr: &[(i64, i64)]) -> io::Result<()> {
+    let mut header: Vec<u8> = Vec::with_capacity(SNAPSHOT_HEADER_SIZE);
+    header.extend_from_slice(&SNAPSHOT_MAGIC);
+    header.extend_from_slice(&SNAPSHOT_VERSION.to_be_bytes());
+    header.extend_from_slice(&(PAGE_SIZE as u32).to_be_bytes());
+    header.push(COMPARATOR_I64_ASCENDING);
+    header.extend_from_slice(&(kv_arr.len() as u64).to_be_bytes());
+    out.write_all(&header)?;
+
+    for &(key, value) in kv_arr {
+        out.write_all(&encode(key, value))?;
+    }
+    out.flush()
+}
+
+/// Read the snapshot in `input` written by `write_snapshot`, calling `add` on each KV pair in ascending key order.
+/// Returns the number of KV pairs read, or an `io::ErrorKind::InvalidData` error if the snapshot is malformed or of
+/// an unknown version.
+/// # Arguments
+/// * `input` - Where to read the snapshot from.
+/// * `add` - Called with each key and value.
+pub fn read_snapshot<R: Read>(mut input: R, mut add: impl FnMut(i64, i64)) -> io::Result<u64> {
+    let mut header: [u8; SNAPSHOT_HEADER_SIZE] = [0u8; SNAPSHOT_HEADER_SIZE];
+    input.read_exact(&mut header)?;
+
+    if header[..8] != SNAPSHOT_MAGIC {
+        return Err(invalid_snapshot("not a snapshot".to_string()));
+    }
+    let version: u32 = u32::from_be_bytes(header[8..12].try_into().unwrap());
+    if version != SNAPSHOT_VERSION {
+        return Err(invalid_snapshot(format!("unknown version {}", version)));
+    }
+    // the page size is informative, records are read one by one
+    if header[16] != COMPARATOR_I64_ASCENDING {
+        return Err(invalid_snapshot(format!(
+            "unknown comparator {}",
+            header[16]
+        )));
+    }
+    let count: u64 = u64::from_be_bytes(header[17..].try_into().unwrap());
+
+    let mut record: [u8; RECORD_SIZE] = [0u8; RECORD_SIZE];
+    let mut last_key: Option<i64> = None;
+    for _ in 0..count {
+        input.read_exact(&mut record)?;
+        let (key, value) = decode(&record);
+        if last_key.is_some_and(|last| last >= key) {
+            return Err(invalid_snapshot("keys out of order".to_string()));
+        }
+        last_key = Some(key);
+        add(key, value);
+    }
+    Ok(count)
+}
+
+/// The first bytes of an export of the records of the runs of a DB.
+pub const RECORDS_MAGIC: [u8; 8] = *b"KVRECS\0\0";
+
+/// The version of the records format written by `write_records`.
+pub const RECORDS_VERSION: u32 = 1;
+
+/// The size of the header of a records export: the magic, and the big endian u32 version and number of runs.
+const RECORDS_HEADER_SIZE: usize = 16;
+
+/// The size of the header of each run: its big endian u32 level and u64 number of records.
+const RUN_HEADER_SIZE: usize = 12;
+
+/// The flag byte of a record deleting its key, whose value is written as 0.
+const TOMBSTONE_FLAG: u8 = 1;
+
+/// The level of a run and its KV pairs in ascending key order, tombstones included.
+pub type LevelRun = (u32, Vec<(i64, i64)>);
+
+/// Given `runs` of `(level, KV pairs)` with the pairs of each run in ascending key order, write every record of them to
+/// `out`: a header, then each run as its level and number of records followed by the records, an on disk record (see
+/// `encode`) and a flag byte set for a delete. Deletes are not written as the `i64::MIN` value they are stored as.
+/// # Arguments
+/// * `out` - Where to write the records.
+/// * `runs` - The levels and KV pairs of the runs, tombstones included.
+pub fn write_records<W: Write>(mut out: W, runs: &[LevelRun]) -> io::Result<()> {
+    let mut header: Vec<u8> = Vec::with_capacity(RECORDS_HEADER_SIZE);
+    header.extend_from_slice(&RECORDS_MAGIC);
+    header.extend_from_slice(&RECORDS_VERSION.to_be_bytes());
+    header.extend_from_slice(&(runs.len() as u32).to_be_bytes());
+    out.write_all(&header)?;
+
+    for (level, kv_arr) in runs {
+        out.write_all(&level.to_be_bytes())?;
+        out.write_all(&(kv_arr.len() as u64).to_be_bytes())?;
+        for &(key, value) in kv_arr {
+            if value == i64::MIN {
+                out.write_all(&encode(key, 0))?;
+                out.write_all(&[TOMBSTONE_FLAG])?;
+            } else {
+                out.write_all(&encode(key, value))?;
+                out.write_all(&[0])?;
+            }
+        }
+    }
+    out.flush()
+}
+
+/// Read the records in `input` written by `write_records`, returning the levels and KV pairs of the runs with the
+/// deletes back as `i64::MIN` values, or an `io::ErrorKind::InvalidData` error if the records are malformed or of an
+/// unknown version.
+/// # Arguments
+/// * `input` - Where to read the records from.
+pub fn read_records<R: Read>(mut input: R) -> io::Result<Vec<LevelRun>> {
+    let mut header: [u8; RECORDS_HEADER_SIZE] = [0u8; RECORDS_HEADER_SIZE];
+    input.read_exact(&mut header)?;
+
+    if header[..8] != RECORDS_MAGIC {
+        return Err(invalid_snapshot("not a records export".to_string()));
+    }
+    let version: u32 = u32::from_be_bytes(header[8..12].try_into().unwrap());
+    if version != RECORDS_VERSION {
+        return Err(invalid_snapshot(format!("unknown version {}", version)));
+    }
+    let run_count: u32 = u32::from_be_bytes(header[12..].try_into().unwrap());
+
+    let mut runs: Vec<LevelRun> = Vec::new();
+    let mut run_header: [u8; RUN_HEADER_SIZE] = [0u8; RUN_HEADER_SIZE];
+    let mut record: [u8; RECORD_SIZE + 1] = [0u8; RECORD_SIZE + 1];
+    for _ in 0..run_count {
+        input.read_exact(&mut run_header)?;
+        let level: u32 = u32::from_be_bytes(run_header[..4].try_into().unwrap());
+        let count: u64 = u64::from_be_bytes(run_header[4..].try_into().unwrap());
+
+        let mut kv_arr: Vec<(i64, i64)> = Vec::new();
+        for _ in 0..count {
+            input.read_exact(&mut record)?;
+            let (key, value) = decode(&record[..RECORD_SIZE]);
+            let value: i64 = match record[RECORD_SIZE] {
+                0 => value,
+                TOMBSTONE_FLAG => i64::MIN,
+                flag => return Err(invalid_snapshot(format!("unknown record flag {}", flag))),
+            };
+            if kv_arr.last().is_some_and(|last| last.0 >= key) {
+                return Err(invalid_snapshot("keys out of order".to_string()));
+            }
+            kv_arr.push((key, value));
+        }
+        runs.push((level, kv_arr));
+    }
+    Ok(runs)
+}
+
+#[cfg(test)]
+mod tests {
+    mod snapshot {
+        use crate::snapshot::{
+            read_records, read_snapshot, write_records, write_snapshot, LevelRun,
+            SNAPSHOT_HEADER_SIZE,
+        };
+        use std::io;
+
+        #[test]
+        fn test_snapshot_round_trip() {
+            let kv_arr: Vec<(i64, i64)> =
+                vec![(i64::MIN, 1), (-5, i64::MAX), (0, 0), (i64::MAX, -1)];
+            let mut bytes: Vec<u8> = Vec::new();
+            write_snapshot(&mut bytes, &kv_arr).unwrap();
+            assert_eq!(SNAPSHOT_HEADER_SIZE + 4 * 16, bytes.len());
+
+            let mut read: Vec<(i64, i64)> = Vec::new();
+            let count: u64 =
+                read_snapshot(&bytes[..], |key, value| read.push((key, value))).unwrap();
+            assert_eq!(4, count);
+            assert_eq!(kv_arr, read);
+        }
+
+        #[test]
+        fn test_snapshot_malformed() {
+            let mut bytes: Vec<u8> = Vec::new();
+            write_snapshot(&mut bytes, &[(1, 1), (2, 2)]).unwrap();
+
+            // cut off in the records
+            let err = read_snapshot(&bytes[..bytes.len() - 1], |_, _| {}).unwrap_err();
+            assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+
+            // other version
+            let mut other: Vec<u8> = bytes.clone();
+            other[11] = 2;
+            let err = read_snapshot(&other[..], |_, _| {}).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+
+            // not a snapshot
+            let err = read_snapshot(&bytes[1..], |_, _| {}).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+
+        #[test]
+        fn test_records_round_trip() {
+            let runs: Vec<LevelRun> = vec![
+                (3, vec![(i64::MIN, 1), (0, i64::MIN), (i64::MAX, 0)]),
+                (1, vec![(0, 5), (7, i64::MIN)]),
+            ];
+            let mut bytes: Vec<u8> = Vec::new();
+            write_records(&mut bytes, &runs).unwrap();
+            assert_eq!(16 + 2 * 12 + 5 * 17, bytes.len());
+            // a delete is flagged, its value is not the tombstone
+            assert_eq!([0u8; 8], bytes[16 + 12 + 17 + 8..16 + 12 + 17 + 16]);
+            assert_eq!(1, bytes[16 + 12 + 2 * 17 - 1]);
+            assert_eq!(runs, read_records(&bytes[..]).unwrap());
+
+            let err = read_records(&bytes[..bytes.len() - 1]).unwrap_err();
+            assert_eq!(io::ErrorKind::UnexpectedEof, err.kind());
+            let mut other: Vec<u8> = bytes.clone();
+            other[bytes.len() - 1] = 2;
+            let err = read_records(&other[..]).unwrap_err();
+            assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        }
+    }
+}
@@ -1,9 +1,12 @@
-use std::collections::HashMap;
-use std::fs::metadata;
+use std::{collections::HashMap, fs::metadata};
 
 use crate::{
     buffer::BufferPool,
-    serde::{binary_search_array_start_index, get_sst_names, serialize_kv_to_file, PAGE_SIZE},
+    serde::{
+        binary_search_array_start_index, deserialize_page, get_indexed_sst_names, get_sst_names,
+        serialize_kv_to_file, SstNaming, PAGE_SIZE,
+    },
+    storage::ScanOutput,
 };
 
 /// The number of entries in a given page (PAGE_SIZE / 16).
@@ -13,25 +16,54 @@ const ENTRIES: usize = 256;
     The following functions are helper function.
 */
 
+/// Helper function to get the number of entries of each leaf page given a `fill_factor`, at least 1.
+/// # Arguments
+/// * `fill_factor` - The fraction of a leaf page to fill, in (0, 1].
+pub fn entries_per_leaf(fill_factor: f32) -> usize {
+    ((ENTRIES as f32 * fill_factor).round() as usize).clamp(1, ENTRIES)
+}
+
 /// Helper function to flush the `Memtable` into a `BTree` implementation SST.
 /// # Arguments
 /// * `file_path` - The path to the new SST.
 /// * `leaf_lst` - The list of nodes to serialize (content leaf nodes).
-pub fn convert_sorted_arr_to_b_tree_arr_and_serialize(file_path: &str, leaf_lst: &Vec<(i64, i64)>) {
-    let mut num_ptrs: usize = (leaf_lst.len() + (ENTRIES - 1)) / ENTRIES; // ceil
-
+/// * `leaf_entries` - The number of entries of each leaf page, at most `ENTRIES`.
+pub fn convert_sorted_arr_to_b_tree_arr_and_serialize(
+    file_path: &str,
+    leaf_lst: &[(i64, i64)],
+    leaf_entries: usize,
+) {
     // special handling: first internal nodes layer
-    let mut candidates: Vec<i64> = (0..leaf_lst.len())
-        .step_by(ENTRIES) // ENTRIES per page
+    let candidates: Vec<i64> = (0..leaf_lst.len())
+        .step_by(leaf_entries) // leaf_entries per page
         .map(|i| leaf_lst[i].0)
         .skip(1)
         .collect();
+    write_b_tree_internal_pages(file_path, candidates);
+
+    if leaf_entries == ENTRIES {
+        serialize_kv_to_file(file_path, leaf_lst);
+    } else {
+        // each leaf page is padded after its entries
+        for leaf in leaf_lst.chunks(leaf_entries) {
+            serialize_kv_to_file(file_path, leaf);
+        }
+    }
+}
+
+/// Helper function to write the internal pages of a `BTree` implementation SST at `file_path`, before its leaf pages.
+/// Nothing is written for a single leaf page.
+/// # Arguments
+/// * `file_path` - The path to the new SST.
+/// * `candidates` - The first key of each leaf page but the first one, in ascending order.
+pub fn write_b_tree_internal_pages(file_path: &str, mut candidates: Vec<i64>) {
+    let mut num_ptrs: usize = candidates.len() + 1;
 
     // [i64] = node of one layer, [node1, node2] = one internal layer, [layer1, layer2] = tree
     let mut internal_levels: Vec<Vec<Vec<i64>>> = Vec::new();
     while !candidates.is_empty() {
         // construct internal layers
-        let curr_level_num_nodes: usize = (num_ptrs + ENTRIES - 1) / ENTRIES; // ceil
+        let curr_level_num_nodes: usize = num_ptrs.div_ceil(ENTRIES); // ceil
 
         let keys_per_node: usize = (num_ptrs - (2 * curr_level_num_nodes)) / curr_level_num_nodes;
         // internal node with idx < excess_keys get an extra key
@@ -90,15 +122,15 @@ pub fn convert_sorted_arr_to_b_tree_arr_and_serialize(file_path: &str, leaf_lst:
             pages_in_front += 1;
         }
     }
-
-    serialize_kv_to_file(file_path, leaf_lst);
 }
 
-/// Given a vector of KV pairs `kv_arr` and a `key`. Return the index of the smallest element >= to `key`.
+/// Given the KV pairs `arr` of an internal page and a `key`, return the index of the last separator <= to `key`,
+/// skipping the first entry which points to the leftmost child. `None` when every separator is > `key`, the key is
+/// then in the leftmost child at index 0.
 /// # Arguments
-/// * `kv_arr` - The array of KV pairs.
+/// * `arr` - The array of KV pairs of the internal page.
 /// * `key` - The key in question.
-pub fn binary_search_internal_se_key(arr: &Vec<(i64, i64)>, key: i64) -> Option<usize> {
+pub fn binary_search_internal_se_key(arr: &[(i64, i64)], key: i64) -> Option<usize> {
     let mut left: usize = 1_usize;
     let mut right: usize = arr.len() - 1;
     let mut found_arr_idx: Option<usize> = None;
@@ -112,13 +144,12 @@ pub fn binary_search_internal_se_key(arr: &Vec<(i64, i64)>, key: i64) -> Option<
                 break;
             }
             std::cmp::Ordering::Less => {
+                // a later separator can still be <= to key
                 found_arr_idx = Some(mid);
-                if mid == left {
-                    break;
-                }
                 left = mid + 1;
             }
             std::cmp::Ordering::Greater => {
+                // left >= 1 so this does not underflow
                 right = mid - 1;
             }
         }
@@ -127,6 +158,118 @@ pub fn binary_search_internal_se_key(arr: &Vec<(i64, i64)>, key: i64) -> Option<
     found_arr_idx
 }
 
+/*
+    The following functions are specifically for the dense key hint.
+*/
+
+/// Struct of where the leaf pages of a `BTree` SST are and which keys they hold.
+#[derive(Clone, Copy)]
+struct LeafLayout {
+    /// The index of the first leaf page, after the internal pages.
+    first_leaf: usize,
+    /// The number of pages in the SST.
+    total_pages: usize,
+    /// The smallest key of the SST.
+    first_key: i64,
+    /// The largest key of the SST.
+    last_key: i64,
+}
+
+/// Struct of the dense key hint of a `BTree`. When the keys of an SST are dense sequential integers, the `i`th key
+/// after its first one is at entry `i % leaf_entries` of leaf page `i / leaf_entries`, so gets and scans find it
+/// without descending the internal pages. The computed slot is checked to hold the key, and the usual descent is
+/// done when it does not.
+pub struct DenseKeys {
+    /// The smallest key of the DB, smaller keys are always found by descending.
+    min_key: i64,
+    /// The number of entries of each leaf page.
+    leaf_entries: usize,
+    /// The leaf layout of each SST read so far, by SST name.
+    layouts: HashMap<String, LeafLayout>,
+}
+
+// Implementation of the `DenseKeys` hint.
+impl DenseKeys {
+    /// Creating a new `DenseKeys` hint given the `min_key` of the DB and the `leaf_entries` of its leaf pages.
+    /// # Arguments
+    /// * `min_key` - The smallest key of the DB.
+    /// * `leaf_entries` - The number of entries of each leaf page.
+    pub fn new(min_key: i64, leaf_entries: usize) -> Self {
+        Self {
+            min_key,
+            leaf_entries,
+            layouts: HashMap::new(),
+        }
+    }
+
+    /// Forget the leaf layout of the SST `sst_name`, to be called when the SST is rewritten or removed.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DenseKeys` hint.
+    /// * `sst_name` - The name of the SST.
+    pub fn invalidate_sst(&mut self, sst_name: &str) {
+        self.layouts.remove(sst_name);
+    }
+
+    /// Get the leaf layout of the SST `file_path`, reading its leftmost path and last page the first time.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DenseKeys` hint.
+    /// * `file_path` - The path to the SST in question.
+    /// * `total_pages` - The number of pages in the SST.
+    /// * `buffer` - The `BufferPool` to read the pages through.
+    fn layout(
+        &mut self,
+        file_path: &str,
+        total_pages: usize,
+        buffer: &mut BufferPool,
+    ) -> LeafLayout {
+        if let Some(layout) = self.layouts.get(file_path) {
+            return *layout;
+        }
+
+        let mut page_idx: usize = 0;
+        let first_page: Vec<(i64, i64)> = loop {
+            let arr: Vec<(i64, i64)> = buffer.find_page(file_path, page_idx * PAGE_SIZE);
+            if arr.len() > 1 && arr[0].0 == arr[1].0 {
+                // case internal node page, the first entry points to the leftmost child
+                assert!(arr[0].1 >= 0);
+                page_idx = arr[0].1 as usize;
+            } else {
+                break arr;
+            }
+        };
+        // the leaf pages are the last ones of the SST
+        let last_page: Vec<(i64, i64)> = buffer.find_page(file_path, (total_pages - 1) * PAGE_SIZE);
+
+        let layout: LeafLayout = LeafLayout {
+            first_leaf: page_idx,
+            total_pages,
+            first_key: first_page.first().map_or(i64::MAX, |a| a.0),
+            last_key: last_page.last().map_or(i64::MIN, |a| a.0),
+        };
+        self.layouts.insert(file_path.to_string(), layout);
+        layout
+    }
+
+    /// Compute the `(page_idx, arr_idx)` slot of `key` in an SST of `layout`, were its keys dense. `None` when the
+    /// slot is past the end of the SST.
+    /// # Arguments
+    /// * `self` - A ref to the `DenseKeys` hint.
+    /// * `layout` - The leaf layout of the SST.
+    /// * `key` - The key in question, from the first to the last key of the SST.
+    fn slot(&self, layout: &LeafLayout, key: i64) -> Option<(usize, usize)> {
+        // widened so the distance between any two keys does not overflow
+        let distance: u128 = (key as i128 - layout.first_key as i128) as u128;
+        let page_idx: u128 = layout.first_leaf as u128 + distance / self.leaf_entries as u128;
+        if page_idx >= layout.total_pages as u128 {
+            return None;
+        }
+        Some((
+            page_idx as usize,
+            (distance % self.leaf_entries as u128) as usize,
+        ))
+    }
+}
+
 /*
     The following functions are specifically for the GET call to SSTs.
 */
@@ -137,8 +280,24 @@ pub fn binary_search_internal_se_key(arr: &Vec<(i64, i64)>, key: i64) -> Option<
 /// * `key` - The key who's value is being searched.
 /// * `buffer` - The `BufferPool` to also search for the key.
 fn search_b_tree_sst(filename: &str, key: i64, buffer: &mut BufferPool) -> Option<i64> {
+    let arr: Vec<(i64, i64)> = find_b_tree_leaf(filename, key, buffer);
+    binary_search_array_start_index(&arr, key).and_then(|i| {
+        if arr[i].0 == key {
+            Some(arr[i].1)
+        } else {
+            None
+        }
+    })
+}
+
+/// Given the `filename`, `key`, and `buffer`, descend the internal pages and return the leaf page that holds `key`
+/// if the SST does, or the one it would be in.
+/// # Arguments
+/// * `filename` - The name of the SST being searched.
+/// * `key` - The key who's leaf page is being searched.
+/// * `buffer` - The `BufferPool` to read the pages through.
+fn find_b_tree_leaf(filename: &str, key: i64, buffer: &mut BufferPool) -> Vec<(i64, i64)> {
     let mut page_idx: usize = 0;
-    let value: Option<i64>;
 
     loop {
         let arr: Vec<(i64, i64)> = buffer.find_page(filename, page_idx * PAGE_SIZE);
@@ -150,36 +309,133 @@ fn search_b_tree_sst(filename: &str, key: i64, buffer: &mut BufferPool) -> Optio
             page_idx = arr[arr_idx].1 as usize;
         } else {
             // case leaf page
-            value = binary_search_array_start_index(&arr, key).and_then(|i| {
-                if arr[i].0 == key {
-                    Some(arr[i].1)
-                } else {
-                    None
-                }
-            });
-            break;
+            return arr;
+        }
+    }
+}
+
+/// Given the `filename`, `key`, `buffer` and `dense` key hint, find and return the value of `key` if it exists. Looks
+/// at the slot the key has were the SST's keys dense first, then descends when the slot holds another key.
+/// # Arguments
+/// * `filename` - The name of the SST being searched.
+/// * `key` - The key who's value is being searched.
+/// * `buffer` - The `BufferPool` to also search for the key.
+/// * `dense` - The dense key hint of the DB.
+fn search_dense_b_tree_sst(
+    filename: &str,
+    key: i64,
+    buffer: &mut BufferPool,
+    dense: &mut DenseKeys,
+) -> Option<i64> {
+    if key < dense.min_key {
+        return search_b_tree_sst(filename, key, buffer);
+    }
+
+    let total_pages: usize =
+        (metadata(filename).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
+    let layout: LeafLayout = dense.layout(filename, total_pages, buffer);
+    if key < layout.first_key || key > layout.last_key {
+        return None;
+    }
+
+    if let Some((page_idx, arr_idx)) = dense.slot(&layout, key) {
+        let arr: Vec<(i64, i64)> = buffer.find_page(filename, page_idx * PAGE_SIZE);
+        if let Some(&(found_key, value)) = arr.get(arr_idx) {
+            if found_key == key {
+                return Some(value);
+            }
         }
     }
-    value
+    // the keys are not dense around key, fall back to the descent
+    search_b_tree_sst(filename, key, buffer)
 }
 
 /// Given the `db_name`, `key`, and `buffer`, find and return the value of `key` if it exists accross all SSTs in DB.
 /// # Arguments
 /// * `db_name` - The name of the DB being searched.
+/// * `naming` - The naming convention of the SSTs.
 /// * `key` - The key who's value is being searched.
 /// * `buffer` - The `BufferPool` to also search for the key.
-pub fn get_b_tree_ssts(db_name: &str, key: i64, buffer: &mut BufferPool) -> Option<i64> {
-    let sst_names: Vec<String> = get_sst_names(db_name);
+/// * `dense` - The dense key hint of the DB, if any.
+pub fn get_b_tree_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    key: i64,
+    buffer: &mut BufferPool,
+    dense: Option<&mut DenseKeys>,
+) -> Option<i64> {
+    get_b_tree_ssts_with_index(db_name, naming, key, buffer, dense).map(|(value, _)| value)
+}
 
-    let mut value: Option<i64> = None;
-    for name in sst_names {
-        value = search_b_tree_sst(&name, key, buffer);
-        if value.is_some() {
-            break;
+/// Given the `db_name`, `key`, and `buffer`, find and return the value of `key` if it exists accross all SSTs in DB,
+/// along with the index of the SST it was found in.
+/// # Arguments
+/// * `db_name` - The name of the DB being searched.
+/// * `naming` - The naming convention of the SSTs.
+/// * `key` - The key who's value is being searched.
+/// * `buffer` - The `BufferPool` to also search for the key.
+/// * `dense` - The dense key hint of the DB, if any.
+pub fn get_b_tree_ssts_with_index(
+    db_name: &str,
+    naming: &SstNaming,
+    key: i64,
+    buffer: &mut BufferPool,
+    mut dense: Option<&mut DenseKeys>,
+) -> Option<(i64, u32)> {
+    get_indexed_sst_names(db_name, naming)
+        .into_iter()
+        .find_map(|(index, name)| {
+            match dense.as_deref_mut() {
+                Some(dense) => search_dense_b_tree_sst(&name, key, buffer, dense),
+                None => search_b_tree_sst(&name, key, buffer),
+            }
+            .map(|value| (value, index))
+        })
+}
+
+/// Helper function to pick among the leaf `pages` of `key` in each SST, given newest first, the first holding `key`,
+/// else the first whose keys span it, else the first one. Returns an empty page if there are none. Stops reading the
+/// pages once one holds `key`.
+/// # Arguments
+/// * `pages` - The leaf page `key` is or would be in, of each SST newest first.
+/// * `key` - The key who's leaf page is being searched.
+pub fn pick_leaf_page(pages: impl Iterator<Item = Vec<(i64, i64)>>, key: i64) -> Vec<(i64, i64)> {
+    let mut spanning: Option<Vec<(i64, i64)>> = None;
+    let mut nearest: Option<Vec<(i64, i64)>> = None;
+    for page in pages {
+        if page.iter().any(|a| a.0 == key) {
+            return page;
+        }
+        let spans: bool = match (page.first(), page.last()) {
+            (Some(first), Some(last)) => first.0 < key && key < last.0,
+            _ => false,
+        };
+        if spans && spanning.is_none() {
+            spanning = Some(page);
+        } else if nearest.is_none() {
+            nearest = Some(page);
         }
     }
+    spanning.or(nearest).unwrap_or_default()
+}
 
-    value
+/// Given the `db_name`, `key`, and `buffer`, return the leaf page of the newest SST holding `key`, else the leaf page
+/// `key` would be in of the newest SST whose keys span it, see `pick_leaf_page`.
+/// # Arguments
+/// * `db_name` - The name of the DB being searched.
+/// * `naming` - The naming convention of the SSTs.
+/// * `key` - The key who's leaf page is being searched.
+/// * `buffer` - The `BufferPool` to read the pages through.
+pub fn leaf_page_b_tree_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    key: i64,
+    buffer: &mut BufferPool,
+) -> Vec<(i64, i64)> {
+    let pages = get_indexed_sst_names(db_name, naming)
+        .into_iter()
+        .map(|(_, name)| find_b_tree_leaf(&name, key, buffer));
+    pick_leaf_page(pages, key)
 }
 
 /*
@@ -194,15 +450,15 @@ pub fn get_b_tree_ssts(db_name: &str, key: i64, buffer: &mut BufferPool) -> Opti
 /// * `page_idx` - The index of the page to scan.
 /// * `arr_idx` - The index of where to start the scan in the page.
 /// * `end` - The end of the scan range.
-/// * `kv_hash` - The HashMap to store the results.
+/// * `kv_hash` - The `ScanOutput` to store the results.
 /// * `buffer` - The `BufferPool` to also search for the keys.
-pub fn scan_b_tree_file(
+pub fn scan_b_tree_file<T: ScanOutput + ?Sized>(
     file_path: &str,
     total_pages: usize,
     page_idx: usize,
     arr_idx: usize,
     end: i64,
-    kv_hash: &mut HashMap<i64, i64>,
+    kv_hash: &mut T,
     buffer: &mut BufferPool,
 ) {
     let mut local_page_idx = page_idx;
@@ -213,7 +469,7 @@ pub fn scan_b_tree_file(
 
         let mut i = local_arr_idx;
         while i < kv_arr.len() && kv_arr[i].0 <= end {
-            kv_hash.entry(kv_arr[i].0).or_insert(kv_arr[i].1);
+            kv_hash.add(kv_arr[i].0, kv_arr[i].1);
             i += 1;
         }
 
@@ -236,18 +492,17 @@ pub fn scan_b_tree_file(
 /// * `page_idx` - The index of the page to scan.
 /// * `arr_idx` - The index of where to start the scan in the page.
 /// * `end` - The end of the scan range.
-/// * `kv_hash` - The HashMap to store the results.
+/// * `kv_hash` - The `ScanOutput` to store the results.
 /// * `buffer` - The `BufferPool` to also search for the keys.
-fn scan_b_tree_sst(
+fn scan_b_tree_sst<T: ScanOutput + ?Sized>(
     file_path: &str,
     start: i64,
     end: i64,
-    kv_hash: &mut HashMap<i64, i64>,
+    kv_hash: &mut T,
     total_pages: usize,
     buffer: &mut BufferPool,
 ) {
     let mut page_idx: usize = 0;
-    let start_page_idx: usize;
 
     // find starting point in file
     loop {
@@ -259,50 +514,289 @@ fn scan_b_tree_sst(
             assert!(arr[arr_idx].1 >= 0);
             page_idx = arr[arr_idx].1 as usize;
         } else {
-            // case leaf page
-            start_page_idx = page_idx;
-            if let Some(start_arr_idx) = binary_search_array_start_index(&arr, start) {
-                scan_b_tree_file(
-                    file_path,
-                    total_pages,
-                    start_page_idx,
-                    start_arr_idx,
-                    end,
-                    kv_hash,
-                    buffer,
-                );
-            }
+            // case leaf page, when start falls in the gap after this leaf the scan begins at the next one
+            let (start_page_idx, start_arr_idx) = match binary_search_array_start_index(&arr, start)
+            {
+                Some(arr_idx) => (page_idx, arr_idx),
+                None => (page_idx + 1, 0_usize),
+            };
+            scan_b_tree_file(
+                file_path,
+                total_pages,
+                start_page_idx,
+                start_arr_idx,
+                end,
+                kv_hash,
+                buffer,
+            );
             break;
         }
     }
 }
 
+/// Given a `file_path` to an SST and the `dense` key hint, start the scan at the slot `start` has were the SST's keys
+/// dense, or find it with `scan_b_tree_sst` when the slot holds another key.
+/// # Arguments
+/// * `file_path` - The path to the SST in question.
+/// * `start` - The start key range of the scan.
+/// * `end` - The end key range of the scan.
+/// * `kv_hash` - The `ScanOutput` to store the results.
+/// * `total_pages` - The number of pages in the SST.
+/// * `buffer` - The `BufferPool` to also search for the keys.
+/// * `dense` - The dense key hint of the DB.
+fn scan_dense_b_tree_sst<T: ScanOutput + ?Sized>(
+    file_path: &str,
+    start: i64,
+    end: i64,
+    kv_hash: &mut T,
+    total_pages: usize,
+    buffer: &mut BufferPool,
+    dense: &mut DenseKeys,
+) {
+    let layout: LeafLayout = dense.layout(file_path, total_pages, buffer);
+    if end < layout.first_key || start > layout.last_key {
+        return;
+    }
+
+    let slot: Option<(usize, usize)> = if start <= layout.first_key {
+        Some((layout.first_leaf, 0))
+    } else if start < dense.min_key {
+        None
+    } else {
+        dense.slot(&layout, start).filter(|&(page_idx, arr_idx)| {
+            let arr: Vec<(i64, i64)> = buffer.find_page(file_path, page_idx * PAGE_SIZE);
+            arr.get(arr_idx).is_some_and(|a| a.0 == start)
+        })
+    };
+
+    match slot {
+        Some((page_idx, arr_idx)) => scan_b_tree_file(
+            file_path,
+            total_pages,
+            page_idx,
+            arr_idx,
+            end,
+            kv_hash,
+            buffer,
+        ),
+        // the keys are not dense around start, fall back to the descent
+        None => scan_b_tree_sst(file_path, start, end, kv_hash, total_pages, buffer),
+    }
+}
+
+/// Given a `file_path` to an SST, return all of its KV pairs in ascending key order, tombstones included.
+/// # Arguments
+/// * `file_path` - The path to the SST in question.
+/// * `buffer` - The `BufferPool` to read the pages through.
+pub fn read_b_tree_sst(file_path: &str, buffer: &mut BufferPool) -> Vec<(i64, i64)> {
+    let mut output: Vec<(i64, i64)> = Vec::new();
+    scan_b_tree_sst_range(file_path, i64::MIN, i64::MAX, &mut output, buffer);
+    output
+}
+
+/// Given a `file_path` to an SST, add its KV pairs from `start` to `end` (both INCLUSIVE) to `output` in ascending
+/// key order, tombstones included.
+/// # Arguments
+/// * `file_path` - The path to the SST in question.
+/// * `start` - The start key range of the scan.
+/// * `end` - The end key range of the scan.
+/// * `output` - The `ScanOutput` to store the results.
+/// * `buffer` - The `BufferPool` to read the pages through.
+pub fn scan_b_tree_sst_range<T: ScanOutput + ?Sized>(
+    file_path: &str,
+    start: i64,
+    end: i64,
+    output: &mut T,
+    buffer: &mut BufferPool,
+) {
+    let total_pages: usize =
+        (metadata(file_path).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
+    scan_b_tree_sst(file_path, start, end, output, total_pages, buffer);
+}
+
+/// Given a `file_path` to an SST, return an iterator over all of its KV pairs in ascending key order, tombstones
+/// included. The leaf pages are read one at a time as the iterator gets to them, straight from the file.
+/// # Arguments
+/// * `file_path` - The path to the SST in question.
+pub fn iter_b_tree_sst(file_path: &str) -> impl Iterator<Item = (i64, i64)> {
+    let total_pages: usize =
+        (metadata(file_path).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
+
+    // the leftmost child of each internal page leads to the first leaf page, the others follow it
+    let mut page_idx: usize = 0;
+    loop {
+        let arr: Vec<(i64, i64)> = deserialize_page(file_path, page_idx * PAGE_SIZE);
+        if arr.len() > 1 && arr[0].0 == arr[1].0 {
+            assert!(arr[0].1 >= 0);
+            page_idx = arr[0].1 as usize;
+        } else {
+            break;
+        }
+    }
+
+    let file_path: String = file_path.to_string();
+    (page_idx..total_pages)
+        .flat_map(move |page_idx| deserialize_page(&file_path, page_idx * PAGE_SIZE))
+}
+
 /// This is the primary call from the Client code to scan through the SSTs in the DB `db_name` to find the values
 /// from `start` to `end` (both INCLUSIVE). It stores its findings in `kv_hash` as to eliminate any duplicates.
 /// # Arguments
 /// * `db_name` - The name of the database to search.
+/// * `naming` - The naming convention of the SSTs.
 /// * `start` - The start key range of the scan.
 /// * `end` - The end key range of the scan.
-/// * `kv_hash` - The HashMap to store the results.
+/// * `kv_hash` - The `ScanOutput` to store the results.
 /// * `buffer` - The `BufferPool` to also search for the keys.
-pub fn scan_b_tree_ssts(
+/// * `dense` - The dense key hint of the DB, if any.
+pub fn scan_b_tree_ssts<T: ScanOutput + ?Sized>(
     db_name: &str,
+    naming: &SstNaming,
     start: i64,
     end: i64,
-    kv_hash: &mut HashMap<i64, i64>,
+    kv_hash: &mut T,
     buffer: &mut BufferPool,
+    mut dense: Option<&mut DenseKeys>,
 ) {
-    let num_elements_in_range: usize = (end - start) as usize;
+    // both ends are INCLUSIVE, widened so the full key range does not overflow
+    let num_elements_in_range: u128 = (end as i128 - start as i128 + 1) as u128;
 
-    let sst_names: Vec<String> = get_sst_names(db_name);
+    let sst_names: Vec<String> = get_sst_names(db_name, naming);
     for name in sst_names {
         let total_pages: usize =
             (metadata(&name).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
 
-        scan_b_tree_sst(&name, start, end, kv_hash, total_pages, buffer);
+        match dense.as_deref_mut() {
+            Some(dense) => {
+                scan_dense_b_tree_sst(&name, start, end, kv_hash, total_pages, buffer, dense)
+            }
+            None => scan_b_tree_sst(&name, start, end, kv_hash, total_pages, buffer),
+        }
 
-        if kv_hash.len() == num_elements_in_range {
+        if kv_hash.count() as u128 == num_elements_in_range {
             break;
         }
     }
 }
+
+/*
+    The following functions are specifically for warming the buffer with SSTs.
+*/
+
+/// Given a `file_path` to an SST, pull into the `buffer` the internal pages leading to `start` and then the leaf pages
+/// up to `end`, reading at most `budget` pages. Returns the number of pages read.
+/// # Arguments
+/// * `file_path` - The path to the SST in question.
+/// * `start` - The start key range to warm.
+/// * `end` - The end key range to warm.
+/// * `total_pages` - The number of pages in the SST.
+/// * `budget` - The largest number of pages to read.
+/// * `buffer` - The `BufferPool` to warm.
+fn warm_b_tree_sst(
+    file_path: &str,
+    start: i64,
+    end: i64,
+    total_pages: usize,
+    budget: usize,
+    buffer: &mut BufferPool,
+) -> usize {
+    let mut page_idx: usize = 0;
+    let mut pages_read: usize = 0;
+    let mut in_leaves: bool = false;
+
+    while pages_read < budget && page_idx < total_pages {
+        let arr: Vec<(i64, i64)> = buffer.find_page(file_path, page_idx * PAGE_SIZE);
+        pages_read += 1;
+
+        if !in_leaves && arr.len() > 1 && arr[0].0 == arr[1].0 {
+            // case internal node page
+            let arr_idx: usize = binary_search_internal_se_key(&arr, start).unwrap_or(0_usize);
+            assert!(arr[arr_idx].1 >= 0);
+            page_idx = arr[arr_idx].1 as usize;
+        } else {
+            // case leaf page, the next leaf is the next page
+            in_leaves = true;
+            if arr.last().is_none_or(|a| a.0 >= end) {
+                break;
+            }
+            page_idx += 1;
+        }
+    }
+    pages_read
+}
+
+/// This is the primary call from the Client code to warm the `buffer` with the pages of the SSTs in the DB `db_name`
+/// that a scan from `start` to `end` (both INCLUSIVE) reads, newest SST first. Stops once as many pages as the
+/// `buffer` holds were read, so the warmed pages do not evict each other. Returns the number of pages read.
+/// # Arguments
+/// * `db_name` - The name of the database to warm.
+/// * `naming` - The naming convention of the SSTs.
+/// * `start` - The start key range to warm.
+/// * `end` - The end key range to warm.
+/// * `buffer` - The `BufferPool` to warm.
+pub fn warm_b_tree_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    start: i64,
+    end: i64,
+    buffer: &mut BufferPool,
+) -> usize {
+    let mut pages_read: usize = 0;
+
+    for name in get_sst_names(db_name, naming) {
+        let budget: usize = buffer.capacity() - pages_read;
+        if budget == 0 {
+            break;
+        }
+        let total_pages: usize =
+            (metadata(&name).expect("Metadata call failed!").len() as usize) / PAGE_SIZE;
+        pages_read += warm_b_tree_sst(&name, start, end, total_pages, budget, buffer);
+    }
+    pages_read
+}
+
+#[cfg(test)]
+mod tests {
+    mod internal_search {
+        use crate::storage::btree::binary_search_internal_se_key;
+
+        /// Helper function to build an internal page over `separators` the way the SSTs are written: the first entry
+        /// points to the leftmost child, the `i`th separator's entry to the child holding the keys from it up to the
+        /// next separator. Child `c` is at page `100 + c`.
+        fn internal_page(separators: &[i64]) -> Vec<(i64, i64)> {
+            let mut page: Vec<(i64, i64)> = vec![(separators[0], 100)];
+            for (i, separator) in separators.iter().enumerate() {
+                page.push((*separator, 101 + i as i64));
+            }
+            page
+        }
+
+        /// Helper function to return the child page the search descends to for `key`.
+        fn child(page: &[(i64, i64)], key: i64) -> i64 {
+            page[binary_search_internal_se_key(page, key).unwrap_or(0)].1
+        }
+
+        #[test]
+        fn test_descends_to_child_of_each_separator() {
+            for count in 1..=9 {
+                let separators: Vec<i64> = (1..=count).map(|i| i * 10).collect();
+                let page: Vec<(i64, i64)> = internal_page(&separators);
+
+                assert_eq!(100, child(&page, i64::MIN));
+                assert_eq!(100, child(&page, 9));
+                for (i, separator) in separators.iter().enumerate() {
+                    let expected: i64 = 101 + i as i64;
+                    assert_eq!(expected, child(&page, *separator), "{:?}", separators);
+                    assert_eq!(expected, child(&page, separator + 1), "{:?}", separators);
+                    assert_eq!(
+                        expected - 1,
+                        child(&page, separator - 1),
+                        "{:?}",
+                        separators
+                    );
+                }
+                assert_eq!(100 + count, child(&page, i64::MAX));
+            }
+        }
+    }
+}
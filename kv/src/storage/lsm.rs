@@ -1,17 +1,25 @@
 use std::{
-    collections::HashMap,
-    fs::{create_dir, remove_file, File, OpenOptions},
-    io::Write,
+    cmp::Reverse,
+    collections::BinaryHeap,
+    fs::{create_dir, remove_file},
+    thread::sleep,
+    time::{Duration, Instant},
 };
 
 use crate::{
     buffer::BufferPool,
-    filter::{Bitmap, BloomFilter},
-    serde::{pad_page_bytes, serialize_kv_to_file, PAGE_SIZE},
-    storage::part3btree,
+    filter::{bloom_seeds, Bitmap, BloomFilter, FilterStats, NUM_SEEDS},
+    serde::{
+        encode_pages, publish_file, temp_file_path, write_pages_to_file, SstNaming, PAGE_SIZE,
+    },
+    storage::{btree, part3btree, DiskPages, PageFiles},
+    KvError,
 };
 
-use super::DiskStorage;
+#[cfg(feature = "rayon")]
+use crate::serde::encode_pages_parallel;
+
+use super::{CompactionCallback, CompactionEvent, DiskStorage, ScanOutput, ValueSource};
 
 pub struct LSMTree {
     name: String,
@@ -19,298 +27,1023 @@ pub struct LSMTree {
     tree_size: u32,
     filters: Vec<Option<Bitmap>>,
     memtable_size: u32,
+    naming: SstNaming,
+    bloom_bits_per_key: u64,
+    // the seeds of the key hashes of the filters
+    bloom_seeds: [u64; NUM_SEEDS],
+    filter_stats: FilterStats,
+    compaction_bytes_per_sec: u64,
+    // (entries, tombstones) of the run at each level, by level like the filters
+    run_counts: Vec<(u64, u64)>,
+    // the fraction of tombstones among the entries of the runs past which they are all merged, none to never
+    tombstone_ratio: Option<f32>,
+    // called after every merge, none to not report them
+    on_compaction: Option<CompactionCallback>,
+    // the thread pool encoding the pages of the runs written, none to encode them on the calling thread
+    #[cfg(feature = "rayon")]
+    encode_pool: Option<rayon::ThreadPool>,
 }
 
 impl LSMTree {
-    pub fn new(name: String, buffer_pool_size: usize, memtable_size: u32) -> Self {
+    pub fn new(
+        name: String,
+        buffer_pool_size: usize,
+        memtable_size: u32,
+        naming: SstNaming,
+        bloom_bits_per_key: u64,
+        compaction_bytes_per_sec: u64,
+        tombstone_ratio: Option<f32>,
+    ) -> Self {
         create_dir(&name).unwrap();
-        let mut filters = vec![];
-        for _ in 0..=50 {
-            filters.push(None);
-        }
         Self {
             name,
             pool: BufferPool::new(buffer_pool_size),
             tree_size: 0,
-            filters,
+            filters: Vec::new(),
             memtable_size,
+            naming,
+            bloom_bits_per_key,
+            bloom_seeds: bloom_seeds(None),
+            filter_stats: FilterStats::default(),
+            compaction_bytes_per_sec,
+            run_counts: Vec::new(),
+            tombstone_ratio,
+            on_compaction: None,
+            #[cfg(feature = "rayon")]
+            encode_pool: None,
         }
     }
 
-    fn merge_ssts(&mut self, level: u32) {
-        // ssts that we are merging
-        let first_sst = format!(
-            "{}/output_leaf_{}_{}.bin",
+    // Sets the seeds of the key hashes of the filters, before anything is flushed
+    pub fn with_bloom_seeds(mut self, seeds: [u64; NUM_SEEDS]) -> Self {
+        self.bloom_seeds = seeds;
+        self
+    }
+
+    // Sets the callback called after every merge with what it did
+    pub fn with_compaction_callback(mut self, callback: Option<CompactionCallback>) -> Self {
+        self.on_compaction = callback;
+        self
+    }
+
+    // Sets the number of threads encoding the pages of the runs written, 1 to encode them on the calling thread
+    #[cfg(feature = "rayon")]
+    pub fn with_encode_threads(mut self, threads: usize) -> Self {
+        self.encode_pool = (threads > 1).then(|| {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .expect("LSMTree: encode thread pool failed!")
+        });
+        self
+    }
+
+    // The pages of a run, encoded on the thread pool if there is one, in the order of the run either way
+    fn encode_run(&self, contents: &[(i64, i64)]) -> Vec<u8> {
+        #[cfg(feature = "rayon")]
+        if let Some(pool) = &self.encode_pool {
+            return pool.install(|| encode_pages_parallel(contents));
+        }
+        encode_pages(contents)
+    }
+
+    fn report_compaction(&self, event: CompactionEvent) {
+        if let Some(callback) = &self.on_compaction {
+            (callback.borrow_mut())(event);
+        }
+    }
+
+    // Sets whether the buffer pool filters the pages it admits, see `BufferPool::with_admission`
+    pub fn with_pool_admission(mut self, admission: bool) -> Self {
+        self.pool = self.pool.with_admission(admission);
+        self
+    }
+
+    fn run_index(&self, level: u32) -> u32 {
+        (self.tree_size / 2_u32.pow(level - 1)) * 2_u32.pow(level - 1)
+    }
+
+    fn leaf_path(&self, level: u32, index: u32) -> String {
+        format!("{}/{}", self.name, self.naming.leaf_file_name(level, index))
+    }
+
+    fn internal_path(&self, level: u32, index: u32) -> String {
+        format!(
+            "{}/{}",
             self.name,
-            level,
-            self.tree_size - 2_u32.pow(level - 1)
+            self.naming.internal_file_name(level, index)
+        )
+    }
+
+    // The filter of the run at a level, none if the level was never written since the open
+    fn filter(&self, level: u32) -> Option<&Bitmap> {
+        self.filters.get(level as usize).and_then(Option::as_ref)
+    }
+
+    // Grows the filters as levels appear, so there is no limit on the number of levels
+    fn set_filter(&mut self, level: u32, filter: Option<Bitmap>) {
+        if self.filters.len() <= level as usize {
+            self.filters.resize_with(level as usize + 1, || None);
+        }
+        self.filters[level as usize] = filter;
+    }
+
+    // Sets the (entries, tombstones) of the run at a level, growing them like the filters
+    fn set_run_counts(&mut self, level: u32, counts: (u64, u64)) {
+        if self.run_counts.len() <= level as usize {
+            self.run_counts.resize(level as usize + 1, (0, 0));
+        }
+        self.run_counts[level as usize] = counts;
+    }
+
+    // The levels holding a run, from the newest run at the lowest level to the oldest one
+    fn levels(&self) -> Vec<u32> {
+        if self.tree_size == 0 {
+            return Vec::new();
+        }
+        (1..=self.tree_size.ilog2() + 1)
+            .filter(|level| self.tree_size & (1 << (level - 1)) != 0)
+            .collect()
+    }
+
+    fn scan_levels<T: ScanOutput + ?Sized>(&mut self, start: i64, end: i64, hash: &mut T) {
+        if self.tree_size == 0 {
+            return;
+        }
+        for i in 1..=self.tree_size.ilog2() + 1 {
+            if self.tree_size & (1 << (i - 1)) == 0 {
+                continue;
+            }
+            let leaf_filename = self.leaf_path(i, self.run_index(i));
+            let internal_filename = self.internal_path(i, self.run_index(i));
+            part3btree::part3_scan_b_tree_sst(
+                &leaf_filename,
+                &internal_filename,
+                start,
+                end,
+                hash,
+                &mut self.pool,
+            );
+        }
+    }
+
+    fn write_run(&mut self, level: u32, contents: &[(i64, i64)]) {
+        let leaf_file_path = self.leaf_path(level, self.tree_size);
+        let internal_file_path = self.internal_path(level, self.tree_size);
+        write_pages_to_file(&temp_file_path(&leaf_file_path), &self.encode_run(contents));
+        publish_file(&leaf_file_path);
+
+        part3btree::part3_create_b_tree_internal_file(
+            &leaf_file_path,
+            &temp_file_path(&internal_file_path),
+            &mut DiskPages::new(None),
         );
-        let first_internal = format!(
-            "{}/output_internal_{}_{}.bin",
-            self.name,
-            level,
-            self.tree_size - 2_u32.pow(level - 1)
+        publish_file(&internal_file_path);
+
+        // create filter
+        let mut b = Bitmap::with_seeds(
+            self.bloom_bits_per_key * contents.len() as u64,
+            self.bloom_seeds,
+        );
+        for i in contents {
+            b.insert_key(i.0);
+        }
+        self.set_filter(level, Some(b));
+        let tombstones: usize = contents.iter().filter(|a| a.1 == i64::MIN).count();
+        self.set_run_counts(level, (contents.len() as u64, tombstones as u64));
+    }
+
+    fn merge_levels_from(&mut self, mut level: u32) {
+        while self.tree_size & (1 << (level - 1)) == 0 {
+            self.merge_ssts(level);
+            level += 1;
+        }
+    }
+
+    fn merge_ssts(&mut self, level: u32) {
+        let started: Instant = Instant::now();
+        // ssts that we are merging
+        let first_sst = self.leaf_path(level, self.tree_size - 2_u32.pow(level - 1));
+        let first_internal = self.internal_path(level, self.tree_size - 2_u32.pow(level - 1));
+        let second_sst = self.leaf_path(level, self.tree_size);
+        let second_internal = self.internal_path(level, self.tree_size);
+
+        let output_file_name = self.leaf_path(level + 1, self.tree_size);
+        let output_file_internal = self.internal_path(level + 1, self.tree_size);
+
+        let mut new_filter = Bitmap::with_seeds(
+            2_u64.pow(level) * self.memtable_size as u64 * self.bloom_bits_per_key,
+            self.bloom_seeds,
         );
-        let second_sst = format!("{}/output_leaf_{}_{}.bin", self.name, level, self.tree_size);
-        let second_internal = format!(
-            "{}/output_internal_{}_{}.bin",
-            self.name, level, self.tree_size
+
+        // the second run is the newer one
+        let counts: MergeCounts = merge_runs(
+            &[first_sst.clone(), second_sst.clone()],
+            &temp_file_path(&output_file_name),
+            &mut DiskPages::new(Some(&mut self.pool)),
+            &mut new_filter,
+            &mut RateLimiter::new(self.compaction_bytes_per_sec),
+            false,
         );
 
-        let first_page_count = File::open(&first_sst)
-            .expect("SST1 not found")
-            .metadata()
-            .unwrap()
-            .len()
-            / PAGE_SIZE as u64;
-        let second_page_count = File::open(&second_sst)
-            .expect("SST2 not found")
-            .metadata()
-            .unwrap()
-            .len()
-            / PAGE_SIZE as u64;
-
-        let mut first_page_idx: u64 = 0;
-        let mut second_page_idx: u64 = 0;
-
-        let mut first_buffer = self
-            .pool
-            .find_page(&first_sst, first_page_idx as usize * PAGE_SIZE);
-        let mut second_buffer = self
-            .pool
-            .find_page(&second_sst, second_page_idx as usize * PAGE_SIZE);
-
-        let mut output_buffer = Vec::with_capacity(256);
-
-        let output_file_name = format!(
-            "{}/output_leaf_{}_{}.bin",
-            self.name,
-            level + 1,
-            self.tree_size
+        publish_file(&output_file_name);
+        part3btree::part3_create_b_tree_internal_file(
+            &output_file_name,
+            &temp_file_path(&output_file_internal),
+            &mut DiskPages::new(None),
         );
-        let output_file_internal = format!(
-            "{}/output_internal_{}_{}.bin",
-            self.name,
-            level + 1,
-            self.tree_size
+        publish_file(&output_file_internal);
+        self.pool.invalidate_sst(&first_sst);
+        remove_file(&first_sst).unwrap();
+        self.pool.invalidate_sst(&second_sst);
+        remove_file(&second_sst).unwrap();
+        self.pool.invalidate_sst(&first_internal);
+        remove_file(first_internal).unwrap();
+        self.pool.invalidate_sst(&second_internal);
+        remove_file(second_internal).unwrap();
+
+        self.set_filter(level, None);
+        self.set_filter(level + 1, Some(new_filter));
+        self.set_run_counts(level, (0, 0));
+        self.set_run_counts(level + 1, counts.run_counts());
+
+        self.report_compaction(CompactionEvent {
+            inputs: vec![(first_sst.clone(), level), (second_sst.clone(), level)],
+            output: output_file_name.clone(),
+            output_level: level + 1,
+            entries_in: counts.read,
+            entries_out: counts.written,
+            tombstones_dropped: counts.tombstones_dropped,
+            duration: started.elapsed(),
+        });
+
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            level,
+            first = %first_sst,
+            second = %second_sst,
+            output = %output_file_name,
+            elapsed = ?started.elapsed(),
+            "merge"
         );
+    }
 
-        let mut new_filter = Bitmap::new(2_u64.pow(level) * self.memtable_size as u64 * 10);
+    // Merges every run into the one at the top level, dropping the tombstones, and leaves that level as the only
+    // one. The run keeps its file name, the runs below the top level all have a higher index.
+    fn merge_all_levels(&mut self) {
+        let started: Instant = Instant::now();
+        let levels: Vec<u32> = self.levels();
+        let top: u32 = *levels.last().expect("LSMTree: no run to merge!");
+        let paths: Vec<(String, String)> = levels
+            .iter()
+            .map(|&level| {
+                (
+                    self.leaf_path(level, self.run_index(level)),
+                    self.internal_path(level, self.run_index(level)),
+                )
+            })
+            .collect();
+        let (output_file_name, output_file_internal) = paths.last().unwrap().clone();
 
-        let mut output_file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&output_file_name)
-            .expect("rip");
+        let entries: u64 = levels
+            .iter()
+            .map(|&level| self.run_counts[level as usize].0)
+            .sum();
+        let mut new_filter =
+            Bitmap::with_seeds(entries.max(1) * self.bloom_bits_per_key, self.bloom_seeds);
 
-        // merge SSTs together
-        loop {
-            if first_buffer.is_empty() {
-                first_page_idx += 1;
-                if first_page_idx == first_page_count {
-                    break;
-                }
-                first_buffer = self
-                    .pool
-                    .find_page(&first_sst, first_page_idx as usize * PAGE_SIZE);
-            }
-            if second_buffer.is_empty() {
-                second_page_idx += 1;
-                if second_page_idx == second_page_count {
-                    break;
-                }
-                second_buffer = self
-                    .pool
-                    .find_page(&second_sst, second_page_idx as usize * PAGE_SIZE);
+        let output_temp: String = temp_file_path(&output_file_name);
+        // the top level holds the oldest run
+        let inputs: Vec<String> = paths.iter().rev().map(|(leaf, _)| leaf.clone()).collect();
+        let counts: MergeCounts = merge_runs(
+            &inputs,
+            &output_temp,
+            &mut DiskPages::new(Some(&mut self.pool)),
+            &mut new_filter,
+            &mut RateLimiter::new(self.compaction_bytes_per_sec),
+            true,
+        );
+
+        // the merged run replaces the top one first, so a crash part way leaves the newer runs to shadow it
+        let emptied: bool = counts.written == 0;
+        if emptied {
+            remove_file(&output_temp).unwrap();
+        } else {
+            publish_file(&output_file_name);
+            part3btree::part3_create_b_tree_internal_file(
+                &output_file_name,
+                &temp_file_path(&output_file_internal),
+                &mut DiskPages::new(None),
+            );
+            publish_file(&output_file_internal);
+        }
+        for (&level, (leaf, internal)) in levels.iter().zip(&paths) {
+            if level != top || emptied {
+                remove_file(leaf).unwrap();
+                remove_file(internal).unwrap();
             }
+            self.pool.invalidate_sst(leaf);
+            self.pool.invalidate_sst(internal);
+            self.set_filter(level, None);
+            self.set_run_counts(level, (0, 0));
+        }
 
-            let first_element = first_buffer[0];
-            let second_element = second_buffer[0];
+        if emptied {
+            self.tree_size = 0;
+        } else {
+            self.tree_size = 1 << (top - 1);
+            self.set_filter(top, Some(new_filter));
+            self.set_run_counts(top, counts.run_counts());
+        }
 
-            match &first_element.0.cmp(&second_element.0) {
-                std::cmp::Ordering::Less => {
-                    output_buffer.push(first_element);
-                    first_buffer.drain(0..1);
-                    new_filter.insert_key(first_element.0);
-                }
-                std::cmp::Ordering::Greater => {
-                    output_buffer.push(second_element);
-                    second_buffer.drain(0..1);
-                    new_filter.insert_key(second_element.0);
-                }
-                std::cmp::Ordering::Equal => {
-                    // keep newest key if duplicates
-                    output_buffer.push(second_element);
-                    first_buffer.drain(0..1);
-                    second_buffer.drain(0..1);
-                    new_filter.insert_key(second_element.0);
-                }
-            }
+        self.report_compaction(CompactionEvent {
+            inputs: inputs
+                .into_iter()
+                .zip(levels.iter().rev().copied())
+                .collect(),
+            output: output_file_name,
+            output_level: top,
+            entries_in: counts.read,
+            entries_out: counts.written,
+            tombstones_dropped: counts.tombstones_dropped,
+            duration: started.elapsed(),
+        });
+    }
+}
 
-            // append to output file when buffer is full
-            if output_buffer.len() == 256 {
-                flush_output_buffer(&mut output_file, &mut output_buffer)
-            }
+// The number of KV pairs a merge read, wrote and dropped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct MergeCounts {
+    read: u64,
+    written: u64,
+    // the tombstones written
+    tombstones: u64,
+    tombstones_dropped: u64,
+}
+
+impl MergeCounts {
+    // The (entries, tombstones) of the merged run
+    fn run_counts(&self) -> (u64, u64) {
+        (self.written, self.tombstones)
+    }
+}
+
+// Caps the write rate of a merge by sleeping whenever the bytes written so far are ahead of the rate, 0 for no cap.
+struct RateLimiter {
+    bytes_per_sec: u64,
+    start: Instant,
+    written: u64,
+}
+
+impl RateLimiter {
+    fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec,
+            start: Instant::now(),
+            written: 0,
         }
+    }
 
-        // write remaining kv pairs to output buffer
-        while first_page_idx < first_page_count {
-            if first_buffer.is_empty() {
-                first_page_idx += 1;
-                if first_page_idx == first_page_count {
-                    break;
-                }
-                first_buffer = self
-                    .pool
-                    .find_page(&first_sst, first_page_idx as usize * PAGE_SIZE);
-            }
-            output_buffer.push(first_buffer[0]);
-            new_filter.insert_key(first_buffer[0].0);
-            first_buffer.drain(0..1);
+    fn throttle(&mut self, bytes: usize) {
+        if self.bytes_per_sec == 0 {
+            return;
+        }
+        self.written += bytes as u64;
+        let allowed: Duration =
+            Duration::from_secs_f64(self.written as f64 / self.bytes_per_sec as f64);
+        let elapsed: Duration = self.start.elapsed();
+        if allowed > elapsed {
+            sleep(allowed - elapsed);
+        }
+    }
+}
 
-            if output_buffer.len() == 256 {
-                flush_output_buffer(&mut output_file, &mut output_buffer);
-            }
+/// A sorted run read one page at a time.
+struct RunStream {
+    path: String,
+    page_count: usize,
+    page_idx: usize,
+    page: std::vec::IntoIter<(i64, i64)>,
+}
+
+impl RunStream {
+    fn new(path: String, files: &mut dyn PageFiles) -> Self {
+        let page_count = files.total_pages(&path);
+        let page = files.read_page(&path, 0).into_iter();
+        Self {
+            path,
+            page_count,
+            page_idx: 0,
+            page,
         }
+    }
 
-        while second_page_idx < second_page_count {
-            if second_buffer.is_empty() {
-                second_page_idx += 1;
-                if second_page_idx == second_page_count {
-                    break;
-                }
-                second_buffer = self
-                    .pool
-                    .find_page(&second_sst, second_page_idx as usize * PAGE_SIZE);
+    fn next(&mut self, files: &mut dyn PageFiles) -> Option<(i64, i64)> {
+        loop {
+            if let Some(pair) = self.page.next() {
+                return Some(pair);
             }
-            output_buffer.push(second_buffer[0]);
-            new_filter.insert_key(second_buffer[0].0);
-            second_buffer.drain(0..1);
-
-            if output_buffer.len() == 256 {
-                flush_output_buffer(&mut output_file, &mut output_buffer);
+            self.page_idx += 1;
+            if self.page_idx >= self.page_count {
+                return None;
             }
+            self.page = files.read_page(&self.path, self.page_idx).into_iter();
         }
+    }
+}
+
+// k-way merge of sorted runs given oldest first into the output file, newest wins on equal keys. Only the current
+// page of each run and one output page are held in memory. The tombstones are dropped when the oldest run of the
+// tree is merged, nothing is left for them to shadow. Returns what was read, written and dropped.
+fn merge_runs(
+    inputs: &[String],
+    output: &str,
+    files: &mut dyn PageFiles,
+    filter: &mut Bitmap,
+    limiter: &mut RateLimiter,
+    drop_tombstones: bool,
+) -> MergeCounts {
+    files.create(output);
+    let mut streams: Vec<RunStream> = inputs
+        .iter()
+        .map(|path| RunStream::new(path.clone(), files))
+        .collect();
 
-        if !output_buffer.is_empty() {
-            flush_output_buffer(&mut output_file, &mut output_buffer);
+    // front element of each run as (key, rank, value), the newest run has rank 0 so it pops first on equal keys
+    let mut heap: BinaryHeap<Reverse<(i64, usize, i64)>> = BinaryHeap::with_capacity(streams.len());
+    for (idx, stream) in streams.iter_mut().enumerate() {
+        if let Some((key, value)) = stream.next(files) {
+            heap.push(Reverse((key, inputs.len() - 1 - idx, value)));
         }
+    }
 
-        part3btree::part3_create_b_tree_internal_file(&output_file_name, &output_file_internal);
-        remove_file(first_sst).unwrap();
-        remove_file(second_sst).unwrap();
-        remove_file(first_internal).unwrap();
-        remove_file(second_internal).unwrap();
+    let mut output_buffer = Vec::with_capacity(256);
+    let mut last_key: Option<i64> = None;
+    let mut counts: MergeCounts = MergeCounts::default();
+    while let Some(Reverse((key, rank, value))) = heap.pop() {
+        counts.read += 1;
+        let idx = inputs.len() - 1 - rank;
+        if let Some((next_key, next_value)) = streams[idx].next(files) {
+            heap.push(Reverse((next_key, rank, next_value)));
+        }
 
-        self.filters[level as usize] = None;
-        self.filters[level as usize + 1] = Some(new_filter);
+        // older versions of a key already written
+        if last_key == Some(key) {
+            continue;
+        }
+        last_key = Some(key);
+        if value == i64::MIN {
+            if drop_tombstones {
+                counts.tombstones_dropped += 1;
+                continue;
+            }
+            counts.tombstones += 1;
+        }
+        counts.written += 1;
+        output_buffer.push((key, value));
+        filter.insert_key(key);
+
+        // append to output file when buffer is full
+        if output_buffer.len() == 256 {
+            flush_output_buffer(output, files, &mut output_buffer, limiter);
+        }
     }
+
+    if !output_buffer.is_empty() {
+        flush_output_buffer(output, files, &mut output_buffer, limiter);
+    }
+    counts
 }
 
-fn flush_output_buffer(file: &mut File, output_buffer: &mut Vec<(i64, i64)>) {
-    let mut bytes: Vec<u8> = Vec::new();
+// Copies the KV pairs of a sorted run below the pivot into the output file, tombstones included. Only the current
+// page of the run and one output page are held in memory, like `merge_runs`. Returns what was read and written.
+fn copy_run_below(
+    input: &str,
+    output: &str,
+    pivot: i64,
+    files: &mut dyn PageFiles,
+    filter: &mut Bitmap,
+    limiter: &mut RateLimiter,
+) -> MergeCounts {
+    files.create(output);
+    let mut stream: RunStream = RunStream::new(input.to_string(), files);
+
+    let mut output_buffer = Vec::with_capacity(256);
+    let mut counts: MergeCounts = MergeCounts::default();
+    while let Some((key, value)) = stream.next(files) {
+        // the rest of the run is from the pivot up
+        if key >= pivot {
+            break;
+        }
+        counts.read += 1;
+        counts.written += 1;
+        if value == i64::MIN {
+            counts.tombstones += 1;
+        }
+        output_buffer.push((key, value));
+        filter.insert_key(key);
 
-    for (key, value) in &mut *output_buffer {
-        bytes.extend_from_slice(&key.to_be_bytes());
-        bytes.extend_from_slice(&value.to_be_bytes());
+        if output_buffer.len() == 256 {
+            flush_output_buffer(output, files, &mut output_buffer, limiter);
+        }
     }
 
-    pad_page_bytes(&mut bytes);
+    if !output_buffer.is_empty() {
+        flush_output_buffer(output, files, &mut output_buffer, limiter);
+    }
+    counts
+}
 
-    file.write_all(&bytes).unwrap();
+fn flush_output_buffer(
+    output: &str,
+    files: &mut dyn PageFiles,
+    output_buffer: &mut Vec<(i64, i64)>,
+    limiter: &mut RateLimiter,
+) {
+    files.append_pages(output, output_buffer);
     output_buffer.clear();
+    limiter.throttle(PAGE_SIZE);
 }
 
 impl DiskStorage for LSMTree {
     fn get(&mut self, key: i64) -> Option<i64> {
+        self.get_with_source(key).map(|(value, _)| value)
+    }
+
+    fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
         if self.tree_size == 0 {
             return None;
         }
         for i in 1..=self.tree_size.ilog2() + 1 {
-            if self.tree_size & (1 << (i - 1)) == 0
-                || !self.filters[0].as_ref().map_or(true, |a| a.check_key(key))
-            {
+            if self.tree_size & (1 << (i - 1)) == 0 {
                 continue;
             }
-            let leaf_filename = format!(
-                "{}/output_leaf_{}_{}.bin",
-                self.name,
-                i,
-                (self.tree_size / 2_u32.pow(i - 1)) * 2_u32.pow(i - 1)
-            );
-            let internal_filename = format!(
-                "{}/output_internal_{}_{}.bin",
-                self.name,
-                i,
-                (self.tree_size / 2_u32.pow(i - 1)) * 2_u32.pow(i - 1)
-            );
+            // runs without a filter (written before a reopen) are always searched
+            let filtered: bool = match self.filter(i) {
+                Some(filter) if !filter.check_key(key) => {
+                    self.filter_stats.rejections += 1;
+                    continue;
+                }
+                Some(_) => {
+                    self.filter_stats.passes += 1;
+                    true
+                }
+                None => false,
+            };
+            let leaf_filename = self.leaf_path(i, self.run_index(i));
+            let internal_filename = self.internal_path(i, self.run_index(i));
             if let Some(a) = part3btree::part3_search_b_tree_sst(
                 &leaf_filename,
                 &internal_filename,
                 key,
                 &mut self.pool,
             ) {
-                return Some(a);
+                let source = ValueSource::Sst {
+                    level: i,
+                    run: self.run_index(i),
+                };
+                return Some((a, source));
+            }
+            if filtered {
+                self.filter_stats.false_positives += 1;
             }
         }
         None
     }
 
-    fn scan(&mut self, start: i64, end: i64, hash: &mut HashMap<i64, i64>) {
-        if self.tree_size == 0 {
-            return;
+    fn leaf_page_for_key(&mut self, key: i64) -> Vec<(i64, i64)> {
+        let runs: Vec<(String, String)> = self
+            .levels()
+            .into_iter()
+            .map(|i| {
+                (
+                    self.leaf_path(i, self.run_index(i)),
+                    self.internal_path(i, self.run_index(i)),
+                )
+            })
+            .collect();
+        let pool: &mut BufferPool = &mut self.pool;
+        let pages = runs
+            .iter()
+            .map(|(leaf, internal)| part3btree::part3_find_leaf_page(leaf, internal, key, pool));
+        btree::pick_leaf_page(pages, key)
+    }
+
+    fn filter_stats(&self) -> FilterStats {
+        self.filter_stats
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.pool.shrink_to_fit();
+    }
+
+    fn cached_pages(&self) -> Vec<(String, usize)> {
+        self.pool.cached_keys()
+    }
+
+    fn iter_level(&mut self, level: u32) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        if level == 0 || level > 32 || self.tree_size & (1 << (level - 1)) == 0 {
+            return output;
         }
-        for i in 1..=self.tree_size.ilog2() + 1 {
-            if self.tree_size & (1 << (i - 1)) == 0 {
-                continue;
-            }
-            let leaf_filename = format!(
-                "{}/output_leaf_{}_{}.bin",
-                self.name,
-                i,
-                (self.tree_size / 2_u32.pow(i - 1)) * 2_u32.pow(i - 1)
-            );
-            let internal_filename = format!(
-                "{}/output_internal_{}_{}.bin",
-                self.name,
-                i,
-                (self.tree_size / 2_u32.pow(i - 1)) * 2_u32.pow(i - 1)
-            );
-            part3btree::part3_scan_b_tree_sst(
-                &leaf_filename,
-                &internal_filename,
-                start,
-                end,
-                hash,
-                &mut self.pool,
+        let leaf_filename = self.leaf_path(level, self.run_index(level));
+        let internal_filename = self.internal_path(level, self.run_index(level));
+        part3btree::part3_scan_b_tree_sst(
+            &leaf_filename,
+            &internal_filename,
+            i64::MIN,
+            i64::MAX,
+            &mut output,
+            &mut self.pool,
+        );
+        output
+    }
+
+    fn rebuild_filters(&mut self) {
+        for level in self.levels() {
+            let run: Vec<(i64, i64)> = self.iter_level(level);
+            let mut filter = Bitmap::with_seeds(
+                run.len().max(1) as u64 * self.bloom_bits_per_key,
+                self.bloom_seeds,
             );
+            for pair in &run {
+                filter.insert_key(pair.0);
+            }
+            self.set_filter(level, Some(filter));
+            // the counts go with the filters, a run without one has none either
+            let tombstones: usize = run.iter().filter(|a| a.1 == i64::MIN).count();
+            self.set_run_counts(level, (run.len() as u64, tombstones as u64));
         }
     }
 
+    fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+        self.scan_levels(start, end, hash);
+    }
+
+    fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        self.scan_levels(start, end, &mut output);
+        output
+    }
+
     fn flush(&mut self, _: u32, contents: Vec<(i64, i64)>) {
         if contents.is_empty() {
             return;
         }
         self.tree_size += 1;
-        let leaf_file_path = format!("{}/output_leaf_{}_{}.bin", self.name, 1, self.tree_size);
-        let internal_file_path =
-            format!("{}/output_internal_{}_{}.bin", self.name, 1, self.tree_size);
-        serialize_kv_to_file(&leaf_file_path, &contents);
+        self.write_run(1, &contents);
 
-        part3btree::part3_create_b_tree_internal_file(&leaf_file_path, &internal_file_path);
+        // merge ssts if necessary
+        self.merge_levels_from(1);
+    }
 
-        // create filter
-        let mut b = Bitmap::new(10 * contents.len() as u64);
-        for i in contents {
-            b.insert_key(i.0);
+    fn compact(&mut self, sst_count: u32) -> u32 {
+        let ratio: f32 = match self.tombstone_ratio {
+            Some(ratio) if self.tree_size > 0 => ratio,
+            _ => return sst_count,
+        };
+        let (entries, tombstones) = self
+            .levels()
+            .into_iter()
+            .map(|level| self.run_counts[level as usize])
+            .fold((0, 0), |total, counts| {
+                (total.0 + counts.0, total.1 + counts.1)
+            });
+        if tombstones > 0 && tombstones as f64 > ratio as f64 * entries as f64 {
+            self.merge_all_levels();
         }
-        self.filters[1] = Some(b);
+        sst_count
+    }
 
-        // merge ssts if necessary
-        let mut level = 1;
-        while self.tree_size & (1 << (level - 1)) == 0 {
-            self.merge_ssts(level);
-            level += 1;
+    fn ingest_sorted_run(&mut self, sorted: Vec<(i64, i64)>, level: u32) -> Result<(), KvError> {
+        // a run at `level` stands for 2^(level - 1) flushes, the merges write up to level 32
+        if !(1..32).contains(&level) {
+            return Err(KvError::InvalidArgument(format!(
+                "level must be between 1 and 31, got {}",
+                level
+            )));
+        }
+        if sorted.is_empty() || sorted.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+            return Err(KvError::InvalidArgument(
+                "run must be non-empty and strictly sorted by key".to_string(),
+            ));
+        }
+        // the run becomes the newest one, so all the levels below it must be empty
+        let run_size: u32 = 1 << (level - 1);
+        if !self.tree_size.is_multiple_of(run_size) {
+            return Err(KvError::InvalidArgument(format!(
+                "levels below {} must be empty",
+                level
+            )));
+        }
+        self.tree_size = self.tree_size.checked_add(run_size).ok_or_else(|| {
+            KvError::InvalidArgument(format!("no room left for a run at level {}", level))
+        })?;
+
+        self.write_run(level, &sorted);
+        self.merge_levels_from(level);
+        Ok(())
+    }
+
+    fn truncate_from(&mut self, pivot: i64) -> Result<u32, KvError> {
+        let mut emptied: bool = false;
+        for level in self.levels() {
+            let leaf_file_path = self.leaf_path(level, self.run_index(level));
+            let internal_file_path = self.internal_path(level, self.run_index(level));
+            let mut new_filter = Bitmap::with_seeds(
+                2_u64.pow(level - 1) * self.memtable_size as u64 * self.bloom_bits_per_key,
+                self.bloom_seeds,
+            );
+            let mut limiter: RateLimiter = RateLimiter::new(self.compaction_bytes_per_sec);
+
+            let leaf_temp: String = temp_file_path(&leaf_file_path);
+            let mut files: DiskPages = DiskPages::new(Some(&mut self.pool));
+            // the run is sorted, so only its last key tells if it holds any from the pivot up
+            let last_page: usize = files.total_pages(&leaf_file_path) - 1;
+            if files
+                .read_page(&leaf_file_path, last_page)
+                .last()
+                .is_none_or(|a| a.0 < pivot)
+            {
+                continue;
+            }
+            let mut counts: MergeCounts = copy_run_below(
+                &leaf_file_path,
+                &leaf_temp,
+                pivot,
+                &mut files,
+                &mut new_filter,
+                &mut limiter,
+            );
+            if counts.written == 0 {
+                // a level cannot hold an empty run, the tombstone shadows nothing and is dropped by the merge below
+                files.append_pages(&leaf_temp, &[(pivot, i64::MIN)]);
+                new_filter.insert_key(pivot);
+                counts.written = 1;
+                counts.tombstones = 1;
+                emptied = true;
+            }
+            drop(files);
+
+            publish_file(&leaf_file_path);
+            part3btree::part3_create_b_tree_internal_file(
+                &leaf_file_path,
+                &temp_file_path(&internal_file_path),
+                &mut DiskPages::new(None),
+            );
+            publish_file(&internal_file_path);
+            self.pool.invalidate_sst(&leaf_file_path);
+            self.pool.invalidate_sst(&internal_file_path);
+            self.set_filter(level, Some(new_filter));
+            self.set_run_counts(level, counts.run_counts());
+        }
+
+        if emptied {
+            self.merge_all_levels();
+        }
+        Ok(self.tree_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    mod merge_runs {
+        use crate::{
+            buffer::BufferPool,
+            filter::{Bitmap, BloomFilter},
+            serde::{deserialize_page, serialize_kv_to_file, PAGE_SIZE},
+            storage::{
+                lsm::{merge_runs, MergeCounts, RateLimiter},
+                part3btree::part3_create_b_tree_internal_file,
+                DiskPages, MemoryPages, PageFiles,
+            },
+        };
+        use std::{
+            collections::BTreeMap,
+            fs::{create_dir_all, metadata, remove_dir_all},
+            time::{Duration, Instant},
+        };
+
+        #[test]
+        fn test_merge_overlapping_runs() {
+            let folder_path: &str = "mergeRunsTestDB1";
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+
+            // run r holds every key that is a multiple of r + 1 in 0..2000, with the run as value
+            let mut expected: BTreeMap<i64, i64> = BTreeMap::new();
+            let mut inputs: Vec<String> = Vec::new();
+            for r in 0..8 {
+                let run: Vec<(i64, i64)> =
+                    (0..2000).step_by(r + 1).map(|k| (k, r as i64)).collect();
+                for &(key, value) in &run {
+                    expected.insert(key, value);
+                }
+                let file_path: String = format!("{}/run_{}.bin", folder_path, r);
+                serialize_kv_to_file(&file_path, &run);
+                inputs.push(file_path);
+            }
+
+            let output_path: String = format!("{}/merged.bin", folder_path);
+            let mut filter: Bitmap = Bitmap::new(2000 * 10);
+            // a single page buffer pool, the merge only needs one page per run at a time
+            let mut pool: BufferPool = BufferPool::new(1);
+            merge_runs(
+                &inputs,
+                &output_path,
+                &mut DiskPages::new(Some(&mut pool)),
+                &mut filter,
+                &mut RateLimiter::new(0),
+                false,
+            );
+
+            let total_pages: usize = metadata(&output_path).unwrap().len() as usize / PAGE_SIZE;
+            assert_eq!(2000_usize.div_ceil(256), total_pages);
+            let merged: Vec<(i64, i64)> = (0..total_pages)
+                .flat_map(|i| deserialize_page(&output_path, i * PAGE_SIZE))
+                .collect();
+            assert_eq!(expected.into_iter().collect::<Vec<(i64, i64)>>(), merged);
+            assert!((0..2000).all(|key| filter.check_key(key)));
+
+            remove_dir_all(folder_path).expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_flush_and_merge_in_memory() {
+            let mut files: MemoryPages = MemoryPages::default();
+
+            // two flushed runs of 3 pages, the newer one overwrites the odd keys and deletes every tenth key
+            let older: Vec<(i64, i64)> = (0..768).map(|k| (k, k)).collect();
+            let newer: Vec<(i64, i64)> = (0..768)
+                .filter(|k| k % 2 == 1 || k % 10 == 0)
+                .map(|k| (k, if k % 10 == 0 { i64::MIN } else { -k }))
+                .collect();
+            for (path, run) in [("leaf_1_0", &older), ("leaf_1_1", &newer)] {
+                files.append_pages(path, run);
+                part3_create_b_tree_internal_file(
+                    path,
+                    &path.replace("leaf", "internal"),
+                    &mut files,
+                );
+            }
+            assert_eq!(3, files.total_pages("leaf_1_0"));
+            // the internal page of a run points at its leaf pages
+            assert_eq!(
+                vec![(256, 1), (256, 2), (512, 3)],
+                files.read_page("internal_1_0", 0)
+            );
+
+            let mut filter: Bitmap = Bitmap::new(768 * 10);
+            let counts: MergeCounts = merge_runs(
+                &["leaf_1_0".to_string(), "leaf_1_1".to_string()],
+                "leaf_2_0",
+                &mut files,
+                &mut filter,
+                &mut RateLimiter::new(0),
+                false,
+            );
+            part3_create_b_tree_internal_file("leaf_2_0", "internal_2_0", &mut files);
+            assert_eq!((768, 77), counts.run_counts());
+            assert_eq!(768 + newer.len() as u64, counts.read);
+            assert_eq!(0, counts.tombstones_dropped);
+
+            let expected: Vec<(i64, i64)> = (0..768)
+                .map(|k| match (k % 10, k % 2) {
+                    (0, _) => (k, i64::MIN),
+                    (_, 1) => (k, -k),
+                    _ => (k, k),
+                })
+                .collect();
+            let merged: Vec<(i64, i64)> = (0..files.total_pages("leaf_2_0"))
+                .flat_map(|i| files.read_page("leaf_2_0", i))
+                .collect();
+            assert_eq!(expected, merged);
+            assert_eq!(
+                files.read_page("internal_1_0", 0),
+                files.read_page("internal_2_0", 0)
+            );
+            assert!((0..768).all(|key| filter.check_key(key)));
+        }
+
+        #[test]
+        fn test_merge_respects_rate_limit() {
+            let folder_path: &str = "mergeRunsTestDB2";
+            create_dir_all(folder_path).expect("Create dir all has failed!");
+
+            let mut inputs: Vec<String> = Vec::new();
+            for r in 0..2 {
+                let run: Vec<(i64, i64)> = (0..256 * 8).map(|k| (k * 2 + r, 0)).collect();
+                let file_path: String = format!("{}/run_{}.bin", folder_path, r);
+                serialize_kv_to_file(&file_path, &run);
+                inputs.push(file_path);
+            }
+
+            let output_path: String = format!("{}/merged.bin", folder_path);
+            let mut filter: Bitmap = Bitmap::new(256 * 16 * 10);
+            let mut pool: BufferPool = BufferPool::new(4);
+
+            // 16 pages of output at 8 pages per second take at least 2 seconds
+            let start: Instant = Instant::now();
+            merge_runs(
+                &inputs,
+                &output_path,
+                &mut DiskPages::new(Some(&mut pool)),
+                &mut filter,
+                &mut RateLimiter::new(8 * PAGE_SIZE as u64),
+                false,
+            );
+            let elapsed: Duration = start.elapsed();
+
+            assert_eq!(16 * PAGE_SIZE as u64, metadata(&output_path).unwrap().len());
+            assert!(elapsed >= Duration::from_millis(1900), "{:?}", elapsed);
+            assert!(elapsed < Duration::from_secs(10), "{:?}", elapsed);
+
+            remove_dir_all(folder_path).expect("Remove dir all has failed!");
+        }
+    }
+
+    mod levels {
+        use crate::{
+            filter::Bitmap,
+            serde::SstNaming,
+            storage::{lsm::LSMTree, DiskStorage},
+        };
+        use std::fs::remove_dir_all;
+
+        #[test]
+        fn test_filters_grow_with_levels() {
+            let folder_path: &str = "lsmLevelsTestDB1";
+            let mut lsm: LSMTree = LSMTree::new(
+                folder_path.to_string(),
+                8,
+                4,
+                SstNaming::default(),
+                10,
+                0,
+                None,
+            );
+            assert!(lsm.filters.is_empty());
+            assert!(lsm.filter(40).is_none());
+
+            // the highest level a run can be ingested at
+            let run: Vec<(i64, i64)> = (0..16).map(|key| (key, -key)).collect();
+            lsm.ingest_sorted_run(run, 31).unwrap();
+            assert_eq!(32, lsm.filters.len());
+            assert!(lsm.filter(31).is_some());
+            assert_eq!(Some(-5), lsm.get(5));
+            assert_eq!(1, lsm.filter_stats().passes);
+
+            // and levels past any run count
+            lsm.set_filter(100, Some(Bitmap::new(8)));
+            assert_eq!(101, lsm.filters.len());
+            assert!(lsm.filter(99).is_none());
+            assert_eq!(Some(-15), lsm.get(15));
+
+            remove_dir_all(folder_path).expect("Remove dir all has failed!");
+        }
+
+        #[test]
+        fn test_rebuild_lost_filters() {
+            let folder_path: &str = "lsmLevelsTestDB2";
+            let mut lsm: LSMTree = LSMTree::new(
+                folder_path.to_string(),
+                8,
+                64,
+                SstNaming::default(),
+                10,
+                0,
+                None,
+            );
+            // three flushes of the even keys leave runs at levels 1 and 2
+            for flush in 0..3 {
+                let run: Vec<(i64, i64)> = (0..64).map(|i| (flush * 128 + i * 2, i)).collect();
+                lsm.flush(0, run);
+            }
+            assert_eq!(vec![1, 2], lsm.levels());
+
+            // without filters every get searches both runs
+            lsm.filters.clear();
+            for key in (1..384).step_by(2) {
+                assert_eq!(None, lsm.get(key));
+            }
+            assert_eq!(0, lsm.filter_stats().rejections);
+
+            lsm.rebuild_filters();
+            assert!(lsm.filter(1).is_some() && lsm.filter(2).is_some());
+            assert_eq!((128, 0), lsm.run_counts[2]);
+            for key in (1..384).step_by(2) {
+                assert_eq!(None, lsm.get(key));
+            }
+            // 10 bits per key rule out nearly all of the 2 runs of the 192 missing keys
+            assert!(
+                lsm.filter_stats().rejections > 300,
+                "{:?}",
+                lsm.filter_stats()
+            );
+            for key in (0..384).step_by(2) {
+                assert_eq!(Some((key % 128) / 2), lsm.get(key), "key {}", key);
+            }
+
+            remove_dir_all(folder_path).expect("Remove dir all has failed!");
         }
     }
 }
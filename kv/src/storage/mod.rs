@@ -3,48 +3,496 @@ mod lsm;
 mod part3btree;
 mod traits;
 
+#[cfg(test)]
+use std::collections::HashMap;
+use std::{
+    fs::{remove_file, rename, File, OpenOptions},
+    io::{Read, Write},
+};
+
+use itertools::Itertools;
+
 pub use lsm::LSMTree;
 
-use std::collections::HashMap;
-pub use traits::DiskStorage;
+pub use traits::{
+    CompactionCallback, CompactionEvent, DiskStorage, PageFiles, ScanOutput, ValueSource,
+};
 
 use crate::{
     buffer::BufferPool,
-    serde::{get_value_ssts, scan_ssts, serialize_kv_to_file},
+    files,
+    record::{encode, encode_delta_page, RECORD_SIZE},
+    serde::{
+        deserialize_page, get_indexed_sst_names, get_value_ssts, get_value_ssts_with_index,
+        next_sst_index, pad_page_bytes, publish_file, scan_file, scan_ssts, serialize_kv_to_file,
+        serialize_kv_to_file_delta, sst_total_pages, temp_file_path, try_deserialize_page,
+        write_pages_to_file, write_sst_footer, write_sst_footer_counts, SstNaming, PAGE_SIZE,
+        TEMP_EXTENSION,
+    },
+    KvError,
 };
 
 use self::btree::{
-    convert_sorted_arr_to_b_tree_arr_and_serialize, get_b_tree_ssts, scan_b_tree_ssts,
+    convert_sorted_arr_to_b_tree_arr_and_serialize, entries_per_leaf, get_b_tree_ssts,
+    get_b_tree_ssts_with_index, iter_b_tree_sst, leaf_page_b_tree_ssts, read_b_tree_sst,
+    scan_b_tree_sst_range, scan_b_tree_ssts, warm_b_tree_ssts, write_b_tree_internal_pages,
+    DenseKeys,
 };
 
+/// Helper function to merge sorted runs given newest first into one sorted run, newest wins on equal keys. The
+/// deletes are dropped, so the runs must include the oldest SST of the DB. The merge is lazy, it only holds the next
+/// KV pair of each run.
+/// # Arguments
+/// * `runs` - The KV pairs of each run in ascending key order, newest run first.
+fn merge_sorted_runs<I: IntoIterator<Item = (i64, i64)>>(
+    runs: Vec<I>,
+) -> impl Iterator<Item = (i64, i64)> {
+    // tagged as (key, rank, value), the newest run has rank 0 so it comes first on equal keys
+    runs.into_iter()
+        .enumerate()
+        .map(|(rank, run)| run.into_iter().map(move |(key, value)| (key, rank, value)))
+        .kmerge()
+        .dedup_by(|a, b| a.0 == b.0)
+        .filter(|a| a.2 != i64::MIN)
+        .map(|(key, _, value)| (key, value))
+}
+
+/// Helper function to keep at most `max_ssts` SSTs in the DB `db_name` by merging its oldest SSTs into one, written
+/// over the oldest, then renumbering the newer SSTs down after it so the indices stay contiguous. Returns the number
+/// of SSTs left and the paths of every SST rewritten, removed or renamed, for the caller to drop from its caches.
+/// # Arguments
+/// * `db_name` - The name of the DB to merge.
+/// * `naming` - The naming convention of the SSTs.
+/// * `max_ssts` - The largest number of SSTs to keep, at least 1.
+/// * `read_sst` - Reads all the KV pairs of an SST in ascending key order.
+/// * `write_sst` - Writes the KV pairs of a merged SST to a path.
+fn merge_oldest_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    max_ssts: u32,
+    mut read_sst: impl FnMut(&str) -> Vec<(i64, i64)>,
+    write_sst: impl Fn(&str, &[(i64, i64)]),
+) -> (u32, Vec<String>) {
+    // newest first, the newest max_ssts - 1 SSTs are kept as they are
+    let mut sst_names: Vec<(u32, String)> = get_indexed_sst_names(db_name, naming);
+    if sst_names.len() <= max_ssts as usize {
+        return (sst_names.len() as u32, Vec::new());
+    }
+    let merged: Vec<(u32, String)> = sst_names.split_off(max_ssts as usize - 1);
+    let contents: Vec<(i64, i64)> =
+        merge_sorted_runs(merged.iter().map(|(_, name)| read_sst(name)).collect()).collect();
+
+    // the oldest SST is replaced by the merge, then the others are removed oldest first so a crash part way leaves
+    // the newest SSTs in place to shadow it
+    let (oldest_index, oldest) = merged.last().expect("Storage: no SST to merge!");
+    let mut touched: Vec<String> = vec![oldest.clone()];
+    let mut next_index: u32 = *oldest_index;
+    if contents.is_empty() {
+        remove_file(oldest).expect("Storage: remove merged SST failed!");
+    } else {
+        write_sst(&temp_file_path(oldest), &contents);
+        publish_file(oldest);
+        next_index += 1;
+    }
+    for (_, name) in merged.iter().rev().skip(1) {
+        remove_file(name).expect("Storage: remove merged SST failed!");
+        files::forget_file(name);
+        touched.push(name.clone());
+    }
+
+    // the kept SSTs are renamed oldest first, always to a lower index than their own, so their order is kept
+    for (_, name) in sst_names.iter().rev() {
+        let new_name: String = format!("./{}/{}", db_name, naming.sst_file_name(next_index));
+        rename(name, &new_name).expect("Storage: rename SST failed!");
+        files::forget_file(name);
+        files::forget_file(&new_name);
+        touched.push(name.clone());
+        touched.push(new_name);
+        next_index += 1;
+    }
+    (next_index, touched)
+}
+
+/// Enum of the page layouts of the SSTs written by an `SstWriter`.
+#[derive(Clone, Copy)]
+enum SstLayout {
+    /// Fixed size records, see `serialize_kv_to_file`.
+    Fixed,
+    /// Delta encoded pages, see `serialize_kv_to_file_delta`.
+    Delta,
+    /// The internal pages then the leaf pages of a `BTree` SST, each leaf page holding `leaf_entries` KV pairs.
+    BTree { leaf_entries: usize },
+}
+
+/// The number of pages copied at a time from the leaf pages of a `BTree` SST to the SST.
+const COPY_PAGES: usize = 64;
+
+/// Struct of an SST written from its KV pairs added in ascending key order, a few pages at a time, so they are never
+/// all held in memory. The SST is written at the `temp_file_path` of its path and only published by `finish`, with
+/// the same pages and footer as if all its KV pairs were written at once.
+struct SstWriter {
+    /// The path of the SST once published.
+    file_path: String,
+    /// The path the pages are written at, the leaf pages alone for a `BTree` SST since its internal pages come first.
+    pages_path: String,
+    layout: SstLayout,
+    /// Whether the SST ends with an `SstFooter`.
+    footers: bool,
+    /// The KV pairs added and not written yet.
+    pending: Vec<(i64, i64)>,
+    /// The first key of each leaf page written but the first one, for the internal pages of a `BTree` SST.
+    candidates: Vec<i64>,
+    /// The number of leaf pages written.
+    leaves: usize,
+    /// The number of KV pairs added.
+    entries: u64,
+    /// The smallest and largest keys added, `None` if none was.
+    key_range: Option<(i64, i64)>,
+}
+
+// Implementation of the `SstWriter`.
+impl SstWriter {
+    /// Creating a new `SstWriter` of an SST to publish at `file_path`, with the `layout` pages and a footer if
+    /// `footers` is set.
+    /// # Arguments
+    /// * `file_path` - The path of the SST once published.
+    /// * `layout` - The page layout of the SST.
+    /// * `footers` - Whether the SST ends with an `SstFooter`.
+    fn new(file_path: &str, layout: SstLayout, footers: bool) -> Self {
+        let temp_path: String = temp_file_path(file_path);
+        let pages_path: String = match layout {
+            SstLayout::BTree { .. } => temp_file_path(&format!("{}.leaves", file_path)),
+            _ => temp_path,
+        };
+        Self {
+            file_path: file_path.to_string(),
+            pages_path,
+            layout,
+            footers,
+            pending: Vec::new(),
+            candidates: Vec::new(),
+            leaves: 0,
+            entries: 0,
+            key_range: None,
+        }
+    }
+
+    /// Helper function to write the pending KV pairs that fill whole pages, or all of them if `last` is set.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `SstWriter`.
+    /// * `last` - Whether no more KV pairs will be added.
+    fn write_pending(&mut self, last: bool) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let written: usize = match self.layout {
+            SstLayout::Fixed => {
+                let written: usize = if last {
+                    self.pending.len()
+                } else {
+                    self.pending.len() - self.pending.len() % (PAGE_SIZE / RECORD_SIZE)
+                };
+                serialize_kv_to_file(&self.pages_path, &self.pending[..written]);
+                written
+            }
+            SstLayout::Delta => {
+                // a delta page holds fewer than PAGE_SIZE KV pairs, so a page encoded from more is full and the
+                // same as the one encoded from all of the KV pairs of the SST
+                let mut bytes: Vec<u8> = Vec::new();
+                let mut written: usize = 0;
+                while written < self.pending.len()
+                    && (last || self.pending.len() - written > PAGE_SIZE)
+                {
+                    let (page, count) = encode_delta_page(&self.pending[written..]);
+                    bytes.extend_from_slice(&page);
+                    written += count;
+                }
+                write_pages_to_file(&self.pages_path, &bytes);
+                written
+            }
+            SstLayout::BTree { leaf_entries } => {
+                let written: usize = if last {
+                    self.pending.len()
+                } else {
+                    self.pending.len() - self.pending.len() % leaf_entries
+                };
+                // each leaf page is padded after its entries
+                for leaf in self.pending[..written].chunks(leaf_entries) {
+                    if self.leaves > 0 {
+                        self.candidates.push(leaf[0].0);
+                    }
+                    serialize_kv_to_file(&self.pages_path, leaf);
+                    self.leaves += 1;
+                }
+                written
+            }
+        };
+        self.pending.drain(..written);
+    }
+
+    /// Write the rest of the SST and publish it. Returns `false`, writing nothing, if no KV pair was added.
+    /// # Arguments
+    /// * `self` - The `SstWriter` to finish.
+    fn finish(mut self) -> bool {
+        let Some((min_key, max_key)) = self.key_range else {
+            return false;
+        };
+        self.write_pending(true);
+
+        let temp_path: String = format!("{}{}", self.file_path, TEMP_EXTENSION);
+        if let SstLayout::BTree { .. } = self.layout {
+            write_b_tree_internal_pages(&temp_path, std::mem::take(&mut self.candidates));
+            let mut leaves: File =
+                File::open(&self.pages_path).expect("SstWriter: open leaf pages failed!");
+            let mut chunk: Vec<u8> = Vec::with_capacity(COPY_PAGES * PAGE_SIZE);
+            loop {
+                chunk.clear();
+                (&mut leaves)
+                    .take((COPY_PAGES * PAGE_SIZE) as u64)
+                    .read_to_end(&mut chunk)
+                    .expect("SstWriter: read leaf pages failed!");
+                if chunk.is_empty() {
+                    break;
+                }
+                write_pages_to_file(&temp_path, &chunk);
+            }
+            files::forget_file(&self.pages_path);
+            remove_file(&self.pages_path).expect("SstWriter: remove leaf pages failed!");
+        }
+        if self.footers {
+            write_sst_footer_counts(&temp_path, self.entries, min_key, max_key);
+        }
+        publish_file(&self.file_path);
+        true
+    }
+}
+
+// The implementation of the `SstWriter` as a `ScanOutput`, so a scan can stream into it. Keys must come in ascending
+// order, as they do from the scan of a single SST.
+impl ScanOutput for SstWriter {
+    fn add(&mut self, key: i64, value: i64) {
+        let min_key: i64 = self.key_range.map_or(key, |(min_key, _)| min_key);
+        self.key_range = Some((min_key, key));
+        self.entries += 1;
+        self.pending.push((key, value));
+        if self.pending.len() >= 2 * PAGE_SIZE {
+            self.write_pending(false);
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.entries as usize
+    }
+}
+
+/// Helper function to drop every KV pair from a pivot up from the SSTs of the DB `db_name`, oldest SST first. Each
+/// SST holding some is streamed by `scan_below` into an `SstWriter` and published over itself. The SSTs left without
+/// KV pairs are removed only once all the others are rewritten, then the SSTs after them are renumbered down like in
+/// `merge_oldest_ssts`. Returns the number of SSTs left and the paths of every SST rewritten, removed or renamed, for
+/// the caller to drop from its caches.
+/// # Arguments
+/// * `db_name` - The name of the DB to truncate.
+/// * `naming` - The naming convention of the SSTs.
+/// * `layout` - The page layout of the SSTs.
+/// * `footers` - Whether the SSTs end with an `SstFooter`.
+/// * `scan_below` - Adds the KV pairs below the pivot of an SST to the writer, in ascending key order. Returns
+///   `false`, adding nothing, if the SST holds no key from the pivot up and is kept as it is.
+fn truncate_ssts(
+    db_name: &str,
+    naming: &SstNaming,
+    layout: SstLayout,
+    footers: bool,
+    mut scan_below: impl FnMut(&str, &mut SstWriter) -> Result<bool, KvError>,
+) -> Result<(u32, Vec<String>), KvError> {
+    let io_error = |file: &str, error: std::io::Error| KvError::Io {
+        file: file.to_string(),
+        kind: error.kind(),
+    };
+
+    let mut touched: Vec<String> = Vec::new();
+    let mut kept: Vec<(u32, String)> = Vec::new();
+    let mut emptied: Vec<String> = Vec::new();
+    for (index, name) in get_indexed_sst_names(db_name, naming).into_iter().rev() {
+        let mut writer: SstWriter = SstWriter::new(&name, layout, footers);
+        if !scan_below(&name, &mut writer)? {
+            kept.push((index, name));
+            continue;
+        }
+        touched.push(name.clone());
+        if writer.finish() {
+            kept.push((index, name));
+        } else {
+            emptied.push(name);
+        }
+    }
+
+    // a crash before this point leaves every SST in place, only some of them without their KV pairs from the pivot up
+    for name in emptied {
+        remove_file(&name).map_err(|error| io_error(&name, error))?;
+        files::forget_file(&name);
+    }
+
+    // the kept SSTs are renamed oldest first, always to a lower index than their own, so their order is kept
+    for (new_index, (index, name)) in kept.iter().enumerate() {
+        if new_index as u32 == *index {
+            continue;
+        }
+        let new_name: String = format!("./{}/{}", db_name, naming.sst_file_name(new_index as u32));
+        rename(name, &new_name).map_err(|error| io_error(name, error))?;
+        files::forget_file(name);
+        files::forget_file(&new_name);
+        touched.push(name.clone());
+        touched.push(new_name);
+    }
+    Ok((kept.len() as u32, touched))
+}
+
 /// Struct of the `AppendOnlyLog` storage type.
 pub struct AppendOnlyLog {
     name: String,
+    naming: SstNaming,
+    delta_pages: bool,
+    /// The largest number of SSTs kept, the oldest ones are merged past it. `None` to never merge.
+    max_ssts: Option<u32>,
+    /// Whether the SSTs end with an `SstFooter`.
+    footers: bool,
 }
 
 // Implementation of the `AppendOnlyLog` storage type.
 impl AppendOnlyLog {
-    /// Creating a new `AppendOnlyLog` given the `name`.
+    /// Creating a new `AppendOnlyLog` given the `name`, the `naming` of its SSTs, whether they are delta encoded, and
+    /// the `max_ssts` it keeps.
     /// # Arguments
     /// * `name` - The name of the newly created `AppendOnlyLog`.
-    pub fn new(name: String) -> Self {
-        Self { name }
+    /// * `naming` - The naming convention of the SSTs.
+    /// * `delta_pages` - Whether the SSTs are written as delta encoded pages instead of fixed size records.
+    /// * `max_ssts` - The largest number of SSTs kept, `None` to never merge.
+    pub fn new(name: String, naming: SstNaming, delta_pages: bool, max_ssts: Option<u32>) -> Self {
+        Self {
+            name,
+            naming,
+            delta_pages,
+            max_ssts,
+            footers: false,
+        }
+    }
+
+    /// Setting whether the SSTs end with an `SstFooter`, see `write_sst_footer`.
+    /// # Arguments
+    /// * `footers` - `true` to write the footers, `false` to only write the pages.
+    pub fn with_footers(mut self, footers: bool) -> Self {
+        self.footers = footers;
+        self
+    }
+
+    /// Helper function to write the sorted `contents` to an SST at `file_path`, delta encoded if the log is, and its
+    /// footer if the log has them.
+    /// # Arguments
+    /// * `self` - A ref to the `AppendOnlyLog`.
+    /// * `file_path` - The path to write the SST at.
+    /// * `contents` - The KV pairs to write, in ascending key order.
+    fn write_sst(&self, file_path: &str, contents: &[(i64, i64)]) {
+        if self.delta_pages {
+            serialize_kv_to_file_delta(file_path, contents);
+        } else {
+            serialize_kv_to_file(file_path, contents);
+        }
+        if self.footers {
+            write_sst_footer(file_path, contents);
+        }
     }
 }
 
 // The implementation of the `AppendOnlyLog` as a `DiskStorage` type. Function docs in "traits.rs".
 impl DiskStorage for AppendOnlyLog {
     fn get(&mut self, key: i64) -> Option<i64> {
-        get_value_ssts(&self.name, key)
+        self.try_get(key).expect("AppendOnlyLog: get failed!")
+    }
+
+    fn try_get(&mut self, key: i64) -> Result<Option<i64>, KvError> {
+        get_value_ssts(&self.name, &self.naming, key)
+    }
+
+    fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
+        get_value_ssts_with_index(&self.name, &self.naming, key)
+            .expect("AppendOnlyLog: get failed!")
+            .map(|(value, run)| (value, ValueSource::Sst { level: 0, run }))
     }
 
-    fn scan(&mut self, start: i64, end: i64, hash: &mut HashMap<i64, i64>) {
-        scan_ssts(&self.name, start, end, hash);
+    fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+        self.try_scan(start, end, hash)
+            .expect("AppendOnlyLog: scan failed!");
+    }
+
+    fn try_scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) -> Result<(), KvError> {
+        scan_ssts(&self.name, &self.naming, start, end, hash)
+    }
+
+    fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        self.try_scan_sorted(start, end)
+            .expect("AppendOnlyLog: scan failed!")
+    }
+
+    fn try_scan_sorted(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        scan_ssts(&self.name, &self.naming, start, end, &mut output)?;
+        Ok(output)
     }
 
     fn flush(&mut self, sst_count: u32, contents: Vec<(i64, i64)>) {
-        let file_path = format!("{}/output_{}.bin", self.name, sst_count);
-        serialize_kv_to_file(&file_path, &contents)
+        let file_path = format!("{}/{}", self.name, self.naming.sst_file_name(sst_count));
+        self.write_sst(&temp_file_path(&file_path), &contents);
+        publish_file(&file_path);
+    }
+
+    fn compact(&mut self, sst_count: u32) -> u32 {
+        let max_ssts: u32 = match self.max_ssts {
+            Some(max_ssts) if sst_count > max_ssts => max_ssts,
+            _ => return sst_count,
+        };
+        let read_sst = |name: &str| {
+            let mut output: Vec<(i64, i64)> = Vec::new();
+            sst_total_pages(name)
+                .and_then(|total_pages| scan_file(name, total_pages, 0, 0, i64::MAX, &mut output))
+                .expect("AppendOnlyLog: read merged SST failed!");
+            output
+        };
+        let write_sst =
+            |file_path: &str, contents: &[(i64, i64)]| self.write_sst(file_path, contents);
+        merge_oldest_ssts(&self.name, &self.naming, max_ssts, read_sst, write_sst).0
+    }
+
+    fn evict_sst(&mut self, sst_index: u32) {
+        let file_path: String = format!("./{}/{}", self.name, self.naming.sst_file_name(sst_index));
+        files::forget_file(&file_path);
+    }
+
+    fn initial_sst_count(&self) -> u32 {
+        // one past the newest SST, counting the files would reuse an index if a lower one is missing
+        next_sst_index(&self.name, &self.naming)
+    }
+
+    fn truncate_from(&mut self, pivot: i64) -> Result<u32, KvError> {
+        let layout: SstLayout = if self.delta_pages {
+            SstLayout::Delta
+        } else {
+            SstLayout::Fixed
+        };
+        let scan_below = |name: &str, writer: &mut SstWriter| {
+            // the SST is sorted, so only its last key tells if it holds any from the pivot up
+            let total_pages: usize = sst_total_pages(name)?;
+            let last_page: Vec<(i64, i64)> =
+                try_deserialize_page(name, (total_pages - 1) * PAGE_SIZE)?;
+            if last_page.last().is_none_or(|a| a.0 < pivot) {
+                return Ok(false);
+            }
+            if let Some(end) = pivot.checked_sub(1) {
+                scan_file(name, total_pages, 0, 0, end, writer)?;
+            }
+            Ok(true)
+        };
+        truncate_ssts(&self.name, &self.naming, layout, self.footers, scan_below)
+            .map(|(count, _)| count)
     }
 }
 
@@ -52,34 +500,458 @@ impl DiskStorage for AppendOnlyLog {
 pub struct BTree {
     name: String,
     pool: BufferPool,
+    naming: SstNaming,
+    /// The number of SSTs above which they are all merged into one, 0 to never merge.
+    merge_threshold: u32,
+    /// The number of entries of each leaf page.
+    leaf_entries: usize,
+    /// The dense key hint, `None` to always descend the internal pages.
+    dense: Option<DenseKeys>,
+    /// The largest number of SSTs kept, the oldest ones are merged past it. `None` for no limit.
+    max_ssts: Option<u32>,
+    /// Whether the SSTs end with an `SstFooter`.
+    footers: bool,
 }
 
 // Implementation of the `BTree` storage type.
 impl BTree {
-    /// Creating a new `BTree` given the `name` and a `buffer_pool_size`.
+    /// Creating a new `BTree` given the `name`, a `buffer_pool_size`, the `naming` of its SSTs, the
+    /// `merge_threshold` above which its SSTs are merged, the `leaf_fill_factor` of its leaf pages, the
+    /// `dense_min_key` of its keys if they are dense and the `max_ssts` it keeps.
     /// # Arguments
     /// * `name` - The name of the newly created `BTree`.
     /// * `buffer_pool_size` - The size of the buffer pool.
-    pub fn new(name: String, buffer_pool_size: usize) -> Self {
+    /// * `naming` - The naming convention of the SSTs.
+    /// * `merge_threshold` - The number of SSTs above which they are merged, 0 to never merge.
+    /// * `leaf_fill_factor` - The fraction of each leaf page to fill, in (0, 1].
+    /// * `dense_min_key` - The smallest key of the dense keys, `None` if the keys are not known to be dense.
+    /// * `max_ssts` - The largest number of SSTs kept, `None` for no limit.
+    pub fn new(
+        name: String,
+        buffer_pool_size: usize,
+        naming: SstNaming,
+        merge_threshold: u32,
+        leaf_fill_factor: f32,
+        dense_min_key: Option<i64>,
+        max_ssts: Option<u32>,
+    ) -> Self {
+        let leaf_entries: usize = entries_per_leaf(leaf_fill_factor);
         Self {
             name,
             pool: BufferPool::new(buffer_pool_size),
+            naming,
+            merge_threshold,
+            leaf_entries,
+            dense: dense_min_key.map(|min_key| DenseKeys::new(min_key, leaf_entries)),
+            max_ssts,
+            footers: false,
+        }
+    }
+
+    /// Setting whether the SSTs end with an `SstFooter`, see `write_sst_footer`.
+    /// # Arguments
+    /// * `footers` - `true` to write the footers, `false` to only write the pages.
+    pub fn with_footers(mut self, footers: bool) -> Self {
+        self.footers = footers;
+        self
+    }
+
+    /// Helper function to write the sorted `contents` to a B tree SST at `file_path`, and its footer if the `BTree`
+    /// has them.
+    /// # Arguments
+    /// * `file_path` - The path to write the SST at.
+    /// * `contents` - The KV pairs to write, in ascending key order.
+    /// * `leaf_entries` - The number of entries of each leaf page.
+    /// * `footers` - Whether to write the footer.
+    fn write_sst(file_path: &str, contents: &[(i64, i64)], leaf_entries: usize, footers: bool) {
+        convert_sorted_arr_to_b_tree_arr_and_serialize(file_path, contents, leaf_entries);
+        if footers {
+            write_sst_footer(file_path, contents);
+        }
+    }
+
+    /// Setting whether the buffer pool filters the pages it admits, see `BufferPool::with_admission`.
+    /// # Arguments
+    /// * `admission` - `true` to filter the admitted pages, `false` to admit every page.
+    pub fn with_pool_admission(mut self, admission: bool) -> Self {
+        self.pool = self.pool.with_admission(admission);
+        self
+    }
+
+    /// Helper function to merge the oldest SSTs once there are more than `max_ssts`, see `merge_oldest_ssts`.
+    /// Returns the number of SSTs left.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `BTree` to merge.
+    /// * `sst_count` - The number of SSTs in the DB, the last flush included.
+    fn merge_oldest(&mut self, sst_count: u32) -> u32 {
+        let max_ssts: u32 = match self.max_ssts {
+            Some(max_ssts) if sst_count > max_ssts => max_ssts,
+            _ => return sst_count,
+        };
+        let pool: &mut BufferPool = &mut self.pool;
+        let (leaf_entries, footers) = (self.leaf_entries, self.footers);
+        let (count, touched) = merge_oldest_ssts(
+            &self.name,
+            &self.naming,
+            max_ssts,
+            |name: &str| read_b_tree_sst(name, pool),
+            |file_path: &str, contents: &[(i64, i64)]| {
+                Self::write_sst(file_path, contents, leaf_entries, footers)
+            },
+        );
+        for name in touched {
+            self.pool.invalidate_sst(&name);
+            if let Some(dense) = self.dense.as_mut() {
+                dense.invalidate_sst(&name);
+            }
         }
+        count
     }
 }
 
 // The implementation of the `BTree` as a `DiskStorage` type. Function docs in "traits.rs".
 impl DiskStorage for BTree {
     fn get(&mut self, key: i64) -> Option<i64> {
-        get_b_tree_ssts(&self.name, key, &mut self.pool)
+        get_b_tree_ssts(
+            &self.name,
+            &self.naming,
+            key,
+            &mut self.pool,
+            self.dense.as_mut(),
+        )
+    }
+
+    fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
+        get_b_tree_ssts_with_index(
+            &self.name,
+            &self.naming,
+            key,
+            &mut self.pool,
+            self.dense.as_mut(),
+        )
+        .map(|(value, run)| (value, ValueSource::Sst { level: 0, run }))
+    }
+
+    fn leaf_page_for_key(&mut self, key: i64) -> Vec<(i64, i64)> {
+        leaf_page_b_tree_ssts(&self.name, &self.naming, key, &mut self.pool)
     }
 
-    fn scan(&mut self, start: i64, end: i64, hash: &mut HashMap<i64, i64>) {
-        scan_b_tree_ssts(&self.name, start, end, hash, &mut self.pool);
+    fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+        scan_b_tree_ssts(
+            &self.name,
+            &self.naming,
+            start,
+            end,
+            hash,
+            &mut self.pool,
+            self.dense.as_mut(),
+        );
+    }
+
+    fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        scan_b_tree_ssts(
+            &self.name,
+            &self.naming,
+            start,
+            end,
+            &mut output,
+            &mut self.pool,
+            self.dense.as_mut(),
+        );
+        output
     }
 
     fn flush(&mut self, sst_count: u32, contents: Vec<(i64, i64)>) {
-        let file_path = format!("{}/output_{}.bin", self.name, sst_count);
-        convert_sorted_arr_to_b_tree_arr_and_serialize(&file_path, &contents)
+        let file_path = format!("{}/{}", self.name, self.naming.sst_file_name(sst_count));
+        Self::write_sst(
+            &temp_file_path(&file_path),
+            &contents,
+            self.leaf_entries,
+            self.footers,
+        );
+        publish_file(&file_path);
+    }
+
+    fn compact(&mut self, sst_count: u32) -> u32 {
+        if self.merge_threshold == 0 || sst_count <= self.merge_threshold {
+            return self.merge_oldest(sst_count);
+        }
+
+        // the merged SST is written over the oldest one, reading one leaf page of each SST at a time
+        let sst_names: Vec<(u32, String)> = get_indexed_sst_names(&self.name, &self.naming);
+        let ((oldest_index, oldest), newer) =
+            sst_names.split_last().expect("BTree: no SST to merge!");
+        let layout: SstLayout = SstLayout::BTree {
+            leaf_entries: self.leaf_entries,
+        };
+        let mut writer: SstWriter = SstWriter::new(oldest, layout, self.footers);
+        // SSTs are given newest first, so the merge keeps the newest value of each key
+        for (key, value) in merge_sorted_runs(
+            sst_names
+                .iter()
+                .map(|(_, name)| iter_b_tree_sst(name))
+                .collect(),
+        ) {
+            writer.add(key, value);
+        }
+        let merged: bool = writer.finish();
+
+        // the oldest SST is replaced by the merge, then the others are removed oldest first so a crash part way
+        // leaves the newest SSTs in place to shadow it
+        self.pool.invalidate_sst(oldest);
+        if let Some(dense) = self.dense.as_mut() {
+            dense.invalidate_sst(oldest);
+        }
+        if !merged {
+            remove_file(oldest).expect("BTree: remove merged SST failed!");
+        }
+        for (_, name) in newer.iter().rev() {
+            self.pool.invalidate_sst(name);
+            if let Some(dense) = self.dense.as_mut() {
+                dense.invalidate_sst(name);
+            }
+            remove_file(name).expect("BTree: remove merged SST failed!");
+        }
+
+        // the oldest SST is not always output_0, e.g. after a quarantine, so the next flush goes past its index
+        if merged {
+            oldest_index + 1
+        } else {
+            *oldest_index
+        }
+    }
+
+    fn warm_range(&mut self, start: i64, end: i64) -> usize {
+        warm_b_tree_ssts(&self.name, &self.naming, start, end, &mut self.pool)
+    }
+
+    fn evict_sst(&mut self, sst_index: u32) {
+        let file_path: String = format!("./{}/{}", self.name, self.naming.sst_file_name(sst_index));
+        self.pool.invalidate_sst(&file_path);
+        files::forget_file(&file_path);
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.pool.shrink_to_fit();
+    }
+
+    fn cached_pages(&self) -> Vec<(String, usize)> {
+        self.pool.cached_keys()
+    }
+
+    fn truncate_from(&mut self, pivot: i64) -> Result<u32, KvError> {
+        let pool: &mut BufferPool = &mut self.pool;
+        let layout: SstLayout = SstLayout::BTree {
+            leaf_entries: self.leaf_entries,
+        };
+        let scan_below = |name: &str, writer: &mut SstWriter| {
+            // the last page of a B tree SST is its last leaf, which holds its largest key
+            let total_pages: usize = sst_total_pages(name)?;
+            let last_leaf: Vec<(i64, i64)> = pool.find_page(name, (total_pages - 1) * PAGE_SIZE);
+            if last_leaf.last().is_none_or(|a| a.0 < pivot) {
+                return Ok(false);
+            }
+            if let Some(end) = pivot.checked_sub(1) {
+                scan_b_tree_sst_range(name, i64::MIN, end, writer, pool);
+            }
+            Ok(true)
+        };
+        let (count, touched) =
+            truncate_ssts(&self.name, &self.naming, layout, self.footers, scan_below)?;
+        for name in touched {
+            self.pool.invalidate_sst(&name);
+            if let Some(dense) = self.dense.as_mut() {
+                dense.invalidate_sst(&name);
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Struct of the `PageFiles` on disk. Pages are read through the buffer pool when there is one, and the file last
+/// appended to is kept open until the next one or the drop.
+pub struct DiskPages<'a> {
+    /// The buffer pool to read pages through, `None` to read them straight from the files.
+    pool: Option<&'a mut BufferPool>,
+    /// The path and handle of the file last appended to.
+    output: Option<(String, File)>,
+}
+
+// Implementation of the `DiskPages`.
+impl<'a> DiskPages<'a> {
+    /// Creating a new `DiskPages` reading through `pool`, if there is one.
+    /// # Arguments
+    /// * `pool` - The buffer pool to read pages through, `None` to read them straight from the files.
+    pub fn new(pool: Option<&'a mut BufferPool>) -> Self {
+        Self { pool, output: None }
+    }
+
+    /// Helper function to get the handle of the file at `path` to append to, opening it if needed.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskPages`.
+    /// * `path` - The path of the file.
+    fn output(&mut self, path: &str) -> &mut File {
+        if self.output.as_ref().is_none_or(|(open, _)| open != path) {
+            // a handle opened for reading before the file was replaced would see stale pages
+            files::forget_file(path);
+            let file: File = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .expect("DiskPages: open file failed!");
+            self.output = Some((path.to_string(), file));
+        }
+        &mut self.output.as_mut().unwrap().1
+    }
+}
+
+// The implementation of the `DiskPages` as `PageFiles`. Function docs in "traits.rs".
+impl PageFiles for DiskPages<'_> {
+    fn create(&mut self, path: &str) {
+        self.output(path);
+    }
+
+    fn total_pages(&mut self, path: &str) -> usize {
+        sst_total_pages(path).expect("DiskPages: SST not found!")
+    }
+
+    fn read_page(&mut self, path: &str, page_idx: usize) -> Vec<(i64, i64)> {
+        match self.pool.as_deref_mut() {
+            Some(pool) => pool.find_page(path, page_idx * PAGE_SIZE),
+            None => deserialize_page(path, page_idx * PAGE_SIZE),
+        }
+    }
+
+    fn append_pages(&mut self, path: &str, kv_arr: &[(i64, i64)]) {
+        let mut bytes: Vec<u8> = Vec::with_capacity(kv_arr.len() * RECORD_SIZE);
+        for &(key, value) in kv_arr {
+            bytes.extend_from_slice(&encode(key, value));
+        }
+        pad_page_bytes(&mut bytes);
+        self.output(path)
+            .write_all(&bytes)
+            .expect("DiskPages: file write failed!");
+    }
+}
+
+/// Struct of `PageFiles` kept in memory, to test the merges and B tree builds without a filesystem.
+#[cfg(test)]
+#[derive(Default)]
+pub struct MemoryPages {
+    /// The pages of each file by path.
+    files: HashMap<String, Vec<Vec<(i64, i64)>>>,
+}
+
+// The implementation of the `MemoryPages` as `PageFiles`. Function docs in "traits.rs".
+#[cfg(test)]
+impl PageFiles for MemoryPages {
+    fn create(&mut self, path: &str) {
+        self.files.entry(path.to_string()).or_default();
+    }
+
+    fn total_pages(&mut self, path: &str) -> usize {
+        self.files
+            .get(path)
+            .expect("MemoryPages: SST not found!")
+            .len()
+    }
+
+    fn read_page(&mut self, path: &str, page_idx: usize) -> Vec<(i64, i64)> {
+        self.files.get(path).expect("MemoryPages: SST not found!")[page_idx].clone()
+    }
+
+    fn append_pages(&mut self, path: &str, kv_arr: &[(i64, i64)]) {
+        let pages = self.files.entry(path.to_string()).or_default();
+        pages.extend(kv_arr.chunks(PAGE_SIZE / RECORD_SIZE).map(<[_]>::to_vec));
+    }
+}
+
+/// Struct of the `NullStorage` storage type. Discards everything that is flushed, so only the `Memtable`
+/// is exercised.
+pub struct NullStorage;
+
+// The implementation of the `NullStorage` as a `DiskStorage` type. Function docs in "traits.rs".
+impl DiskStorage for NullStorage {
+    fn get(&mut self, _key: i64) -> Option<i64> {
+        None
+    }
+
+    fn scan(&mut self, _start: i64, _end: i64, _hash: &mut dyn ScanOutput) {}
+
+    fn scan_sorted(&mut self, _start: i64, _end: i64) -> Vec<(i64, i64)> {
+        Vec::new()
+    }
+
+    fn flush(&mut self, _sst_count: u32, _contents: Vec<(i64, i64)>) {}
+
+    fn truncate_from(&mut self, _pivot: i64) -> Result<u32, KvError> {
+        Ok(0)
+    }
+}
+
+/// Struct of the `MemoryStorage` storage type. Keeps the flushed SSTs in memory instead of on disk, so the DB is gone
+/// once the `Client` is dropped.
+pub struct MemoryStorage {
+    /// The flushed SSTs, oldest first, each in ascending key order.
+    ssts: Vec<Vec<(i64, i64)>>,
+}
+
+// Implementation of the `MemoryStorage` storage type.
+impl MemoryStorage {
+    /// Creating a new, empty `MemoryStorage`.
+    pub fn new() -> Self {
+        Self { ssts: Vec::new() }
+    }
+}
+
+// The implementation of the `MemoryStorage` as a `DiskStorage` type. Function docs in "traits.rs".
+impl DiskStorage for MemoryStorage {
+    fn get(&mut self, key: i64) -> Option<i64> {
+        self.get_with_source(key).map(|(value, _)| value)
+    }
+
+    fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
+        self.ssts.iter().enumerate().rev().find_map(|(run, sst)| {
+            sst.binary_search_by_key(&key, |a| a.0).ok().map(|idx| {
+                let source = ValueSource::Sst {
+                    level: 0,
+                    run: run as u32,
+                };
+                (sst[idx].1, source)
+            })
+        })
+    }
+
+    fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) {
+        for sst in self.ssts.iter().rev() {
+            let first: usize = sst.partition_point(|a| a.0 < start);
+            for &(key, value) in sst[first..].iter().take_while(|a| a.0 <= end) {
+                hash.add(key, value);
+            }
+        }
+    }
+
+    fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)> {
+        let mut output: Vec<(i64, i64)> = Vec::new();
+        self.scan(start, end, &mut output);
+        output
+    }
+
+    fn flush(&mut self, _sst_count: u32, contents: Vec<(i64, i64)>) {
+        self.ssts.push(contents);
+    }
+
+    fn truncate_from(&mut self, pivot: i64) -> Result<u32, KvError> {
+        for sst in self.ssts.iter_mut() {
+            let end: usize = sst.partition_point(|a| a.0 < pivot);
+            sst.truncate(end);
+        }
+        self.ssts.retain(|sst| !sst.is_empty());
+        Ok(self.ssts.len() as u32)
+    }
+
+    fn shrink_to_fit(&mut self) {
+        self.ssts.shrink_to_fit();
     }
 }
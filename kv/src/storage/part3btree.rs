@@ -1,25 +1,28 @@
 #![allow(dead_code)]
 
-use crate::serde::{binary_search_array_start_index, deserialize_page, serialize_kv_to_file};
+use crate::serde::binary_search_array_start_index;
 use crate::storage::btree::{binary_search_internal_se_key, scan_b_tree_file};
-use crate::storage::BufferPool;
-use std::collections::HashMap;
+use crate::storage::{BufferPool, PageFiles, ScanOutput};
 use std::fs::metadata;
 
 const PAGE_SIZE: usize = 4096;
 const ENTRIES: usize = 256;
 
-pub fn part3_create_b_tree_internal_file(leaf_file_path: &str, internal_file_path: &str) {
-    let total_pages: usize = (metadata(leaf_file_path)
-        .expect("Metadata call failed!")
-        .len() as usize)
-        / PAGE_SIZE;
+pub fn part3_create_b_tree_internal_file(
+    leaf_file_path: &str,
+    internal_file_path: &str,
+    files: &mut dyn PageFiles,
+) {
+    // a single leaf page has no internal nodes, but the (empty) internal file must still exist
+    files.create(internal_file_path);
+
+    let total_pages: usize = files.total_pages(leaf_file_path);
     let mut num_ptrs = total_pages;
 
     // special handling: first internal nodes layer
     let mut candidates: Vec<i64> = Vec::new();
     for i in 1..total_pages {
-        let kv_arr: Vec<(i64, i64)> = deserialize_page(leaf_file_path, i * PAGE_SIZE);
+        let kv_arr: Vec<(i64, i64)> = files.read_page(leaf_file_path, i);
         candidates.push(kv_arr[0].0);
     }
 
@@ -27,7 +30,7 @@ pub fn part3_create_b_tree_internal_file(leaf_file_path: &str, internal_file_pat
     let mut internal_levels: Vec<Vec<Vec<i64>>> = Vec::new();
     while !candidates.is_empty() {
         // construct internal layers
-        let curr_level_num_nodes = (num_ptrs + ENTRIES - 1) / ENTRIES; // ceil
+        let curr_level_num_nodes = num_ptrs.div_ceil(ENTRIES); // ceil
 
         let keys_per_node = (num_ptrs - (2 * curr_level_num_nodes)) / curr_level_num_nodes;
         // internal node with idx < excess_keys get an extra key
@@ -80,7 +83,7 @@ pub fn part3_create_b_tree_internal_file(leaf_file_path: &str, internal_file_pat
                 num_offset_pages += 1;
             }
 
-            serialize_kv_to_file(internal_file_path, &node_page_arr);
+            files.append_pages(internal_file_path, &node_page_arr);
             pages_in_front += 1;
         }
     }
@@ -94,6 +97,26 @@ pub fn part3_search_b_tree_sst(
     key: i64,
     buffer: &mut BufferPool,
 ) -> Option<i64> {
+    let kv_arr: Vec<(i64, i64)> =
+        part3_find_leaf_page(leaf_filename, internal_filename, key, buffer);
+    let value: Option<i64> = binary_search_array_start_index(&kv_arr, key).and_then(|i| {
+        if kv_arr[i].0 == key {
+            Some(kv_arr[i].1)
+        } else {
+            None
+        }
+    });
+
+    value
+}
+
+// descends the internal file to the leaf page that holds the key if the run does
+pub fn part3_find_leaf_page(
+    leaf_filename: &str,
+    internal_filename: &str,
+    key: i64,
+    buffer: &mut BufferPool,
+) -> Vec<(i64, i64)> {
     let internal_total_pages: usize = (metadata(internal_filename)
         .expect("Metadata call failed!")
         .len() as usize)
@@ -116,26 +139,17 @@ pub fn part3_search_b_tree_sst(
 
     // leaf file search
     page_idx -= internal_total_pages; // TODO: if filter, + btree_idx return from deserialize_filter
-    let kv_arr: Vec<(i64, i64)> = buffer.find_page(leaf_filename, page_idx * PAGE_SIZE);
-    let value: Option<i64> = binary_search_array_start_index(&kv_arr, key).and_then(|i| {
-        if kv_arr[i].0 == key {
-            Some(kv_arr[i].1)
-        } else {
-            None
-        }
-    });
-
-    value
+    buffer.find_page(leaf_filename, page_idx * PAGE_SIZE)
 }
 
 /////// scan
 
-pub fn part3_scan_b_tree_sst(
+pub fn part3_scan_b_tree_sst<T: ScanOutput + ?Sized>(
     leaf_filename: &str,
     internal_filename: &str,
     key1: i64,
     key2: i64,
-    kv_hash: &mut HashMap<i64, i64>,
+    kv_hash: &mut T,
     buffer: &mut BufferPool,
 ) {
     let internal_total_pages: usize = (metadata(internal_filename)
@@ -163,19 +177,22 @@ pub fn part3_scan_b_tree_sst(
         .len() as usize)
         / PAGE_SIZE;
 
-    let start_page_idx = page_idx - internal_total_pages; // TODO: if filter, + btree_idx return from deserialize_filter
-    let kv_arr: Vec<(i64, i64)> = buffer.find_page(leaf_filename, start_page_idx * PAGE_SIZE);
-    if let Some(start_arr_idx) = binary_search_array_start_index(&kv_arr, key1) {
-        scan_b_tree_file(
-            leaf_filename,
-            leaf_total_pages,
-            start_page_idx,
-            start_arr_idx,
-            key2,
-            kv_hash,
-            buffer,
-        );
-    }
+    let leaf_page_idx = page_idx - internal_total_pages; // TODO: if filter, + btree_idx return from deserialize_filter
+    let kv_arr: Vec<(i64, i64)> = buffer.find_page(leaf_filename, leaf_page_idx * PAGE_SIZE);
+    // when key1 falls in the gap after this leaf the scan begins at the next one
+    let (start_page_idx, start_arr_idx) = match binary_search_array_start_index(&kv_arr, key1) {
+        Some(arr_idx) => (leaf_page_idx, arr_idx),
+        None => (leaf_page_idx + 1, 0_usize),
+    };
+    scan_b_tree_file(
+        leaf_filename,
+        leaf_total_pages,
+        start_page_idx,
+        start_arr_idx,
+        key2,
+        kv_hash,
+        buffer,
+    );
 }
 
 /////
@@ -187,7 +204,7 @@ mod tests {
         part3_create_b_tree_internal_file, part3_scan_b_tree_sst, part3_search_b_tree_sst,
     };
     use crate::storage::serialize_kv_to_file;
-    use crate::storage::BufferPool;
+    use crate::storage::{BufferPool, DiskPages};
 
     use std::collections::HashMap;
     use std::fs::{create_dir_all, remove_dir, remove_file};
@@ -205,12 +222,16 @@ mod tests {
         }
         serialize_kv_to_file(&leaf_filename, &kv_arr);
 
-        part3_create_b_tree_internal_file(&leaf_filename, &internal_filename);
+        part3_create_b_tree_internal_file(
+            &leaf_filename,
+            &internal_filename,
+            &mut DiskPages::new(None),
+        );
 
         let mut buffer = BufferPool::new(16);
         // test get
         assert_eq!(
-            Some(22679 * 2 as i64),
+            Some(22679 * 2_i64),
             part3_search_b_tree_sst(&leaf_filename, &internal_filename, 22679, &mut buffer)
         );
         assert_eq!(
@@ -222,13 +243,13 @@ mod tests {
         part3_scan_b_tree_sst(
             &leaf_filename,
             &internal_filename,
-            333 as i64,
-            9999 as i64,
+            333_i64,
+            9999_i64,
             &mut kv_hash,
             &mut buffer,
         );
         for i in 333..9999 {
-            assert_eq!(i * 2 as i64, *kv_hash.get(&i).unwrap());
+            assert_eq!(i * 2_i64, *kv_hash.get(&i).unwrap());
         }
 
         remove_file(&leaf_filename).expect("Remove file has failed!");
@@ -1,23 +1,279 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    rc::Rc,
+    time::Duration,
+};
 
-/// Trait to generalize the work of different storage methods.
+use crate::{filter::FilterStats, KvError};
+
+/// Trait to generalize the result structures that a scan can fill.
+pub trait ScanOutput {
+    /// Function to add a KV pair found by a scan. Sources are visited newest first, so an already present key
+    /// must be kept as is.
+    /// # Arguments
+    /// * `self` - A mutable ref to the result structure.
+    /// * `key` - The key found.
+    /// * `value` - The value found.
+    fn add(&mut self, key: i64, value: i64);
+    /// Function to return the number of KV pairs currently in the result structure.
+    /// # Arguments
+    /// * `self` - A ref to the result structure.
+    fn count(&self) -> usize;
+}
+
+// The implementation of the `HashMap` as a `ScanOutput`. De-duplicates keys, newest wins.
+impl ScanOutput for HashMap<i64, i64> {
+    fn add(&mut self, key: i64, value: i64) {
+        self.entry(key).or_insert(value);
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+// The implementation of the `BTreeMap` as a `ScanOutput`. De-duplicates keys, newest wins.
+impl ScanOutput for BTreeMap<i64, i64> {
+    fn add(&mut self, key: i64, value: i64) {
+        self.entry(key).or_insert(value);
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+// The implementation of the `Vec` as a `ScanOutput`. No de-duplication, the pairs are kept in visit order.
+impl ScanOutput for Vec<(i64, i64)> {
+    fn add(&mut self, key: i64, value: i64) {
+        self.push((key, value));
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+/// Trait for the files of pages of KV pairs that the `LSMTree` merges and B tree builds read and write, so they can
+/// run on disk or, to test them, in memory. Files are named by their path.
+pub trait PageFiles {
+    /// Function to create the file at `path`, empty, if it does not exist yet.
+    /// # Arguments
+    /// * `self` - A mutable ref to the files.
+    /// * `path` - The path of the file.
+    fn create(&mut self, path: &str);
+    /// Function to return the number of pages of the file at `path`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the files.
+    /// * `path` - The path of the file.
+    fn total_pages(&mut self, path: &str) -> usize;
+    /// Function to return the KV pairs of the page at `page_idx` of the file at `path`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the files.
+    /// * `path` - The path of the file.
+    /// * `page_idx` - The index of the page in the file.
+    fn read_page(&mut self, path: &str, page_idx: usize) -> Vec<(i64, i64)>;
+    /// Function to append `kv_arr` to the file at `path` as whole pages, the last one padded.
+    /// # Arguments
+    /// * `self` - A mutable ref to the files.
+    /// * `path` - The path of the file.
+    /// * `kv_arr` - The KV pairs to append.
+    fn append_pages(&mut self, path: &str, kv_arr: &[(i64, i64)]);
+}
+
+/// Enum of where the value found by a get came from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueSource {
+    /// The value was still in the `Memtable`.
+    Memtable,
+    /// The value was in an SST. Storages without levels report level 0 and the SST index as the run.
+    Sst { level: u32, run: u32 },
+    /// The value was in a storage that does not report where its values are.
+    Storage,
+}
+
+/// Struct of what an `LSMTree` merge did, passed to the callback set with `KVConfig::on_compaction`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CompactionEvent {
+    /// The leaf files of the merged runs and their levels, oldest first.
+    pub inputs: Vec<(String, u32)>,
+    /// The leaf file the runs were merged into, removed if nothing was written to it.
+    pub output: String,
+    /// The level of the merged run.
+    pub output_level: u32,
+    /// The number of KV pairs read from the runs, the older versions of a key and the tombstones included.
+    pub entries_in: u64,
+    /// The number of KV pairs written to the merged run.
+    pub entries_out: u64,
+    /// The number of tombstones dropped, as no older run was left for them to shadow.
+    pub tombstones_dropped: u64,
+    /// How long the merge took, the internal pages of the merged run included.
+    pub duration: Duration,
+}
+
+/// The callback of `KVConfig::on_compaction`, shared by the clones of the `KVConfig`.
+pub type CompactionCallback = Rc<RefCell<Box<dyn FnMut(CompactionEvent)>>>;
+
+/// Trait to generalize the work of different storage methods. Implement it to plug a custom storage into a `Client`
+/// with `Client::open_with_storage`. The contract is:
+/// * Each `flush` gets the `Memtable` contents in strictly ascending key order, tombstones (`i64::MIN` values)
+///   included, and must keep them as one more SST newer than all the previous ones.
+/// * Reads are newest wins: `get` returns the value of the newest SST holding the key, tombstones included, and `scan`
+///   visits SSTs newest first so `ScanOutput::add` keeps the newest value.
+/// * `scan_sorted` returns the range in ascending key order, it is only called while there is at most one SST.
 pub trait DiskStorage {
     /// Function to fetch the value at a particular `key` if it exists.
     /// # Arguments
     /// * `self` - A mutable ref to `DiskStorage` to search.
     /// * `key` - The key who's value is being searched.
     fn get(&mut self, key: i64) -> Option<i64>;
+    /// Function to fetch the value at a particular `key` if it exists, returning an error instead of panicking when
+    /// an SST cannot be read. Storages that cannot detect corrupt pages fall back to `get`.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `key` - The key who's value is being searched.
+    fn try_get(&mut self, key: i64) -> Result<Option<i64>, KvError> {
+        Ok(self.get(key))
+    }
+    /// Function to fetch the value at a particular `key` if it exists, along with where it was found, to debug
+    /// shadowing. Storages that do not report it fall back to `get` and `ValueSource::Storage`.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `key` - The key who's value is being searched.
+    fn get_with_source(&mut self, key: i64) -> Option<(i64, ValueSource)> {
+        self.get(key).map(|value| (value, ValueSource::Storage))
+    }
+    /// Function to return the leaf page of the newest SST holding `key`, else the leaf page it would be in of the
+    /// newest SST whose keys span it, else of the newest SST, with the KV pairs as stored, tombstones included. Only
+    /// paged storages have leaf pages, the others return nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `key` - The key who's leaf page is being searched.
+    fn leaf_page_for_key(&mut self, _key: i64) -> Vec<(i64, i64)> {
+        Vec::new()
+    }
     /// Function to fetch the values at a particular key range if they exists. From `start` to `end` INCLUSIVE.
     /// # Arguments
     /// * `self` - A mutable ref to `DiskStorage` to search.
     /// * `start` - The begining of the scan range (INCLUSIVE).
     /// * `end` - The end of the scan range (INCLUSIVE).
-    /// * `kv_hash` - The HashMap to store the output so we do not have duplicates.
-    fn scan(&mut self, start: i64, end: i64, hash: &mut HashMap<i64, i64>);
+    /// * `hash` - The `ScanOutput` to store the output, a `HashMap` so we do not have duplicates.
+    fn scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput);
+    /// Function to fetch the values at a particular key range like `scan`, returning an error instead of panicking
+    /// when an SST cannot be read, for example one removed during the scan. Storages that cannot detect it fall back
+    /// to `scan`.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `start` - The begining of the scan range (INCLUSIVE).
+    /// * `end` - The end of the scan range (INCLUSIVE).
+    /// * `hash` - The `ScanOutput` to store the output.
+    fn try_scan(&mut self, start: i64, end: i64, hash: &mut dyn ScanOutput) -> Result<(), KvError> {
+        self.scan(start, end, hash);
+        Ok(())
+    }
+    /// Function to fetch the values at a particular key range in ascending key order. From `start` to `end` INCLUSIVE.
+    /// No de-duplication is done, so this should only be called when the storage holds at most one SST.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `start` - The begining of the scan range (INCLUSIVE).
+    /// * `end` - The end of the scan range (INCLUSIVE).
+    fn scan_sorted(&mut self, start: i64, end: i64) -> Vec<(i64, i64)>;
+    /// Function to fetch the values at a particular key range in ascending key order like `scan_sorted`, returning an
+    /// error instead of panicking when an SST cannot be read. Storages that cannot detect it fall back to
+    /// `scan_sorted`.
+    /// # Arguments
+    /// * `self` - A mutable ref to `DiskStorage` to search.
+    /// * `start` - The begining of the scan range (INCLUSIVE).
+    /// * `end` - The end of the scan range (INCLUSIVE).
+    fn try_scan_sorted(&mut self, start: i64, end: i64) -> Result<Vec<(i64, i64)>, KvError> {
+        Ok(self.scan_sorted(start, end))
+    }
     /// Function to flush the current `Memtable` contents into an SST.
     /// # Arguments
     /// * `self` - A mutable ref to the `DiskStorage` to flush.
     /// * `sst_count` - The number of SSTs already in the DB.
     /// * `contents` - The contents that need to be flushed.
     fn flush(&mut self, sst_count: u32, contents: Vec<(i64, i64)>);
+    /// Function to return the bloom filter outcomes of the gets so far. Storages without filters keep the default
+    /// of all zeros.
+    /// # Arguments
+    /// * `self` - A ref to the `DiskStorage`.
+    fn filter_stats(&self) -> FilterStats {
+        FilterStats::default()
+    }
+    /// Function to return the KV pairs of the run at a `level`, in ascending key order and tombstones included.
+    /// Only level based storages have levels, the others return nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to read.
+    /// * `level` - The level to read, starting from 1.
+    fn iter_level(&mut self, _level: u32) -> Vec<(i64, i64)> {
+        Vec::new()
+    }
+    /// Function to regenerate the bloom filter of every SST from its KV pairs, with the filter settings of the
+    /// storage, such as after they were lost. Storages without filters do nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to rebuild the filters of.
+    fn rebuild_filters(&mut self) {}
+    /// Function to return the number of SSTs the storage already holds when a `Client` is opened on it. Storages
+    /// starting empty keep the default of 0.
+    /// # Arguments
+    /// * `self` - A ref to the `DiskStorage`.
+    fn initial_sst_count(&self) -> u32 {
+        0
+    }
+    /// Function to merge the SSTs after a flush once there are too many of them. Returns the number of SSTs left,
+    /// the next flush is numbered from it. Storages that do not merge keep all of their SSTs.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to merge.
+    /// * `sst_count` - The number of SSTs in the DB, the last flush included.
+    fn compact(&mut self, sst_count: u32) -> u32 {
+        sst_count
+    }
+    /// Function to pull into the buffer pool the pages a scan from `start` to `end` (both INCLUSIVE) reads, at most as
+    /// many as the pool holds, so the next reads of the range are cached. Returns the number of pages read. Storages
+    /// without a buffer pool read nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to warm.
+    /// * `start` - The begining of the range to warm (INCLUSIVE).
+    /// * `end` - The end of the range to warm (INCLUSIVE).
+    fn warm_range(&mut self, _start: i64, _end: i64) -> usize {
+        0
+    }
+    /// Function to return the `(sst_name, page_offset)` of every page in the buffer pool, for debugging. Storages
+    /// without a buffer pool return nothing.
+    /// # Arguments
+    /// * `self` - A ref to the `DiskStorage`.
+    fn cached_pages(&self) -> Vec<(String, usize)> {
+        Vec::new()
+    }
+    /// Function to drop the pages of the SST `sst_index` from the buffer pool and close its cached file handle, so
+    /// its next read goes to disk. Storages without such caches do nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to evict from.
+    /// * `sst_index` - The index of the SST in its file name.
+    fn evict_sst(&mut self, _sst_index: u32) {}
+    /// Function to free the memory kept from busier times, such as the buckets of an emptier buffer pool, without
+    /// dropping any cached data. Storages without such memory do nothing.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to shrink.
+    fn shrink_to_fit(&mut self) {}
+    /// Function to write an already sorted run directly at a `level`, skipping the levels below it.
+    /// Only supported by level based storages, the others return `KvError::Unsupported`.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to ingest into.
+    /// * `sorted` - The KV pairs of the run, in strictly ascending key order.
+    /// * `level` - The level to write the run at.
+    fn ingest_sorted_run(&mut self, _sorted: Vec<(i64, i64)>, _level: u32) -> Result<(), KvError> {
+        Err(KvError::Unsupported("ingest_sorted_run".to_string()))
+    }
+    /// Function to drop every KV pair from `pivot` up, tombstones included. Each SST holding some is rewritten with
+    /// only its KV pairs below `pivot`, one SST at a time. Returns the new SST count of the storage, or
+    /// `KvError::Unsupported` for storages that cannot be rewritten.
+    /// # Arguments
+    /// * `self` - A mutable ref to the `DiskStorage` to truncate.
+    /// * `pivot` - The smallest key dropped.
+    fn truncate_from(&mut self, _pivot: i64) -> Result<u32, KvError> {
+        Err(KvError::Unsupported("truncate_from".to_string()))
+    }
 }
@@ -1,5 +1,3 @@
-use kv;
-
 #[test]
 fn lsm_get() {
     let mut db = kv::Client::open(
@@ -42,3 +40,31 @@ fn lsm_scan() {
         assert!(pair.0 + 5 == pair.1)
     }
 }
+
+#[test]
+fn lsm_get_every_tree_size() {
+    let mut db = kv::Client::open(
+        "lsm_tree_sizes".to_string(),
+        kv::KVConfig::default()
+            .memtable_size(4)
+            .storage_type(kv::StorageType::LSMTree)
+            .cleanup(true),
+    );
+
+    // each run holds 3 new keys and a new version of key 0
+    for run in 1..=64 {
+        db.put(0, run);
+        for i in 0..3 {
+            db.put(run * 3 + i, run);
+        }
+
+        assert_eq!(db.get(0), Some(run), "tree size {}", run);
+        for older in 1..=run {
+            for i in 0..3 {
+                assert_eq!(db.get(older * 3 + i), Some(older), "tree size {}", run);
+            }
+        }
+        assert_eq!(db.get(run * 3 + 3), None, "tree size {}", run);
+        assert_eq!(db.scan(0, run * 3 + 2).len() as i64, run * 3 + 1);
+    }
+}